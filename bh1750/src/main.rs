@@ -6,40 +6,34 @@ use embassy_executor::Spawner;
 use embassy_nrf::gpio::{Level, Output, OutputDrive};
 use embassy_nrf::peripherals;
 use embassy_nrf::{bind_interrupts, twim};
-use embassy_nrf_ws2812_pwm::Ws2812;
+use embassy_nrf_ws2812_pwm::modulation::{Curve, DarknessGate, Modulation, Target};
+use embassy_nrf_ws2812_pwm::{InterruptHandler, ws2812};
 use embassy_time::{Delay, Timer};
-use libm::{logf, roundf};
 use smart_leds::colors;
 use smart_leds::{SmartLedsWriteAsync as _, brightness};
-use static_cell::{ConstStaticCell, StaticCell};
+use static_cell::ConstStaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
     TWISPI0 => twim::InterruptHandler<peripherals::TWISPI0>;
+    PWM0 => InterruptHandler<peripherals::PWM0>;
 });
 
-pub fn lux_to_u8(lux: f32, min_lux: f32, max_lux: f32, min_out: u8, max_out: u8) -> u8 {
-    let clamped = lux.clamp(min_lux, max_lux);
-
-    // Precalculate constants with +1 to avoid log(0)
-    let ln_min = logf(min_lux + 1.0);
-    let ln_max = logf(max_lux + 1.0);
-    let ln_val = logf(clamped + 1.0);
+const NUM_LEDS: usize = 1;
 
-    // Normalize to 0..1 with logarithmic curve
-    let norm = (ln_val - ln_min) / (ln_max - ln_min);
-    let inv = 1.0 - norm;
+/// Mapping from lux to LED brightness.
+///
+/// The log curve suits most rooms, use `Curve::Linear` or a breakpoint
+/// table such as `Curve::Breakpoints(&[(5.0, 0.0), (200.0, 0.6), (2000.0, 1.0)])`
+/// to tune it for a particular room or sensor.
+const CURVE: Curve = Curve::Logarithmic;
 
-    // Map to output range
-    let out_range = (max_out - min_out) as f32;
-    let out = min_out as f32 + inv * out_range;
-
-    roundf(out).clamp(u8::MIN as f32, u8::MAX as f32) as u8
-}
-
-const NUM_LEDS: usize = 1;
-const BUFFER_SIZE: usize = NUM_LEDS * 24;
-static LED_BUFFER: StaticCell<[u16; BUFFER_SIZE]> = StaticCell::new();
+/// Only light the LED when it is dark, for window or holiday lighting,
+/// instead of following the light level.
+///
+/// Set to for example `Some(DarknessGate::new(10.0, 30.0))` to turn
+/// on below 10 lux and off again above 30 lux.
+const DARK_ONLY: Option<DarknessGate> = None;
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
@@ -51,8 +45,7 @@ async fn main(_spawner: Spawner) {
     led.set_low();
 
     // Prepare the WS2812 LED
-    let buf = LED_BUFFER.init([0u16; BUFFER_SIZE]);
-    let mut ws: Ws2812<_> = Ws2812::new(p.PWM0, p.P0_13, buf);
+    let mut ws = ws2812!(p.PWM0, Irqs, p.P0_13, NUM_LEDS).expect("to create WS2812 driver");
 
     // Create I2C instance
     static RAM_BUFFER: ConstStaticCell<[u8; 16]> = ConstStaticCell::new([0; 16]);
@@ -71,20 +64,22 @@ async fn main(_spawner: Spawner) {
     bh1750
         .start_continuous_measurement(Resolution::High)
         .expect("to start measuring light sensor");
-    let mut smoothed_lux: Option<f32> = None;
+
+    // Brighter rooms get a brighter LED, 0.10–0.15 smoothing recommended
+    let mut dimmer =
+        Modulation::new(Target::Brightness, CURVE, 5.0, 2000.0, 0, 250).with_smoothing(0.12);
+    let mut dark_only = DARK_ONLY;
 
     loop {
         match bh1750.get_current_measurement(Resolution::High) {
             Ok(lux) => {
                 defmt::debug!("Lux: {}", lux);
 
-                let s_lux = smoothed_lux.get_or_insert(lux);
-
-                // 0.10–0.15 recommended
-                let alpha = 0.12;
-                *s_lux = *s_lux + alpha * (lux - *s_lux);
-
-                let pwm = u8::MAX - lux_to_u8(*s_lux, 5.0, 2000.0, 5, 255);
+                let pwm = match dark_only.as_mut() {
+                    Some(gate) if gate.update(lux) => 250,
+                    Some(_) => 0,
+                    None => dimmer.update(lux),
+                };
 
                 defmt::debug!("PWM: {}", pwm);
 
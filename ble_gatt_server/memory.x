@@ -0,0 +1,8 @@
+MEMORY
+{
+  /* NOTE 1 K = 1 KiBi = 1024 bytes */
+  /* These values correspond to the NRF52840 */
+  /* The last 8K hold the settings, see src/settings.rs */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 1016K
+  RAM : ORIGIN = 0x20000000, LENGTH = 256K
+}
@@ -0,0 +1,68 @@
+/// Define a `#[repr(u8)]` command enum written to a GATT characteristic.
+///
+/// Besides the enum this generates `TryFrom<u8>`, `From<Enum> for u8`,
+/// `defmt::Format` and the trouble `AsGatt` and `FromGatt` conversions
+/// so the enum can be used directly as a characteristic value.
+///
+/// ```ignore
+/// command_enum! {
+///     pub enum Command {
+///         Stop = 0,
+///         Start = 1,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! command_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $value:literal),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, ::defmt::Format)]
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant = $value),+
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = &'static str;
+
+            fn try_from(value: u8) -> Result<Self, Self::Error> {
+                Ok(match value {
+                    $($value => $name::$variant,)+
+                    _ => return Err(concat!("invalid ", stringify!($name))),
+                })
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                value as u8
+            }
+        }
+
+        impl ::trouble_host::prelude::AsGatt for $name {
+            const MIN_SIZE: usize = 1;
+            const MAX_SIZE: usize = 1;
+
+            fn as_gatt(&self) -> &[u8] {
+                // SAFETY: the enum is `repr(u8)` without fields
+                // so it is a single initialized byte
+                unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, 1) }
+            }
+        }
+
+        impl ::trouble_host::prelude::FromGatt for $name {
+            fn from_gatt(data: &[u8]) -> Result<Self, ::trouble_host::prelude::FromGattError> {
+                match data {
+                    [value] => Self::try_from(*value)
+                        .map_err(|_| ::trouble_host::prelude::FromGattError::InvalidCharacter),
+                    _ => Err(::trouble_host::prelude::FromGattError::InvalidLength),
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,41 @@
+use crate::led_mode::LedMode;
+use embassy_time::Duration;
+
+/// What to do with the LEDs when the central disconnects.
+///
+/// Stored in the disconnect characteristic as three bytes,
+/// `[0]` keeps the current mode, `[1, mode]` reverts to a mode and
+/// `[2, secs_lo, secs_hi]` turns the LEDs off after a timeout unless
+/// a central reconnects first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DisconnectAction {
+    Keep,
+    Revert(LedMode),
+    OffAfter(Duration),
+}
+
+impl DisconnectAction {
+    /// Parse the characteristic value.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(match bytes {
+            [0, ..] => DisconnectAction::Keep,
+            [1, mode, ..] => DisconnectAction::Revert(LedMode::try_from(*mode).ok()?),
+            [2, lo, hi, ..] => DisconnectAction::OffAfter(Duration::from_secs(
+                u16::from_le_bytes([*lo, *hi]) as u64,
+            )),
+            _ => return None,
+        })
+    }
+
+    /// Characteristic value.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        match self {
+            DisconnectAction::Keep => [0, 0, 0],
+            DisconnectAction::Revert(mode) => [1, *mode as u8, 0],
+            DisconnectAction::OffAfter(timeout) => {
+                let [lo, hi] = (timeout.as_secs().min(u16::MAX as u64) as u16).to_le_bytes();
+                [2, lo, hi]
+            }
+        }
+    }
+}
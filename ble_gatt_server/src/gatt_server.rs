@@ -1,18 +1,53 @@
+use crate::disconnect::DisconnectAction;
+use crate::heartbeat::HEARTBEAT;
 use crate::led_mode::LedMode;
+use crate::load::{FEEDS_MAX, LOAD};
+use crate::scene::{NAME_MAX, SCENE_NAMES_LEN, Scene};
+use crate::settings::SETTINGS;
 use defmt::{info, warn};
 use embassy_futures::join::join;
+use embassy_futures::select::{Either, select};
+use embassy_nrf_ws2812_pwm::effects::Strobe;
+use embassy_nrf_ws2812_pwm::palette::GradientPalette;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use smart_leds::RGB8;
 use trouble_host::prelude::*;
 
 pub static NOTIFIER: Signal<CriticalSectionRawMutex, LedMode> = Signal::new();
 
+/// LED index written to the calibration characteristic.
+pub static CALIBRATION: Signal<CriticalSectionRawMutex, u8> = Signal::new();
+
+/// Color seen during the color order test (0 red, 1 green, 2 blue).
+pub static COLOR_TEST: Signal<CriticalSectionRawMutex, u8> = Signal::new();
+
+/// Gradient palette written to the palette characteristic.
+pub static PALETTE: Signal<CriticalSectionRawMutex, GradientPalette> = Signal::new();
+
+/// Strobe written to the alert characteristic, already
+/// clamped to the flash safety limits.
+pub static ALERT: Signal<CriticalSectionRawMutex, Strobe> = Signal::new();
+
 /// Max number of connections
 const CONNECTIONS_MAX: usize = 1;
 
 /// Max number of L2CAP channels.
 const L2CAP_CHANNELS_MAX: usize = 2; // Signal + att
 
+/// Random static address of the lamp, remotes connect to it.
+pub const ADDRESS: [u8; 6] = [0xff, 0x8f, 0x1a, 0x05, 0xe4, 0xff];
+
+/// Company identifier for the state beacon (reserved by the Bluetooth SIG for testing).
+const BEACON_COMPANY_ID: u16 = 0xffff;
+
+/// Mode byte of the save scene characteristic saving the current mode.
+const CURRENT_MODE: u8 = 0xff;
+
+/// How often the heartbeat characteristic is updated while connected.
+const HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
+
 // GATT Server definition
 #[gatt_server]
 struct Server {
@@ -22,18 +57,56 @@ struct Server {
 /// Battery service
 #[gatt_service(uuid = service::GENERIC_MEDIA_CONTROL)]
 struct LedService {
-    #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 4])]
+    #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 8])]
     #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "led", read, value = "LED mode")]
     #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100000", write, read, notify)]
     mode: u8,
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "calibration", read, value = "Calibration LED index")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100001", write, read)]
+    calibration: u8,
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "palette", read, value = "WLED gradient palette")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100002", write)]
+    palette: [u8; 64],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "color_test", read, value = "Color seen (0 red, 1 green, 2 blue)")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100003", write)]
+    color_test: u8,
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "scene", read, value = "Scene index")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100004", write, read)]
+    scene: u8,
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "scene_names", read, value = "Scene names (NUL separated)")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100005", read)]
+    scene_names: [u8; SCENE_NAMES_LEN],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "disconnect", read, value = "Action on disconnect")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100006", write, read)]
+    disconnect: [u8; 3],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "heartbeat", read, value = "Frame counter and CRC-32 of the last frame")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100007", read, notify)]
+    heartbeat: [u8; 8],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "alert", read, value = "Alert strobe (RGB, period ms LE, duty %)")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100008", write)]
+    alert: [u8; 6],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "load", read, value = "Current per power feed (mA, u16 LE)")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100009", read, notify)]
+    load: [u8; 2 * FEEDS_MAX],
+    #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, name = "save_scene", read, value = "Save scene (index, mode or 0xff for the current one, name, no name removes it)")]
+    #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb00110000a", write)]
+    save_scene: [u8; 2 + NAME_MAX],
 }
 
 /// Run the BLE stack.
-pub async fn run<C>(controller: C, name: &str, mode: LedMode)
+///
+/// When `beacon` is set the current LED mode is included in the
+/// advertising manufacturer data so observers can read the state
+/// without connecting.
+///
+/// What happens when the central disconnects is the [DisconnectAction]
+/// of the [settings](crate::settings), centrals can change it with the
+/// disconnect characteristic.
+pub async fn run<C>(controller: C, name: &str, mode: LedMode, beacon: bool)
 where
     C: Controller,
 {
-    let address: Address = Address::random([0xff, 0x8f, 0x1a, 0x05, 0xe4, 0xff]);
+    let address: Address = Address::random(ADDRESS);
     info!("address = {:?}", address);
 
     let mut resources: HostResources<DefaultPacketPool, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX> =
@@ -54,13 +127,53 @@ where
 
     let handle = &server.led_service.mode;
     server.set(handle, &(mode as u8)).unwrap();
+    server
+        .set(
+            &server.led_service.scene_names,
+            &SETTINGS.get().scenes.names(),
+        )
+        .unwrap();
+    server
+        .set(
+            &server.led_service.disconnect,
+            &SETTINGS.get().disconnect.to_bytes(),
+        )
+        .unwrap();
 
     let _ = join(ble_task(runner), async {
+        // Deadline for turning the LEDs off after a disconnect
+        let mut off_at = None;
         loop {
-            match advertise(name, &mut peripheral, &server).await {
+            let advertiser = advertise(name, &mut peripheral, &server, beacon);
+            let conn = match off_at {
+                Some(at) => match select(advertiser, Timer::at(at)).await {
+                    Either::First(conn) => conn,
+                    Either::Second(_) => {
+                        info!("[adv] no reconnection, turning LEDs off");
+                        set_mode(&server, LedMode::Off);
+                        off_at = None;
+                        continue;
+                    }
+                },
+                None => advertiser.await,
+            };
+            off_at = None;
+
+            match conn {
                 Ok(conn) => {
                     let a = gatt_events_task(&server, &conn);
-                    let _ = a.await;
+                    let b = heartbeat_task(&server, &conn);
+                    let _ = select(a, b).await;
+
+                    let action = SETTINGS.get().disconnect;
+                    info!("[gatt] disconnect action: {:?}", action);
+                    match action {
+                        DisconnectAction::Keep => {}
+                        DisconnectAction::Revert(mode) => set_mode(&server, mode),
+                        DisconnectAction::OffAfter(timeout) => {
+                            off_at = Some(Instant::now() + timeout)
+                        }
+                    }
                 }
                 Err(e) => {
                     let e = defmt::Debug2Format(&e);
@@ -72,6 +185,62 @@ where
     .await;
 }
 
+/// Change the LED mode from the server side.
+fn set_mode(server: &Server<'_>, mode: LedMode) {
+    let _ = server.set(&server.led_service.mode, &(mode as u8));
+    NOTIFIER.signal(mode);
+}
+
+/// Save, rename or remove a scene as written to the save scene
+/// characteristic and update the scene names.
+fn edit_scene(server: &Server<'_>, data: &[u8]) {
+    let &[index, mode, ref name @ ..] = data else {
+        warn!("invalid scene, ignoring");
+        return;
+    };
+    // Clients may pad the name with NUL bytes
+    let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+    let mut scenes = SETTINGS.get().scenes;
+    let changed = if name.is_empty() {
+        scenes.remove(index)
+    } else {
+        let mode = match mode {
+            CURRENT_MODE => server.get(&server.led_service.mode).unwrap_or_default(),
+            mode => mode,
+        };
+        let scene = core::str::from_utf8(name)
+            .ok()
+            .zip(LedMode::try_from(mode).ok())
+            .and_then(|(name, mode)| Scene::new(name, mode));
+        scene.is_some_and(|scene| scenes.save(index, scene))
+    };
+    if !changed {
+        warn!("invalid scene, ignoring");
+        return;
+    }
+    SETTINGS.update(|settings| settings.scenes = scenes);
+    let _ = server.set(&server.led_service.scene_names, &scenes.names());
+}
+
+/// Notify the heartbeat and load periodically until the connection fails.
+async fn heartbeat_task<P: PacketPool>(server: &Server<'_>, conn: &GattConnection<'_, '_, P>) {
+    let heartbeat = server.led_service.heartbeat;
+    let load = server.led_service.load;
+    loop {
+        let value = HEARTBEAT.to_bytes();
+        if heartbeat.notify(conn, &value).await.is_err() {
+            info!("[gatt] heartbeat notify failed, stopping");
+            break;
+        }
+        let value = LOAD.to_bytes();
+        if load.notify(conn, &value).await.is_err() {
+            info!("[gatt] load notify failed, stopping");
+            break;
+        }
+        Timer::after(HEARTBEAT_PERIOD).await;
+    }
+}
+
 async fn ble_task<C: Controller, P: PacketPool>(mut runner: Runner<'_, C, P>) {
     loop {
         if let Err(e) = runner.run().await {
@@ -90,6 +259,13 @@ async fn gatt_events_task<P: PacketPool>(
     conn: &GattConnection<'_, '_, P>,
 ) -> Result<(), Error> {
     let mode = server.led_service.mode;
+    let calibration = server.led_service.calibration;
+    let palette = server.led_service.palette;
+    let color_test = server.led_service.color_test;
+    let scene = server.led_service.scene;
+    let alert = server.led_service.alert;
+    let save_scene = server.led_service.save_scene;
+    let disconnect = server.led_service.disconnect;
     let reason = loop {
         match conn.next().await {
             GattConnectionEvent::Disconnected { reason } => break reason,
@@ -108,11 +284,84 @@ async fn gatt_events_task<P: PacketPool>(
                                 event.data()
                             );
 
-                            if let Ok(mode) = LedMode::try_from(event.data()[0]) {
+                            if let Ok(mode) = LedMode::from_gatt(event.data()) {
                                 NOTIFIER.signal(mode);
                             } else {
                                 warn!("invalid LED mode, ignoring");
                             }
+                        } else if event.handle() == calibration.handle {
+                            info!(
+                                "[gatt] Write Event to calibration Characteristic: {:?}",
+                                event.data()
+                            );
+                            CALIBRATION.signal(event.data()[0]);
+                        } else if event.handle() == palette.handle {
+                            info!(
+                                "[gatt] Write Event to palette Characteristic: {:?}",
+                                event.data()
+                            );
+                            match GradientPalette::from_wled(event.data()) {
+                                Ok(new_palette) => PALETTE.signal(new_palette),
+                                Err(e) => {
+                                    warn!(
+                                        "invalid palette, ignoring: {:?}",
+                                        defmt::Debug2Format(&e)
+                                    )
+                                }
+                            }
+                        } else if event.handle() == color_test.handle {
+                            info!(
+                                "[gatt] Write Event to color_test Characteristic: {:?}",
+                                event.data()
+                            );
+                            COLOR_TEST.signal(event.data()[0]);
+                        } else if event.handle() == scene.handle {
+                            info!(
+                                "[gatt] Write Event to scene Characteristic: {:?}",
+                                event.data()
+                            );
+                            let scenes = SETTINGS.get().scenes;
+                            match scenes.get(event.data()[0]) {
+                                Some(scene) => {
+                                    info!("scene: {}", scene.name());
+                                    NOTIFIER.signal(scene.mode);
+                                }
+                                None => warn!("invalid scene, ignoring"),
+                            }
+                        } else if event.handle() == alert.handle {
+                            info!(
+                                "[gatt] Write Event to alert Characteristic: {:?}",
+                                event.data()
+                            );
+                            match event.data() {
+                                &[r, g, b, lo, hi, duty, ..] => {
+                                    // Strobe::new clamps the period and duty
+                                    let period = u16::from_le_bytes([lo, hi]) as u64;
+                                    ALERT.signal(Strobe::new(
+                                        RGB8::new(r, g, b),
+                                        Duration::from_millis(period),
+                                        duty,
+                                    ));
+                                }
+                                _ => warn!("invalid alert, ignoring"),
+                            }
+                        } else if event.handle() == save_scene.handle {
+                            info!(
+                                "[gatt] Write Event to save_scene Characteristic: {:?}",
+                                event.data()
+                            );
+                            edit_scene(server, event.data());
+                        } else if event.handle() == disconnect.handle {
+                            info!(
+                                "[gatt] Write Event to disconnect Characteristic: {:?}",
+                                event.data()
+                            );
+                            match DisconnectAction::from_bytes(event.data()) {
+                                Some(action) => {
+                                    SETTINGS.update(|settings| settings.disconnect = action)
+                                }
+                                None => warn!("invalid disconnect action, ignoring"),
+                            }
                         }
                     }
                     _ => {}
@@ -136,9 +385,10 @@ async fn advertise<'values, 'server, C: Controller>(
     name: &'values str,
     peripheral: &mut Peripheral<'values, C, DefaultPacketPool>,
     server: &'server Server<'values>,
+    beacon: bool,
 ) -> Result<GattConnection<'values, 'server, DefaultPacketPool>, BleHostError<C::Error>> {
     let mut advertiser_data = [0; 31];
-    let len = AdStructure::encode_slice(
+    let mut len = AdStructure::encode_slice(
         &[
             AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
             AdStructure::ServiceUuids16(&[[0x49, 0x18]]),
@@ -146,6 +396,16 @@ async fn advertise<'values, 'server, C: Controller>(
         ],
         &mut advertiser_data[..],
     )?;
+    if beacon {
+        let state = [server.get(&server.led_service.mode).unwrap_or_default()];
+        len += AdStructure::encode_slice(
+            &[AdStructure::ManufacturerSpecificData {
+                company_identifier: BEACON_COMPANY_ID,
+                payload: &state,
+            }],
+            &mut advertiser_data[len..],
+        )?;
+    }
     let advertiser = peripheral
         .advertise(
             &Default::default(),
@@ -0,0 +1,79 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use smart_leds::RGB8;
+
+/// Frames written by the LED task and the CRC of the last one.
+pub static HEARTBEAT: Heartbeat = Heartbeat::new();
+
+/// Progress of the LED task for remote supervision.
+///
+/// Stored in the heartbeat characteristic as the frame counter
+/// followed by the CRC-32 of the RGB bytes of the last frame, both
+/// little endian. A counter that stops increasing means the LED task
+/// is hung, a CRC that never changes means the animation is stuck.
+pub struct Heartbeat {
+    state: Mutex<CriticalSectionRawMutex, Cell<(u32, u32)>>,
+}
+
+impl Heartbeat {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(Cell::new((0, 0))),
+        }
+    }
+
+    /// Record a written frame.
+    pub fn frame(&self, crc: u32) {
+        self.state.lock(|state| {
+            let (frames, _) = state.get();
+            state.set((frames.wrapping_add(1), crc));
+        });
+    }
+
+    /// Characteristic value.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let (frames, crc) = self.state.lock(Cell::get);
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&frames.to_le_bytes());
+        bytes[4..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+/// CRC-32 (IEEE) of a frame, computed while it is written.
+pub struct FrameCrc(u32);
+
+impl FrameCrc {
+    /// Start the CRC of a frame.
+    pub const fn new() -> Self {
+        Self(u32::MAX)
+    }
+
+    /// Add a color to the CRC.
+    pub fn update(&mut self, color: RGB8) {
+        self.update_bytes(&[color.r, color.g, color.b]);
+    }
+
+    /// Add bytes to the CRC.
+    pub fn update_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+
+    /// CRC of the colors added so far.
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for FrameCrc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,4 +1,12 @@
 #![no_std]
 
+pub mod command;
+pub mod disconnect;
 pub mod gatt_server;
+pub mod heartbeat;
 pub mod led_mode;
+pub mod load;
+pub mod remote;
+pub mod scene;
+pub mod settings;
+pub mod status_led;
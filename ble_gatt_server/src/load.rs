@@ -0,0 +1,46 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// Max number of power feeds reported.
+pub const FEEDS_MAX: usize = 4;
+
+/// Estimated current per power feed of the last frame.
+pub static LOAD: Load = Load::new();
+
+/// Load of the power feeds for installers.
+///
+/// Stored in the load characteristic as the estimated current of each
+/// feed in mA, u16 little endian, unused feeds are zero. Compare the
+/// feeds to check the load is spread across the injection points.
+pub struct Load {
+    feeds: Mutex<CriticalSectionRawMutex, Cell<[u16; FEEDS_MAX]>>,
+}
+
+impl Load {
+    const fn new() -> Self {
+        Self {
+            feeds: Mutex::new(Cell::new([0; FEEDS_MAX])),
+        }
+    }
+
+    /// Record the current of each feed of a written frame in mA,
+    /// feeds past [FEEDS_MAX] are ignored.
+    pub fn frame(&self, currents: &[u32]) {
+        let mut feeds = [0; FEEDS_MAX];
+        for (feed, &current) in feeds.iter_mut().zip(currents) {
+            *feed = current.min(u16::MAX as u32) as u16;
+        }
+        self.feeds.lock(|state| state.set(feeds));
+    }
+
+    /// Characteristic value.
+    pub fn to_bytes(&self) -> [u8; 2 * FEEDS_MAX] {
+        let feeds = self.feeds.lock(Cell::get);
+        let mut bytes = [0; 2 * FEEDS_MAX];
+        for (chunk, feed) in bytes.chunks_exact_mut(2).zip(feeds) {
+            chunk.copy_from_slice(&feed.to_le_bytes());
+        }
+        bytes
+    }
+}
@@ -1,40 +1,138 @@
 #![no_std]
 #![no_main]
 
-use ble_gatt_server::gatt_server::NOTIFIER;
+use ble_gatt_server::gatt_server::{ALERT, CALIBRATION, COLOR_TEST, NOTIFIER, PALETTE};
+use ble_gatt_server::heartbeat::{FrameCrc, HEARTBEAT};
+use ble_gatt_server::load::LOAD;
+use ble_gatt_server::settings::{self, SETTINGS, SettingsStore};
+use ble_gatt_server::status_led::StatusLed;
 use ble_gatt_server::{gatt_server::run, led_mode::LedMode};
+use core::sync::atomic::{AtomicU8, Ordering};
 use defmt::unwrap;
+use defmt_rtt as _;
 use embassy_executor::Spawner;
-use embassy_futures::join::join;
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_nrf::gpio::{Level, Output, OutputDrive};
+use embassy_nrf::interrupt;
+use embassy_nrf::interrupt::Priority;
 use embassy_nrf::mode::Async;
 use embassy_nrf::peripherals;
 use embassy_nrf::{bind_interrupts, rng};
-use embassy_nrf_ws2812_pwm::Ws2812;
-use embassy_time::{Duration, Timer};
-use nrf_sdc::mpsl::MultiprotocolServiceLayer;
+use embassy_nrf_ws2812_pwm::brownout::{self, Threshold};
+use embassy_nrf_ws2812_pwm::builder::Ws2812Builder;
+use embassy_nrf_ws2812_pwm::color_order::{Channel, ColorOrder};
+use embassy_nrf_ws2812_pwm::diagnostics;
+use embassy_nrf_ws2812_pwm::effects::{Effect, Rainbow, Strobe};
+use embassy_nrf_ws2812_pwm::handoff;
+use embassy_nrf_ws2812_pwm::palette::GradientPalette;
+#[cfg(feature = "panic-led")]
+use embassy_nrf_ws2812_pwm::panic_led;
+use embassy_nrf_ws2812_pwm::power::PowerModel;
+use embassy_nrf_ws2812_pwm::runner::{Frame, FrameChannel, Ws2812Handle, Ws2812Runner};
+use embassy_nrf_ws2812_pwm::{Samples, Ws2812, priority_executor};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use nrf_sdc::mpsl::{Flash, MultiprotocolServiceLayer, SessionMem};
 use nrf_sdc::{self as sdc, mpsl};
+#[cfg(not(feature = "panic-led"))]
+use panic_probe as _;
 use smart_leds::colors;
-use smart_leds::{
-    RGB8, SmartLedsWriteAsync as _, brightness,
-    hsv::{Hsv, hsv2rgb},
-};
+use smart_leds::{RGB8, SmartLedsWriteAsync as _, brightness};
 use static_cell::StaticCell;
 use trouble_host::prelude::*;
-use {defmt_rtt as _, panic_probe as _};
 
 bind_interrupts!(struct Irqs {
     RNG => rng::InterruptHandler<peripherals::RNG>;
+    PWM0 => embassy_nrf_ws2812_pwm::InterruptHandler<peripherals::PWM0>;
     EGU0_SWI0 => nrf_sdc::mpsl::LowPrioInterruptHandler;
-    CLOCK_POWER => nrf_sdc::mpsl::ClockInterruptHandler;
+    CLOCK_POWER => brownout::InterruptHandler, nrf_sdc::mpsl::ClockInterruptHandler;
     RADIO => nrf_sdc::mpsl::HighPrioInterruptHandler;
     TIMER0 => nrf_sdc::mpsl::HighPrioInterruptHandler;
     RTC0 => nrf_sdc::mpsl::HighPrioInterruptHandler;
 });
 
 const NUM_LEDS: usize = 8;
-const BUFFER_SIZE: usize = NUM_LEDS * 24;
-static LED_BUFFER: StaticCell<[u16; BUFFER_SIZE]> = StaticCell::new();
+static LED_BUFFER: StaticCell<Samples<NUM_LEDS>> = StaticCell::new();
+
+/// Current model of the LEDs for the load characteristic.
+const POWER_MODEL: PowerModel = PowerModel::WS2812B;
+
+/// Last LED powered by each feed, power is injected at both ends.
+const POWER_FEEDS: [usize; 2] = [NUM_LEDS / 2, NUM_LEDS];
+
+/// Supply voltage below which the strip is blanked.
+const BROWNOUT_THRESHOLD: Threshold = Threshold::V28;
+
+/// Interrupt priority of the LED tasks.
+///
+/// Running the LED tasks on an interrupt executor lets them preempt the
+/// BLE host running in thread mode, pick a lower priority (higher number)
+/// to favor the radio or `None` to share the thread mode executor.
+/// The MPSL reserves priorities 0, 1 and 4 so they must not be used.
+const LED_PRIORITY: Option<Priority> = Some(Priority::P6);
+
+priority_executor!(LED_EXECUTOR, EGU1_SWI1);
+
+/// Frames queued by the LED manager for the runner.
+const FRAME_DEPTH: usize = 2;
+
+static FRAMES: FrameChannel<NUM_LEDS, FRAME_DEPTH> = FrameChannel::new();
+
+/// Consecutive failed writes, counted by the runner.
+static FAILURES: AtomicU8 = AtomicU8::new(0);
+
+/// Signaled by the runner when a write fails.
+static WRITE_FAILED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+#[embassy_executor::task]
+async fn ws2812_task(mut runner: Ws2812Runner<'static, 'static, NUM_LEDS, FRAME_DEPTH>) -> ! {
+    brownout::enable(BROWNOUT_THRESHOLD, Irqs);
+    let _ = select(write_frames(&mut runner), brownout::wait()).await;
+
+    // The supply is failing, blank the strip and stop writing
+    // frames, the manager stalls once the channel is full.
+    // Pending settings are written while there is power left
+    defmt::warn!("power failure warning, blanking LEDs");
+    brownout::blank(runner.ws());
+    SETTINGS.flush();
+    core::future::pending().await
+}
+
+/// Write the frames of the LED manager, tracking failed writes
+/// and updating the heartbeat and load.
+///
+/// The load is metered from the frame as encoded, after the
+/// brightness and gamma of the driver.
+async fn write_frames(runner: &mut Ws2812Runner<'static, 'static, NUM_LEDS, FRAME_DEPTH>) -> ! {
+    loop {
+        match runner.write_next().await {
+            Ok(()) => {
+                FAILURES.store(0, Ordering::Relaxed);
+                let mut crc = FrameCrc::new();
+                runner.colors().for_each(|color| crc.update(color));
+                HEARTBEAT.frame(crc.finish());
+                LOAD.frame(&runner.ws().estimate_feed_current_ma(POWER_FEEDS));
+            }
+            Err(e) => {
+                let failures = FAILURES.load(Ordering::Relaxed).saturating_add(1);
+                FAILURES.store(failures, Ordering::Relaxed);
+                WRITE_FAILED.signal(());
+                defmt::warn!("LED write failed ({}): {:?}", failures, e);
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn led_task(mut leds: Leds, mode: LedMode) -> ! {
+    led_manager(&mut leds, mode).await
+}
+
+#[embassy_executor::task]
+async fn settings_task(store: SettingsStore<Flash<'static>>) -> ! {
+    settings::run(store).await
+}
 
 #[embassy_executor::task]
 async fn mpsl_task(mpsl: &'static MultiprotocolServiceLayer<'static>) -> ! {
@@ -70,6 +168,21 @@ fn build_sdc<'d, const N: usize>(
 async fn main(spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
 
+    let boot = diagnostics::boot_info();
+    defmt::info!(
+        "reset reason: {}, boots: {}, crashes: {}",
+        defmt::Debug2Format(&boot.reason),
+        boot.boots,
+        boot.crashes
+    );
+
+    // Resume the mode stashed before a firmware update reset, firmware
+    // update code calls `handoff::stash(&[mode as u8])` before resetting
+    let mode = handoff::take()
+        .and_then(|(state, len)| LedMode::try_from(*state[..len].first()?).ok())
+        .unwrap_or(LedMode::Off);
+    defmt::info!("initial mode: {}", mode);
+
     let mpsl_p =
         mpsl::Peripherals::new(p.RTC0, p.TIMER0, p.TEMP, p.PPI_CH19, p.PPI_CH30, p.PPI_CH31);
     let lfclk_cfg = mpsl::raw::mpsl_clock_lfclk_cfg_t {
@@ -80,11 +193,25 @@ async fn main(spawner: Spawner) {
         skip_wait_lfclk_started: mpsl::raw::MPSL_DEFAULT_SKIP_WAIT_LFCLK_STARTED != 0,
     };
     static MPSL: StaticCell<MultiprotocolServiceLayer> = StaticCell::new();
-    let mpsl = MPSL.init(unwrap!(mpsl::MultiprotocolServiceLayer::new(
-        mpsl_p, Irqs, lfclk_cfg
+    // One timeslot session for the settings flash writes
+    static SESSION_MEM: StaticCell<SessionMem<1>> = StaticCell::new();
+    let mpsl = MPSL.init(unwrap!(mpsl::MultiprotocolServiceLayer::with_timeslots(
+        mpsl_p,
+        Irqs,
+        lfclk_cfg,
+        SESSION_MEM.init(SessionMem::new())
     )));
     spawner.must_spawn(mpsl_task(&*mpsl));
 
+    // Flash writes run in MPSL timeslots so they don't disturb the radio
+    let mut store = SettingsStore::new(Flash::take(mpsl, p.NVMC));
+    let saved = store.load().await.unwrap_or_else(|e| {
+        defmt::warn!("loading settings failed: {:?}", defmt::Debug2Format(&e));
+        Default::default()
+    });
+    SETTINGS.set(saved);
+    spawner.must_spawn(settings_task(store));
+
     let sdc_p = sdc::Peripherals::new(
         p.PPI_CH17, p.PPI_CH18, p.PPI_CH20, p.PPI_CH21, p.PPI_CH22, p.PPI_CH23, p.PPI_CH24,
         p.PPI_CH25, p.PPI_CH26, p.PPI_CH27, p.PPI_CH28, p.PPI_CH29,
@@ -95,64 +222,180 @@ async fn main(spawner: Spawner) {
     let mut sdc_mem = sdc::Mem::<4720>::new();
     let sdc = unwrap!(build_sdc(sdc_p, &mut rng, mpsl, &mut sdc_mem));
 
-    let buf = LED_BUFFER.init([0u16; BUFFER_SIZE]);
-    let ws: Ws2812<_> = Ws2812::new(p.PWM0, p.P0_13, buf);
-    let _ = join(
-        run(sdc, "WLED BLE", LedMode::Off),
-        led_manager(ws, LedMode::Off),
-    )
-    .await;
+    let buf = LED_BUFFER.init([[0; 24]; NUM_LEDS]);
+    let mut ws: Ws2812<_> = Ws2812Builder::new()
+        .power_model(POWER_MODEL)
+        .color_order(saved.color_order)
+        .build(p.PWM0, Irqs, p.P0_13, buf)
+        .expect("to create WS2812 driver");
+
+    // Flash a diagnostic color if the last reset was a crash
+    if let Some(color) = boot.color() {
+        let _ = ws.fill(color).await;
+        Timer::after_secs(2).await;
+    }
+
+    // Green LED pin on the Makerdiary nRF52840 connect kit
+    // used to show the mode if the strip can't be driven
+    let status = StatusLed::new(Output::new(p.P1_11, Level::High, OutputDrive::Standard));
+    let leds = Leds {
+        frames: FRAMES.handle(),
+        fallback: Some(status),
+    };
+    let runner = FRAMES.runner(ws);
+
+    match LED_PRIORITY {
+        Some(priority) => {
+            let led_spawner = LED_EXECUTOR.start(interrupt::EGU1_SWI1, priority);
+            led_spawner.must_spawn(ws2812_task(runner));
+            led_spawner.must_spawn(led_task(leds, mode));
+        }
+        None => {
+            spawner.must_spawn(ws2812_task(runner));
+            spawner.must_spawn(led_task(leds, mode));
+        }
+    }
+
+    run(sdc, "WLED BLE", mode, true).await;
+}
+
+/// Blink SOS in red on the strip when panicking so failures
+/// are visible on devices without a debugger attached.
+#[cfg(feature = "panic-led")]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    defmt::error!("{}", defmt::Display2Format(info));
+
+    // SAFETY: interrupts are disabled and the panicking code never
+    // resumes so nothing else can use the PWM and pin any more
+    let p = unsafe { embassy_nrf::Peripherals::steal() };
+    match Ws2812::<NUM_LEDS>::new_owned(p.PWM0, Irqs, p.P0_13) {
+        // 64 MHz core clock
+        Ok(mut ws) => panic_led::sos(&mut ws, colors::RED, |ms| cortex_m::asm::delay(ms * 64_000)),
+        Err(_) => cortex_m::asm::udf(),
+    }
+}
+
+/// Palette shown until one is written over BLE (red, green, blue).
+const DEFAULT_PALETTE: [u8; 16] = [0, 255, 0, 0, 85, 0, 255, 0, 170, 0, 0, 255, 255, 255, 0, 0];
+
+/// Strobe shown in alert mode until one is written over BLE.
+const DEFAULT_ALERT: Strobe = Strobe::new(colors::RED, Duration::from_millis(500), 50);
+
+/// Number of consecutive failed writes before the strip is considered broken.
+const MAX_WRITE_FAILURES: u8 = 3;
+
+/// Frames for the runner with an optional GPIO status LED fallback.
+///
+/// Writes happen in the runner, so a failed write is only seen
+/// by the frames sent after it.
+struct Leds {
+    frames: Ws2812Handle<'static, NUM_LEDS, FRAME_DEPTH>,
+    fallback: Option<StatusLed<'static>>,
 }
 
-async fn led_manager(mut ws: Ws2812<BUFFER_SIZE>, mut mode: LedMode) -> ! {
+impl Leds {
+    /// Whether writes have failed enough times to switch to the status LED.
+    fn is_broken(&self) -> bool {
+        self.fallback.is_some() && FAILURES.load(Ordering::Relaxed) >= MAX_WRITE_FAILURES
+    }
+
+    /// Send a frame to the runner, returns `false` once the strip is broken.
+    async fn send(&self, frame: Frame<NUM_LEDS>) -> bool {
+        WRITE_FAILED.reset();
+        self.frames.send(frame).await;
+        !self.is_broken()
+    }
+
+    /// Write colors to the strip, returns `false` once the strip is broken.
+    async fn write(&self, colors: impl Iterator<Item = RGB8>) -> bool {
+        let mut frame = [RGB8::default(); NUM_LEDS];
+        frame
+            .iter_mut()
+            .zip(colors)
+            .for_each(|(led, color)| *led = color);
+        self.send(Frame::Pixels(frame)).await
+    }
+
+    /// Set every LED to `color`, returns `false` once the strip is broken.
+    async fn fill(&self, color: RGB8) -> bool {
+        self.send(Frame::Fill(color)).await
+    }
+
+    /// Wait for a new mode, returns `None` if a write failed
+    /// first so the last frame is sent again.
+    async fn next_mode(&self) -> Option<LedMode> {
+        match select(NOTIFIER.wait(), WRITE_FAILED.wait()).await {
+            Either::First(mode) => Some(mode),
+            Either::Second(()) => None,
+        }
+    }
+}
+
+async fn led_manager(leds: &mut Leds, mut mode: LedMode) -> ! {
+    let mut palette = GradientPalette::from_wled(&DEFAULT_PALETTE).expect("valid default palette");
+    let mut alert = DEFAULT_ALERT;
+    // Color order of the runner's driver, built with the saved one
+    let mut color_order = SETTINGS.get().color_order;
+
     loop {
         defmt::info!("mode: {}", mode);
+
+        if leds.is_broken()
+            && let Some(status) = leds.fallback.as_mut()
+        {
+            if let Either::Second(new_mode) = select(status.show(mode), NOTIFIER.wait()).await {
+                mode = new_mode;
+            }
+            continue;
+        }
+
         match mode {
             LedMode::Off => {
-                let data = [RGB8::new(0, 0, 0); 8];
-                ws.write(data.into_iter()).await.unwrap();
-                mode = NOTIFIER.wait().await;
+                if leds.fill(RGB8::default()).await
+                    && let Some(new_mode) = leds.next_mode().await
+                {
+                    mode = new_mode;
+                }
             }
             LedMode::Red => {
-                let data = [colors::RED; 8];
-                ws.write(data.into_iter()).await.unwrap();
-                mode = NOTIFIER.wait().await;
+                if leds.fill(colors::RED).await
+                    && let Some(new_mode) = leds.next_mode().await
+                {
+                    mode = new_mode;
+                }
             }
             LedMode::Green => {
-                let data = [colors::GREEN; 8];
-                ws.write(data.into_iter()).await.unwrap();
-                mode = NOTIFIER.wait().await;
+                if leds.fill(colors::GREEN).await
+                    && let Some(new_mode) = leds.next_mode().await
+                {
+                    mode = new_mode;
+                }
             }
             LedMode::Blue => {
-                let data = [colors::BLUE; 8];
-                ws.write(data.into_iter()).await.unwrap();
-                mode = NOTIFIER.wait().await;
+                if leds.fill(colors::BLUE).await
+                    && let Some(new_mode) = leds.next_mode().await
+                {
+                    mode = new_mode;
+                }
             }
             LedMode::Rainbow => {
-                let mut hue_offset = 0u8;
+                // Keep brightness reasonable
+                let mut rainbow = Rainbow::new(32, Duration::from_millis(1600)).value(50);
+                let start = Instant::now();
 
                 loop {
-                    // Create rainbow effect
-                    let mut colors = [RGB8::default(); 8];
-
-                    for (i, color) in colors.iter_mut().enumerate() {
-                        let hue = hue_offset.wrapping_add((i as u8) * 32);
-                        let hsv = Hsv {
-                            hue,
-                            sat: 255,
-                            val: 50, // Keep brightness reasonable
-                        };
-                        *color = hsv2rgb(hsv);
-                    }
+                    let mut colors = [RGB8::default(); NUM_LEDS];
+                    rainbow.frame(start.elapsed(), &mut colors);
 
                     // Write colors with brightness control
-                    ws.write(brightness(colors.into_iter(), 64)).await.unwrap();
+                    if !leds.write(brightness(colors.into_iter(), 64)).await {
+                        break;
+                    }
 
                     match select(Timer::after(Duration::from_millis(25)), NOTIFIER.wait()).await {
-                        Either::First(_) => {
-                            hue_offset = hue_offset.wrapping_add(4);
-                            continue;
-                        }
+                        Either::First(_) => continue,
                         Either::Second(new_mode) => {
                             mode = new_mode;
                             break;
@@ -160,6 +403,167 @@ async fn led_manager(mut ws: Ws2812<BUFFER_SIZE>, mut mode: LedMode) -> ! {
                     }
                 }
             }
+            LedMode::Calibrate => {
+                // Step through the LEDs until an index is written
+                // over BLE, then hold on that LED
+                let mut index = 0usize;
+                let mut hold = false;
+                CALIBRATION.reset();
+
+                loop {
+                    defmt::info!("calibrating LED {}", index);
+                    let mut frame = [RGB8::default(); NUM_LEDS];
+                    frame[index] = colors::WHITE;
+                    if !leds.write(brightness(frame.into_iter(), 64)).await {
+                        break;
+                    }
+
+                    let step = async {
+                        if hold {
+                            core::future::pending::<()>().await
+                        } else {
+                            Timer::after_secs(1).await
+                        }
+                    };
+
+                    match select3(step, CALIBRATION.wait(), NOTIFIER.wait()).await {
+                        Either3::First(_) => {
+                            index = (index + 1) % NUM_LEDS;
+                        }
+                        Either3::Second(new_index) => {
+                            index = new_index as usize % NUM_LEDS;
+                            hold = true;
+                        }
+                        Either3::Third(new_mode) => {
+                            mode = new_mode;
+                            break;
+                        }
+                    }
+                }
+            }
+            LedMode::Palette => {
+                let mut offset = 0u8;
+
+                loop {
+                    let mut frame = [RGB8::default(); NUM_LEDS];
+                    palette.fill(&mut frame, offset);
+                    if !leds.write(brightness(frame.into_iter(), 64)).await {
+                        break;
+                    }
+
+                    match select3(
+                        Timer::after(Duration::from_millis(25)),
+                        PALETTE.wait(),
+                        NOTIFIER.wait(),
+                    )
+                    .await
+                    {
+                        Either3::First(_) => {
+                            offset = offset.wrapping_add(2);
+                        }
+                        Either3::Second(new_palette) => {
+                            palette = new_palette;
+                        }
+                        Either3::Third(new_mode) => {
+                            mode = new_mode;
+                            break;
+                        }
+                    }
+                }
+            }
+            LedMode::ColorTest => {
+                // Light one byte on the wire at a time on the first LED
+                // and let the user report the color they see
+                let previous = color_order;
+                leds.send(Frame::ColorOrder(ColorOrder::Rgb)).await;
+                COLOR_TEST.reset();
+
+                let mut seen = [Channel::Red; 3];
+                let mut step = 0;
+                let mut new_mode = None;
+                while step < seen.len() {
+                    let mut frame = [RGB8::default(); NUM_LEDS];
+                    frame[0] = [colors::RED, colors::LIME, colors::BLUE][step];
+                    if !leds.write(brightness(frame.into_iter(), 64)).await {
+                        break;
+                    }
+
+                    match select(COLOR_TEST.wait(), NOTIFIER.wait()).await {
+                        Either::First(answer) => {
+                            let channel = match answer {
+                                0 => Channel::Red,
+                                1 => Channel::Green,
+                                2 => Channel::Blue,
+                                _ => {
+                                    defmt::warn!("invalid color {}, ignoring", answer);
+                                    continue;
+                                }
+                            };
+                            seen[step] = channel;
+                            step += 1;
+                        }
+                        Either::Second(m) => {
+                            new_mode = Some(m);
+                            break;
+                        }
+                    }
+                }
+
+                match ColorOrder::from_channels(seen).filter(|_| step == seen.len()) {
+                    Some(order) => {
+                        defmt::info!("color order: {}", defmt::Debug2Format(&order));
+                        color_order = order;
+                        SETTINGS.update(|settings| settings.color_order = order);
+                    }
+                    None => {
+                        defmt::warn!("color order test incomplete, keeping previous order");
+                        color_order = previous;
+                    }
+                }
+                leds.send(Frame::ColorOrder(color_order)).await;
+
+                match new_mode {
+                    Some(m) => mode = m,
+                    None => {
+                        // Show red, green and blue to confirm the order
+                        let mut frame = [RGB8::default(); NUM_LEDS];
+                        frame[..3].copy_from_slice(&[colors::RED, colors::LIME, colors::BLUE]);
+                        if leds.write(brightness(frame.into_iter(), 64)).await
+                            && let Some(new_mode) = leds.next_mode().await
+                        {
+                            mode = new_mode;
+                        }
+                    }
+                }
+            }
+            LedMode::Alert => {
+                let start = Instant::now();
+
+                loop {
+                    let mut frame = [RGB8::default(); NUM_LEDS];
+                    alert.frame(Instant::now() - start, &mut frame);
+                    if !leds.write(brightness(frame.into_iter(), 64)).await {
+                        break;
+                    }
+
+                    match select3(
+                        Timer::after(Duration::from_millis(20)),
+                        ALERT.wait(),
+                        NOTIFIER.wait(),
+                    )
+                    .await
+                    {
+                        Either3::First(_) => {}
+                        Either3::Second(new_alert) => {
+                            alert = new_alert;
+                        }
+                        Either3::Third(new_mode) => {
+                            mode = new_mode;
+                            break;
+                        }
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,272 @@
+//! Remote control of a lamp from another nRF device.
+//!
+//! A remote (buttons, a rotary encoder) runs the central role: it
+//! connects to the lamp running the [gatt_server](crate::gatt_server),
+//! discovers the LED service and writes its characteristics through a
+//! [LedClient]. Input tasks feed [RemoteCommand]s into [COMMANDS] and
+//! [run] forwards them to the lamp, reconnecting whenever the link
+//! drops so the remote and the lamp can pair without a phone.
+//!
+//! The controller of the remote must support the central role
+//! (`support_central` in the SoftDevice Controller builder).
+
+use crate::led_mode::LedMode;
+use crate::scene::SCENE_NAMES_LEN;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use trouble_host::prelude::*;
+
+/// Commands from the input tasks of the remote.
+pub static COMMANDS: Channel<CriticalSectionRawMutex, RemoteCommand, 4> = Channel::new();
+
+/// Max number of services discovered on the lamp.
+const SERVICES_MAX: usize = 4;
+
+/// Max number of L2CAP channels.
+const L2CAP_CHANNELS_MAX: usize = 2; // Signal + att
+
+/// Address of the remote itself.
+const ADDRESS: [u8; 6] = [0xfe, 0x8f, 0x1a, 0x05, 0xe4, 0xff];
+
+/// Wait before connecting again after the link failed.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// UUID of the characteristics of the LED service, the last byte is
+/// the characteristic index as defined in the gatt_server module.
+const LED_CHARACTERISTIC_UUID: u128 = 0x408813df_5dd4_1f87_ec11_cdb001100000;
+
+/// Index of the mode characteristic.
+const MODE: u8 = 0x00;
+/// Index of the scene characteristic.
+const SCENE: u8 = 0x04;
+/// Index of the scene names characteristic.
+const SCENE_NAMES: u8 = 0x05;
+/// Index of the heartbeat characteristic.
+const HEARTBEAT: u8 = 0x07;
+/// Index of the alert characteristic.
+const ALERT: u8 = 0x08;
+
+/// Action requested by a button or the encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RemoteCommand {
+    /// Switch the lamp to a mode.
+    Mode(LedMode),
+    /// Switch the lamp to a scene by index.
+    Scene(u8),
+    /// Step through the scenes, for example by encoder detents.
+    StepScene(i8),
+    /// Start an alert strobe, encoded as the alert characteristic
+    /// (RGB, period ms LE, duty %).
+    Alert([u8; 6]),
+}
+
+/// Client-side handles of the LED service characteristics.
+pub struct LedClient<'a, 'c, C: Controller> {
+    client: &'a GattClient<'c, C, DefaultPacketPool, SERVICES_MAX>,
+    mode: Characteristic<u8>,
+    scene: Characteristic<u8>,
+    scene_names: Characteristic<[u8; SCENE_NAMES_LEN]>,
+    heartbeat: Characteristic<[u8; 8]>,
+    alert: Characteristic<[u8; 6]>,
+}
+
+impl<'a, 'c, C: Controller> LedClient<'a, 'c, C> {
+    /// Discover the LED service and its characteristics.
+    pub async fn discover(
+        client: &'a GattClient<'c, C, DefaultPacketPool, SERVICES_MAX>,
+    ) -> Result<Self, BleHostError<C::Error>> {
+        let services = client
+            .services_by_uuid(&Uuid::from(service::GENERIC_MEDIA_CONTROL))
+            .await?;
+        let Some(service) = services.first() else {
+            return Err(Error::NotFound.into());
+        };
+        Ok(Self {
+            client,
+            mode: client
+                .characteristic_by_uuid(service, &led_uuid(MODE))
+                .await?,
+            scene: client
+                .characteristic_by_uuid(service, &led_uuid(SCENE))
+                .await?,
+            scene_names: client
+                .characteristic_by_uuid(service, &led_uuid(SCENE_NAMES))
+                .await?,
+            heartbeat: client
+                .characteristic_by_uuid(service, &led_uuid(HEARTBEAT))
+                .await?,
+            alert: client
+                .characteristic_by_uuid(service, &led_uuid(ALERT))
+                .await?,
+        })
+    }
+
+    /// Read the current mode.
+    pub async fn mode(&self) -> Result<LedMode, BleHostError<C::Error>> {
+        let mut value = [0];
+        self.client
+            .read_characteristic(&self.mode, &mut value)
+            .await?;
+        LedMode::try_from(value[0]).map_err(|_| Error::InvalidValue.into())
+    }
+
+    /// Switch to a mode.
+    pub async fn set_mode(&self, mode: LedMode) -> Result<(), BleHostError<C::Error>> {
+        self.client
+            .write_characteristic(&self.mode, &[mode as u8])
+            .await
+    }
+
+    /// Read the current scene index.
+    pub async fn scene(&self) -> Result<u8, BleHostError<C::Error>> {
+        let mut value = [0];
+        self.client
+            .read_characteristic(&self.scene, &mut value)
+            .await?;
+        Ok(value[0])
+    }
+
+    /// Switch to a scene.
+    pub async fn set_scene(&self, index: u8) -> Result<(), BleHostError<C::Error>> {
+        self.client
+            .write_characteristic(&self.scene, &[index])
+            .await
+    }
+
+    /// Read the number of scenes saved on the lamp.
+    pub async fn scene_count(&self) -> Result<usize, BleHostError<C::Error>> {
+        let mut names = [0; SCENE_NAMES_LEN];
+        self.client
+            .read_characteristic(&self.scene_names, &mut names)
+            .await?;
+        Ok(names
+            .split(|&byte| byte == 0)
+            .take_while(|name| !name.is_empty())
+            .count())
+    }
+
+    /// Read the frame counter and CRC of the last frame,
+    /// see [Heartbeat](crate::heartbeat::Heartbeat).
+    pub async fn heartbeat(&self) -> Result<(u32, u32), BleHostError<C::Error>> {
+        let mut value = [0; 8];
+        self.client
+            .read_characteristic(&self.heartbeat, &mut value)
+            .await?;
+        let [f0, f1, f2, f3, c0, c1, c2, c3] = value;
+        Ok((
+            u32::from_le_bytes([f0, f1, f2, f3]),
+            u32::from_le_bytes([c0, c1, c2, c3]),
+        ))
+    }
+
+    /// Start an alert strobe.
+    pub async fn alert(&self, alert: [u8; 6]) -> Result<(), BleHostError<C::Error>> {
+        self.client.write_characteristic(&self.alert, &alert).await
+    }
+
+    /// Send a command to the lamp.
+    pub async fn send(&self, command: RemoteCommand) -> Result<(), BleHostError<C::Error>> {
+        match command {
+            RemoteCommand::Mode(mode) => self.set_mode(mode).await,
+            RemoteCommand::Scene(index) => self.set_scene(index).await,
+            RemoteCommand::StepScene(step) => {
+                let count = self.scene_count().await? as i16;
+                if count == 0 {
+                    return Ok(());
+                }
+                let index = self.scene().await?;
+                let next = (index as i16 + step as i16).rem_euclid(count);
+                self.set_scene(next as u8).await
+            }
+            RemoteCommand::Alert(alert) => self.alert(alert).await,
+        }
+    }
+}
+
+/// UUID of an LED service characteristic.
+fn led_uuid(index: u8) -> Uuid {
+    Uuid::new_long((LED_CHARACTERISTIC_UUID | index as u128).to_le_bytes())
+}
+
+/// Run the remote, forwarding [COMMANDS] to the lamp at `lamp`,
+/// usually `Address::random(gatt_server::ADDRESS)`.
+///
+/// Commands arriving while disconnected wait in the channel until the
+/// remote is connected again.
+pub async fn run<C>(controller: C, lamp: Address)
+where
+    C: Controller,
+{
+    let address = Address::random(ADDRESS);
+    info!("address = {:?}", address);
+
+    let mut resources: HostResources<DefaultPacketPool, 1, L2CAP_CHANNELS_MAX> =
+        HostResources::new();
+    let stack = trouble_host::new(controller, &mut resources).set_random_address(address);
+    let Host {
+        mut central,
+        mut runner,
+        ..
+    } = stack.build();
+
+    let _ = select(
+        async {
+            loop {
+                if let Err(e) = runner.run().await {
+                    let e = defmt::Debug2Format(&e);
+                    panic!("[ble_task] error: {:?}", e);
+                }
+            }
+        },
+        async {
+            loop {
+                if let Err(e) = control(&stack, &mut central, lamp).await {
+                    warn!("[remote] link failed: {:?}", defmt::Debug2Format(&e));
+                }
+                Timer::after(RETRY_DELAY).await;
+            }
+        },
+    )
+    .await;
+}
+
+/// Connect to the lamp and forward commands until the link fails.
+async fn control<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    central: &mut Central<'_, C, DefaultPacketPool>,
+    lamp: Address,
+) -> Result<(), BleHostError<C::Error>> {
+    // Scan for the lamp's address only and connect to it
+    let config = ConnectConfig {
+        connect_params: Default::default(),
+        scan_config: ScanConfig {
+            filter_accept_list: &[(lamp.kind, &lamp.addr)],
+            ..Default::default()
+        },
+    };
+    info!("[remote] connecting to {:?}", lamp);
+    let conn = central.connect(&config).await?;
+    info!("[remote] connected");
+
+    let client = GattClient::<C, DefaultPacketPool, SERVICES_MAX>::new(stack, &conn).await?;
+    match select(client.task(), forward(&client)).await {
+        Either::First(result) => result,
+        Either::Second(result) => result,
+    }
+}
+
+/// Discover the LED service and send commands until writing fails.
+async fn forward<C: Controller>(
+    client: &GattClient<'_, C, DefaultPacketPool, SERVICES_MAX>,
+) -> Result<(), BleHostError<C::Error>> {
+    let lamp = LedClient::discover(client).await?;
+    info!("[remote] LED service discovered");
+    loop {
+        let command = COMMANDS.receive().await;
+        info!("[remote] sending {:?}", command);
+        lamp.send(command).await?;
+    }
+}
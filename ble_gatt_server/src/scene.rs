@@ -0,0 +1,168 @@
+use crate::led_mode::LedMode;
+
+/// Max number of saved scenes.
+pub const SCENES_MAX: usize = 8;
+
+/// Max length of a scene name in bytes.
+pub const NAME_MAX: usize = 15;
+
+/// Size of the scene names characteristic.
+pub const SCENE_NAMES_LEN: usize = SCENES_MAX * (NAME_MAX + 1);
+
+/// Size of a scene in the settings, the mode, the name length and the name.
+pub(crate) const SCENE_LEN: usize = 2 + NAME_MAX;
+
+/// Size of the scenes in the settings.
+pub(crate) const SCENES_LEN: usize = SCENES_MAX * SCENE_LEN;
+
+/// Mode byte of an unused slot in the settings.
+const EMPTY: u8 = 0xff;
+
+/// LED mode under a human readable name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scene {
+    name: [u8; NAME_MAX],
+    name_len: u8,
+    pub mode: LedMode,
+}
+
+impl Scene {
+    /// Create a scene, `None` if the name is empty, longer
+    /// than [NAME_MAX] bytes or contains a NUL byte.
+    pub const fn new(name: &str, mode: LedMode) -> Option<Self> {
+        let bytes = name.as_bytes();
+        if bytes.is_empty() || bytes.len() > NAME_MAX {
+            return None;
+        }
+        let mut scene = Self {
+            name: [0; NAME_MAX],
+            name_len: bytes.len() as u8,
+            mode,
+        };
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == 0 {
+                return None;
+            }
+            scene.name[i] = bytes[i];
+            i += 1;
+        }
+        Some(scene)
+    }
+
+    /// Name of the scene.
+    pub fn name(&self) -> &str {
+        // Names are only built from strings
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or_default()
+    }
+}
+
+/// Scenes saved in the [settings](crate::settings), the index is the
+/// value written to the scene characteristic.
+///
+/// Scenes are kept in the first slots, removing one moves
+/// the following scenes down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scenes {
+    scenes: [Option<Scene>; SCENES_MAX],
+}
+
+impl Scenes {
+    /// Scenes of a lamp that never saved any.
+    pub const DEFAULT: Self = Self {
+        scenes: [
+            Scene::new("Off", LedMode::Off),
+            Scene::new("Movie night", LedMode::Blue),
+            Scene::new("Reading", LedMode::Palette),
+            Scene::new("Party", LedMode::Rainbow),
+            None,
+            None,
+            None,
+            None,
+        ],
+    };
+
+    /// Scene at an index.
+    pub fn get(&self, index: u8) -> Option<&Scene> {
+        self.scenes.get(index as usize)?.as_ref()
+    }
+
+    /// Number of scenes.
+    pub fn len(&self) -> usize {
+        self.scenes
+            .iter()
+            .take_while(|scene| scene.is_some())
+            .count()
+    }
+
+    /// Whether there are no scenes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Replace the scene at `index` or add one after the last,
+    /// returns `false` for other indexes.
+    pub fn save(&mut self, index: u8, scene: Scene) -> bool {
+        let index = index as usize;
+        if index > self.len() || index >= SCENES_MAX {
+            return false;
+        }
+        self.scenes[index] = Some(scene);
+        true
+    }
+
+    /// Remove the scene at `index`, returns `false` if there is none.
+    pub fn remove(&mut self, index: u8) -> bool {
+        let index = index as usize;
+        if index >= self.len() {
+            return false;
+        }
+        self.scenes[index..].rotate_left(1);
+        self.scenes[SCENES_MAX - 1] = None;
+        true
+    }
+
+    /// Scene names in index order separated by NUL bytes
+    /// so clients can list them with a single read.
+    pub fn names(&self) -> [u8; SCENE_NAMES_LEN] {
+        let mut names = [0; SCENE_NAMES_LEN];
+        let mut pos = 0;
+        for scene in self.scenes.iter().flatten() {
+            let name = scene.name().as_bytes();
+            names[pos..][..name.len()].copy_from_slice(name);
+            pos += name.len() + 1;
+        }
+        names
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; SCENES_LEN] {
+        let mut bytes = [EMPTY; SCENES_LEN];
+        for (chunk, scene) in bytes.chunks_exact_mut(SCENE_LEN).zip(self.scenes) {
+            if let Some(scene) = scene {
+                chunk[0] = scene.mode as u8;
+                chunk[1] = scene.name_len;
+                chunk[2..].copy_from_slice(&scene.name);
+            }
+        }
+        bytes
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; SCENES_LEN]) -> Option<Self> {
+        let mut scenes = [None; SCENES_MAX];
+        for (scene, chunk) in scenes.iter_mut().zip(bytes.chunks_exact(SCENE_LEN)) {
+            if chunk[0] == EMPTY {
+                break;
+            }
+            let name = chunk[2..].get(..chunk[1] as usize)?;
+            let name = core::str::from_utf8(name).ok()?;
+            *scene = Some(Scene::new(name, LedMode::try_from(chunk[0]).ok()?)?);
+        }
+        Some(Self { scenes })
+    }
+}
+
+impl Default for Scenes {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
@@ -0,0 +1,244 @@
+//! Settings kept in flash across resets.
+//!
+//! Settings are stored as fixed size records appended to [REGION],
+//! the last record with a valid CRC wins and the region is erased once
+//! it is full, so the region takes dozens of changes before it's erased
+//! again. Changes made through [SETTINGS] are written by [run] once
+//! they have settled for [SAVE_DELAY], stepping through values over
+//! BLE costs a single record. [SharedSettings::flush] writes pending
+//! changes right away, for a supply about to fail.
+
+use crate::disconnect::DisconnectAction;
+use crate::heartbeat::FrameCrc;
+use crate::scene::{SCENES_LEN, Scenes};
+use core::cell::Cell;
+use core::ops::Range;
+use defmt::warn;
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_nrf_ws2812_pwm::color_order::ColorOrder;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use embedded_storage_async::nor_flash::NorFlash;
+
+/// Flash region of the settings, the last two pages of the nRF52840
+/// flash, left out of `memory.x`.
+pub const REGION: Range<u32> = 0xfe000..0x100000;
+
+/// Time without changes before the settings are written.
+pub const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// Settings written by the BLE and LED tasks.
+pub static SETTINGS: SharedSettings = SharedSettings::new();
+
+/// Marker and layout version at the start of each record, records
+/// of another layout are ignored.
+const MAGIC: [u8; 4] = *b"WSS\x03";
+
+/// Size of the encoded settings.
+const SETTINGS_LEN: usize = 1 + 3 + SCENES_LEN;
+
+/// Size of a record, the magic, the settings and their CRC-32
+/// padded to the flash word.
+const RECORD_LEN: usize = (MAGIC.len() + SETTINGS_LEN + 4).next_multiple_of(4);
+
+/// Settings of the lamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Color order found with the color order test.
+    pub color_order: ColorOrder,
+    /// What to do with the LEDs when the central disconnects.
+    pub disconnect: DisconnectAction,
+    /// Scenes saved over BLE.
+    pub scenes: Scenes,
+}
+
+impl Settings {
+    /// Settings of a lamp that never saved any.
+    pub const DEFAULT: Self = Self {
+        color_order: ColorOrder::Grb,
+        disconnect: DisconnectAction::Keep,
+        scenes: Scenes::DEFAULT,
+    };
+
+    fn to_bytes(self) -> [u8; SETTINGS_LEN] {
+        let mut bytes = [0; SETTINGS_LEN];
+        bytes[0] = color_order_to_u8(self.color_order);
+        bytes[1..4].copy_from_slice(&self.disconnect.to_bytes());
+        bytes[4..].copy_from_slice(&self.scenes.to_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; SETTINGS_LEN]) -> Option<Self> {
+        Some(Self {
+            color_order: color_order_from_u8(bytes[0])?,
+            disconnect: DisconnectAction::from_bytes(&bytes[1..4])?,
+            scenes: Scenes::from_bytes(bytes[4..].try_into().ok()?)?,
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+fn color_order_to_u8(color_order: ColorOrder) -> u8 {
+    match color_order {
+        ColorOrder::Rgb => 0,
+        ColorOrder::Rbg => 1,
+        ColorOrder::Grb => 2,
+        ColorOrder::Gbr => 3,
+        ColorOrder::Brg => 4,
+        ColorOrder::Bgr => 5,
+    }
+}
+
+fn color_order_from_u8(value: u8) -> Option<ColorOrder> {
+    Some(match value {
+        0 => ColorOrder::Rgb,
+        1 => ColorOrder::Rbg,
+        2 => ColorOrder::Grb,
+        3 => ColorOrder::Gbr,
+        4 => ColorOrder::Brg,
+        5 => ColorOrder::Bgr,
+        _ => return None,
+    })
+}
+
+/// Current settings shared between tasks.
+pub struct SharedSettings {
+    settings: Mutex<CriticalSectionRawMutex, Cell<Settings>>,
+    changed: Signal<CriticalSectionRawMutex, ()>,
+    flush: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl SharedSettings {
+    const fn new() -> Self {
+        Self {
+            settings: Mutex::new(Cell::new(Settings::DEFAULT)),
+            changed: Signal::new(),
+            flush: Signal::new(),
+        }
+    }
+
+    /// Current settings.
+    pub fn get(&self) -> Settings {
+        self.settings.lock(Cell::get)
+    }
+
+    /// Replace the settings without saving them,
+    /// for the settings loaded at startup.
+    pub fn set(&self, settings: Settings) {
+        self.settings.lock(|cell| cell.set(settings));
+    }
+
+    /// Change the settings and save them once they settle.
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) {
+        self.settings.lock(|cell| {
+            let mut settings = cell.get();
+            f(&mut settings);
+            cell.set(settings);
+        });
+        self.changed.signal(());
+    }
+
+    /// Save pending changes without waiting for them to settle.
+    pub fn flush(&self) {
+        self.flush.signal(());
+    }
+}
+
+/// Settings records in [REGION] of a flash.
+pub struct SettingsStore<F> {
+    flash: F,
+    /// Offset of the next record, the end of the region when full.
+    next: u32,
+}
+
+impl<F: NorFlash> SettingsStore<F> {
+    /// Create a store, call [SettingsStore::load] before saving.
+    pub fn new(flash: F) -> Self {
+        const { assert!(RECORD_LEN.is_multiple_of(F::WRITE_SIZE)) };
+        Self {
+            flash,
+            next: REGION.end,
+        }
+    }
+
+    /// Read the last saved settings, the defaults if there are none.
+    pub async fn load(&mut self) -> Result<Settings, F::Error> {
+        let mut settings = None;
+        let mut record = [0; RECORD_LEN];
+        let mut offset = REGION.start;
+        self.next = REGION.end;
+        while offset + RECORD_LEN as u32 <= REGION.end {
+            self.flash.read(offset, &mut record).await?;
+            if record.iter().all(|&byte| byte == 0xff) {
+                self.next = offset;
+                break;
+            }
+            // Records cut short by a reset fail the CRC and are skipped
+            if let Some(saved) = parse(&record) {
+                settings = Some(saved);
+            }
+            offset += RECORD_LEN as u32;
+        }
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Append a record, erasing the region first when it is full.
+    pub async fn save(&mut self, settings: &Settings) -> Result<(), F::Error> {
+        if self.next + RECORD_LEN as u32 > REGION.end {
+            self.flash.erase(REGION.start, REGION.end).await?;
+            self.next = REGION.start;
+        }
+        let mut record = [0xff; RECORD_LEN];
+        record[..MAGIC.len()].copy_from_slice(&MAGIC);
+        record[MAGIC.len()..][..SETTINGS_LEN].copy_from_slice(&settings.to_bytes());
+        let crc = crc(&record[..MAGIC.len() + SETTINGS_LEN]);
+        record[MAGIC.len() + SETTINGS_LEN..][..4].copy_from_slice(&crc.to_le_bytes());
+        // Skip the slot even if the write fails, it may be half written
+        let offset = self.next;
+        self.next += RECORD_LEN as u32;
+        self.flash.write(offset, &record).await
+    }
+}
+
+fn parse(record: &[u8; RECORD_LEN]) -> Option<Settings> {
+    let (data, rest) = record.split_at(MAGIC.len() + SETTINGS_LEN);
+    let saved = u32::from_le_bytes(rest[..4].try_into().ok()?);
+    if data[..MAGIC.len()] != MAGIC || crc(data) != saved {
+        return None;
+    }
+    Settings::from_bytes(data[MAGIC.len()..].try_into().ok()?)
+}
+
+fn crc(bytes: &[u8]) -> u32 {
+    let mut crc = FrameCrc::new();
+    crc.update_bytes(bytes);
+    crc.finish()
+}
+
+/// Save [SETTINGS] whenever they change and settle or are flushed,
+/// never returns.
+pub async fn run<F: NorFlash>(mut store: SettingsStore<F>) -> ! {
+    loop {
+        // A flush without pending changes has nothing to save
+        if let Either::Second(()) = select(SETTINGS.changed.wait(), SETTINGS.flush.wait()).await {
+            continue;
+        }
+        while let Either3::Second(()) = select3(
+            Timer::after(SAVE_DELAY),
+            SETTINGS.changed.wait(),
+            SETTINGS.flush.wait(),
+        )
+        .await
+        {}
+        if let Err(e) = store.save(&SETTINGS.get()).await {
+            warn!("saving settings failed: {:?}", defmt::Debug2Format(&e));
+        }
+    }
+}
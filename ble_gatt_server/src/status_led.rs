@@ -0,0 +1,49 @@
+use crate::led_mode::LedMode;
+use embassy_nrf::gpio::Output;
+use embassy_time::Timer;
+
+/// Plain GPIO status LED used when the WS2812 strip can't be driven.
+///
+/// The LED is treated as active low like the green LED
+/// on the Makerdiary nRF52840 connect kit.
+pub struct StatusLed<'d> {
+    pin: Output<'d>,
+}
+
+impl<'d> StatusLed<'d> {
+    /// Create a status LED from an output pin.
+    pub fn new(mut pin: Output<'d>) -> Self {
+        pin.set_high();
+        Self { pin }
+    }
+
+    /// Show a mode by blinking the LED, never returns.
+    ///
+    /// Off keeps the LED dark, rainbow pulses slowly and
+    /// the solid colors blink their mode number followed by a pause.
+    pub async fn show(&mut self, mode: LedMode) -> ! {
+        loop {
+            match mode {
+                LedMode::Off => {
+                    self.pin.set_high();
+                    Timer::after_secs(1).await;
+                }
+                LedMode::Rainbow => {
+                    self.pin.set_low();
+                    Timer::after_millis(1000).await;
+                    self.pin.set_high();
+                    Timer::after_millis(1000).await;
+                }
+                _ => {
+                    for _ in 0..mode as u8 {
+                        self.pin.set_low();
+                        Timer::after_millis(150).await;
+                        self.pin.set_high();
+                        Timer::after_millis(250).await;
+                    }
+                    Timer::after_millis(1200).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Map screen edges to LED indices for TV backlight builds.
+//!
+//! The strip is assumed to run around the screen starting at the top
+//! left corner going clockwise: along the top (left to right), down the
+//! right side, along the bottom (right to left) and up the left side.
+//! The `start` offset and `clockwise` flag describe where the first LED
+//! of the strip really is and which way it runs.
+
+/// Edge of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// Corner of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+/// Layout of LEDs around the edges of a screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeLayout {
+    /// LEDs along the top edge.
+    pub top: usize,
+    /// LEDs along the right edge.
+    pub right: usize,
+    /// LEDs along the bottom edge.
+    pub bottom: usize,
+    /// LEDs along the left edge.
+    pub left: usize,
+    /// Whether there is an LED in each corner.
+    pub corners: bool,
+    /// Position of the first LED of the strip counted
+    /// clockwise from the top left corner.
+    pub start: usize,
+    /// Whether the strip runs clockwise from `start`.
+    pub clockwise: bool,
+}
+
+impl EdgeLayout {
+    /// Layout without corners starting at the top left going clockwise.
+    pub const fn new(top: usize, right: usize, bottom: usize, left: usize) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+            corners: false,
+            start: 0,
+            clockwise: true,
+        }
+    }
+
+    /// Total number of LEDs including corners.
+    pub const fn len(&self) -> usize {
+        let corners = if self.corners { 4 } else { 0 };
+        self.top + self.right + self.bottom + self.left + corners
+    }
+
+    /// Whether the layout has no LEDs.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Strip index of the `n`th LED along an edge.
+    ///
+    /// Top and bottom edges are counted left to right, the
+    /// left and right edges top to bottom. Returns `None` when
+    /// `n` is past the end of the edge.
+    pub fn index(&self, edge: Edge, n: usize) -> Option<usize> {
+        let corner = usize::from(self.corners);
+        let top = corner;
+        let right = top + self.top + corner;
+        let bottom = right + self.right + corner;
+        let left = bottom + self.bottom + corner;
+
+        let position = match edge {
+            Edge::Top if n < self.top => top + n,
+            Edge::Right if n < self.right => right + n,
+            Edge::Bottom if n < self.bottom => bottom + (self.bottom - 1 - n),
+            Edge::Left if n < self.left => left + (self.left - 1 - n),
+            _ => return None,
+        };
+        Some(self.physical(position))
+    }
+
+    /// Strip index of a corner LED, `None` without corners.
+    pub fn corner(&self, corner: Corner) -> Option<usize> {
+        if !self.corners {
+            return None;
+        }
+        let position = match corner {
+            Corner::TopLeft => 0,
+            Corner::TopRight => self.top + 1,
+            Corner::BottomRight => self.top + self.right + 2,
+            Corner::BottomLeft => self.top + self.right + self.bottom + 3,
+        };
+        Some(self.physical(position))
+    }
+
+    /// Convert a clockwise position from the top left into a strip index.
+    fn physical(&self, position: usize) -> usize {
+        let len = self.len();
+        let start = self.start % len;
+        if self.clockwise {
+            (position + len - start) % len
+        } else {
+            (start + len - position) % len
+        }
+    }
+}
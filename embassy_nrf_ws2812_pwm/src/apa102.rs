@@ -0,0 +1,208 @@
+//! APA102 and SK9822 driver using a SPIM peripheral.
+//!
+//! These LEDs are clocked, so unlike WS2812 the timing is set by SCK
+//! and frames can be sent at any SPI speed. A frame starts with 4 zero
+//! bytes, each LED takes a byte with its 5 bit global brightness
+//! followed by the blue, green and red channels, and the frame ends
+//! with 4 zero bytes, which SK9822 needs to latch, plus half a clock
+//! per LED to push the data through the strip.
+//!
+//! ```ignore
+//! bind_interrupts!(struct Irqs {
+//!     SPI2 => spim::InterruptHandler<peripherals::SPI2>;
+//! });
+//!
+//! static BUF: StaticCell<apa102::Buffer<60>> = StaticCell::new();
+//! let buf = BUF.init(apa102::Buffer::new());
+//! let mut leds = Apa102::new(p.SPI2, Irqs, p.P0_14, p.P0_13, buf);
+//! leds.set_global_brightness(8);
+//! leds.write(colors.iter().copied()).await?;
+//! ```
+
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+use embassy_nrf::spim::{self, Config, Frequency, InterruptHandler, Spim};
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// Bytes of the start frame.
+const START: usize = 4;
+
+/// Highest global brightness.
+pub const MAX_GLOBAL_BRIGHTNESS: u8 = 31;
+
+/// Bytes of the end frame.
+const fn end_len(num_leds: usize) -> usize {
+    4 + num_leds.div_ceil(16)
+}
+
+/// SPI bytes of an [Apa102] driving `LEDS` LEDs, the start frame
+/// then 4 bytes per LED.
+///
+/// The end frame is made of zeros like the start frame, which is sent
+/// again as many times as needed instead of being stored.
+#[repr(C)]
+pub struct Buffer<const LEDS: usize> {
+    start: [u8; START],
+    leds: [[u8; 4]; LEDS],
+}
+
+impl<const LEDS: usize> Buffer<LEDS> {
+    /// Buffer of a frame with every LED off.
+    pub const fn new() -> Self {
+        Self {
+            start: [0; START],
+            leds: [[0; 4]; LEDS],
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `repr(C)` arrays of bytes are laid out without padding
+        unsafe { core::slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+impl<const LEDS: usize> Default for Buffer<LEDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// APA102 or SK9822 driver for a strip of `LEDS` LEDs.
+pub struct Apa102<'d, const LEDS: usize> {
+    spim: Spim<'d>,
+    buf: &'d mut Buffer<LEDS>,
+    color_order: ColorOrder,
+    global: u8,
+}
+
+impl<'d, const LEDS: usize> Apa102<'d, LEDS> {
+    /// Create a driver clocking the strip at 4 MHz.
+    pub fn new<T: spim::Instance>(
+        spim: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        sck: Peri<'d, impl Pin>,
+        mosi: Peri<'d, impl Pin>,
+        buf: &'d mut Buffer<LEDS>,
+    ) -> Self {
+        let mut config = Config::default();
+        config.frequency = Frequency::M4;
+        Self::with_config(spim, irq, sck, mosi, buf, config)
+    }
+
+    /// Create a driver with a custom SPIM configuration,
+    /// for example a faster clock for short strips.
+    pub fn with_config<T: spim::Instance>(
+        spim: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        sck: Peri<'d, impl Pin>,
+        mosi: Peri<'d, impl Pin>,
+        buf: &'d mut Buffer<LEDS>,
+        config: Config,
+    ) -> Self {
+        buf.start.fill(0);
+        Self {
+            spim: Spim::new_txonly(spim, irq, sck, mosi, config),
+            buf,
+            color_order: ColorOrder::Bgr,
+            global: MAX_GLOBAL_BRIGHTNESS,
+        }
+    }
+
+    /// Change the order of the color channels, applied from the next
+    /// write. APA102 and SK9822 use [ColorOrder::Bgr].
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.color_order = color_order;
+    }
+
+    /// Global brightness of every LED, from `0` to [MAX_GLOBAL_BRIGHTNESS].
+    pub fn global_brightness(&self) -> u8 {
+        self.global
+    }
+
+    /// Change the global brightness, applied from the next write.
+    ///
+    /// The LEDs dim by lowering their drive current instead of PWM,
+    /// so dimmed colors keep their full 8 bit resolution. Values
+    /// above [MAX_GLOBAL_BRIGHTNESS] are clamped.
+    pub fn set_global_brightness(&mut self, brightness: u8) {
+        self.global = brightness.min(MAX_GLOBAL_BRIGHTNESS);
+    }
+
+    /// Encode a frame, LEDs past the end of the colors are turned off.
+    fn encode(&mut self, mut colors: impl Iterator<Item = RGB8>) {
+        for led in self.buf.leds.iter_mut() {
+            let color = colors.next().unwrap_or_default();
+            let [a, b, c] = self.color_order.bytes(color);
+            *led = [0xe0 | self.global, a, b, c];
+        }
+    }
+
+    /// Number of start frames sent again as the end frame.
+    const END_FRAMES: usize = end_len(LEDS).div_ceil(START);
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Apa102<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.encode(iterator.into_iter().map(Into::into));
+        self.spim.write(self.buf.as_bytes()).await?;
+        for _ in 0..Self::END_FRAMES {
+            self.spim.write(&self.buf.start).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Apa102<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.encode(iterator.into_iter().map(Into::into));
+        self.spim.blocking_write(self.buf.as_bytes())?;
+        for _ in 0..Self::END_FRAMES {
+            self.spim.blocking_write(&self.buf.start)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_holds_start_then_leds() {
+        let mut buf = Buffer::<3>::new();
+        buf.leds[0] = [0xff, 1, 2, 3];
+        assert_eq!(
+            buf.as_bytes(),
+            [0, 0, 0, 0, 0xff, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn end_frame_covers_half_a_clock_per_led() {
+        // 4 bytes plus one per 16 LEDs, sent as 4 byte start frames
+        assert_eq!(Apa102::<'static, 0>::END_FRAMES, 1);
+        assert_eq!(Apa102::<'static, 16>::END_FRAMES, 2);
+        assert_eq!(Apa102::<'static, 64>::END_FRAMES, 2);
+        assert_eq!(Apa102::<'static, 65>::END_FRAMES, 3);
+    }
+}
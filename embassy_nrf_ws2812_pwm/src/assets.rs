@@ -0,0 +1,302 @@
+//! Palettes, images and recordings stored in external flash.
+//!
+//! Assets are read through the async `ReadNorFlash` trait so they can
+//! live in QSPI flash (as on the nRF52840 DK and Connect Kit) without
+//! memory mapping it. The flash holds a directory followed by the data:
+//!
+//! | Offset | Size | Field                              |
+//! |--------|------|------------------------------------|
+//! | 0      | 4    | Magic `WSAS`                       |
+//! | 4      | 2    | Number of entries (LE)             |
+//! | 6      | 2    | Reserved                           |
+//! | 8      | 24n  | Entries                            |
+//!
+//! Each entry is the [AssetKind] byte, 3 reserved bytes, a NUL padded
+//! name of [NAME_LEN] bytes then the offset (from the directory) and
+//! length of the data as little endian `u32`s. Palettes use the WLED
+//! byte format, images are RGB bytes and recordings use the format of
+//! the [recording](crate::recording) module.
+
+use crate::palette::{GradientPalette, MAX_STOPS, PaletteError};
+use crate::recording::{HEADER_LEN, RecordingError, RecordingHeader};
+use embedded_storage_async::nor_flash::ReadNorFlash;
+use smart_leds::RGB8;
+
+/// Magic bytes at the start of the asset directory.
+pub const MAGIC: [u8; 4] = *b"WSAS";
+
+/// Maximum length of an asset name.
+pub const NAME_LEN: usize = 12;
+
+const DIRECTORY_LEN: u32 = 8;
+const ENTRY_LEN: u32 = 24;
+
+/// Type of data stored in an asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    /// WLED gradient palette.
+    Palette = 1,
+    /// RGB bytes, for example a matrix image.
+    Image = 2,
+    /// Recorded animation.
+    Recording = 3,
+}
+
+/// Error reading an asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AssetError<E> {
+    /// Error from the flash.
+    Flash(E),
+    /// Missing or wrong magic bytes.
+    Magic,
+    /// No asset with the requested name or index.
+    NotFound,
+    /// Asset is not of the requested kind.
+    Kind,
+    /// Asset data is shorter than expected.
+    Length,
+    /// Invalid palette data.
+    Palette(PaletteError),
+    /// Invalid recording data.
+    Recording(RecordingError),
+}
+
+impl<E> From<E> for AssetError<E> {
+    fn from(value: E) -> Self {
+        AssetError::Flash(value)
+    }
+}
+
+/// Directory entry of an asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Asset {
+    /// Type of data.
+    pub kind: AssetKind,
+    name: [u8; NAME_LEN],
+    offset: u32,
+    len: u32,
+}
+
+impl Asset {
+    /// Name of the asset.
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+
+    /// Length of the data in bytes.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Whether the asset has no data.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Assets stored in flash at a base address.
+pub struct Assets<F> {
+    flash: F,
+    base: u32,
+    count: u16,
+}
+
+impl<F: ReadNorFlash> Assets<F> {
+    /// Read the asset directory at `base`.
+    pub async fn mount(mut flash: F, base: u32) -> Result<Self, AssetError<F::Error>> {
+        let mut directory = [0; DIRECTORY_LEN as usize];
+        read(&mut flash, base, &mut directory).await?;
+        if directory[..4] != MAGIC {
+            return Err(AssetError::Magic);
+        }
+        Ok(Self {
+            flash,
+            base,
+            count: u16::from_le_bytes([directory[4], directory[5]]),
+        })
+    }
+
+    /// Release the flash.
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    /// Number of assets.
+    pub fn len(&self) -> u16 {
+        self.count
+    }
+
+    /// Whether there are no assets.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Directory entry at an index.
+    pub async fn entry(&mut self, index: u16) -> Result<Asset, AssetError<F::Error>> {
+        if index >= self.count {
+            return Err(AssetError::NotFound);
+        }
+        let mut entry = [0; ENTRY_LEN as usize];
+        let address = self.base + DIRECTORY_LEN + index as u32 * ENTRY_LEN;
+        read(&mut self.flash, address, &mut entry).await?;
+
+        let kind = match entry[0] {
+            1 => AssetKind::Palette,
+            2 => AssetKind::Image,
+            3 => AssetKind::Recording,
+            _ => return Err(AssetError::Kind),
+        };
+        let mut name = [0; NAME_LEN];
+        name.copy_from_slice(&entry[4..4 + NAME_LEN]);
+        let word =
+            |i: usize| u32::from_le_bytes([entry[i], entry[i + 1], entry[i + 2], entry[i + 3]]);
+        Ok(Asset {
+            kind,
+            name,
+            offset: word(16),
+            len: word(20),
+        })
+    }
+
+    /// Find an asset by name.
+    pub async fn find(&mut self, name: &str) -> Result<Asset, AssetError<F::Error>> {
+        for index in 0..self.count {
+            let asset = self.entry(index).await?;
+            if asset.name() == name {
+                return Ok(asset);
+            }
+        }
+        Err(AssetError::NotFound)
+    }
+
+    /// Read part of the data of an asset, stopping at its end.
+    ///
+    /// Returns the number of bytes read.
+    pub async fn read(
+        &mut self,
+        asset: &Asset,
+        offset: u32,
+        buf: &mut [u8],
+    ) -> Result<usize, AssetError<F::Error>> {
+        let len = (asset.len.saturating_sub(offset) as usize).min(buf.len());
+        let address = self.base + asset.offset + offset;
+        read(&mut self.flash, address, &mut buf[..len]).await?;
+        Ok(len)
+    }
+
+    /// Load a palette asset.
+    pub async fn palette(
+        &mut self,
+        asset: &Asset,
+    ) -> Result<GradientPalette, AssetError<F::Error>> {
+        expect_kind(asset, AssetKind::Palette)?;
+        let mut bytes = [0; MAX_STOPS * 4];
+        let len = self.read(asset, 0, &mut bytes).await?;
+        GradientPalette::from_wled(&bytes[..len]).map_err(AssetError::Palette)
+    }
+
+    /// Read the colors of an image asset into `out`.
+    ///
+    /// Returns the number of colors read.
+    pub async fn image(
+        &mut self,
+        asset: &Asset,
+        out: &mut [RGB8],
+    ) -> Result<usize, AssetError<F::Error>> {
+        expect_kind(asset, AssetKind::Image)?;
+        let len = out.len().min(asset.len as usize / 3);
+        self.colors(asset, 0, &mut out[..len]).await?;
+        Ok(len)
+    }
+
+    /// Header of a recording asset.
+    pub async fn recording(
+        &mut self,
+        asset: &Asset,
+    ) -> Result<RecordingHeader, AssetError<F::Error>> {
+        expect_kind(asset, AssetKind::Recording)?;
+        let mut header = [0; HEADER_LEN];
+        if self.read(asset, 0, &mut header).await? < HEADER_LEN {
+            return Err(AssetError::Length);
+        }
+        RecordingHeader::parse(&header).map_err(AssetError::Recording)
+    }
+
+    /// Read a frame of a recording asset into `out`.
+    ///
+    /// LEDs beyond the end of `out` are skipped.
+    pub async fn frame(
+        &mut self,
+        asset: &Asset,
+        header: &RecordingHeader,
+        index: usize,
+        out: &mut [RGB8],
+    ) -> Result<(), AssetError<F::Error>> {
+        if index >= header.frames {
+            return Err(AssetError::NotFound);
+        }
+        let offset = HEADER_LEN + index * header.frame_len();
+        let leds = header.leds.min(out.len());
+        self.colors(asset, offset as u32, &mut out[..leds]).await
+    }
+
+    /// Read RGB bytes at `offset` in an asset into `out`.
+    async fn colors(
+        &mut self,
+        asset: &Asset,
+        mut offset: u32,
+        out: &mut [RGB8],
+    ) -> Result<(), AssetError<F::Error>> {
+        let mut bytes = [0; 48];
+        for colors in out.chunks_mut(bytes.len() / 3) {
+            let bytes = &mut bytes[..colors.len() * 3];
+            if self.read(asset, offset, bytes).await? < bytes.len() {
+                return Err(AssetError::Length);
+            }
+            for (color, rgb) in colors.iter_mut().zip(bytes.chunks_exact(3)) {
+                *color = RGB8::new(rgb[0], rgb[1], rgb[2]);
+            }
+            offset += bytes.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+fn expect_kind<E>(asset: &Asset, kind: AssetKind) -> Result<(), AssetError<E>> {
+    if asset.kind == kind {
+        Ok(())
+    } else {
+        Err(AssetError::Kind)
+    }
+}
+
+/// Word aligned scratch buffer, QSPI EasyDMA needs aligned RAM.
+#[repr(align(4))]
+struct Scratch([u8; 64]);
+
+/// Read any range of flash regardless of the read size of the flash.
+async fn read<F: ReadNorFlash>(
+    flash: &mut F,
+    mut address: u32,
+    mut buf: &mut [u8],
+) -> Result<(), F::Error> {
+    const { assert!(64 % F::READ_SIZE == 0, "unsupported flash read size") };
+    let align = F::READ_SIZE as u32;
+    let mut scratch = Scratch([0; 64]);
+    while !buf.is_empty() {
+        let start = address - address % align;
+        let skip = (address - start) as usize;
+        let len = (skip + buf.len())
+            .next_multiple_of(F::READ_SIZE)
+            .min(scratch.0.len());
+        flash.read(start, &mut scratch.0[..len]).await?;
+
+        let n = (len - skip).min(buf.len());
+        buf[..n].copy_from_slice(&scratch.0[skip..skip + n]);
+        address += n as u32;
+        buf = &mut buf[n..];
+    }
+    Ok(())
+}
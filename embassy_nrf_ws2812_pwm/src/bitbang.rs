@@ -0,0 +1,96 @@
+//! Bit-banged WS2812 driver for pins without PWM routing.
+//!
+//! [Ws2812BitBang] toggles any [OutputPin] and waits with a [DelayNs],
+//! which only holds the WS2812 timing when
+//!
+//! - the delay is accurate to about 100 ns, a cycle counting busy wait
+//!   rather than a timer based one,
+//! - setting the pin takes a few cycles, the embassy-nrf [Output]
+//!   writes one register,
+//! - nothing interrupts a frame, so wrap the write in a critical
+//!   section or run it from the highest priority. The SoftDevice
+//!   controller can still preempt it and corrupt the frame.
+//!
+//! The high times come out longer than configured by the time the pin
+//! and delay calls take, pick a [Timing] with short high times and
+//! check the waveform on a scope. It suits a status LED or a few LEDs,
+//! use the PWM driver for strips.
+//!
+//! [Output]: embassy_nrf::gpio::Output
+
+use crate::color_order::ColorOrder;
+use crate::timing::Timing;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// WS2812 driver toggling a GPIO from software.
+pub struct Ws2812BitBang<P, D> {
+    pin: P,
+    delay: D,
+    timing: Timing,
+    color_order: ColorOrder,
+}
+
+impl<P: OutputPin, D: DelayNs> Ws2812BitBang<P, D> {
+    /// Create a driver using the default [Timing] (WS2812B).
+    pub fn new(pin: P, delay: D) -> Self {
+        Self::new_with_timing(pin, delay, Timing::default())
+    }
+
+    /// Create a driver with custom timings.
+    pub fn new_with_timing(pin: P, delay: D, timing: Timing) -> Self {
+        Self {
+            pin,
+            delay,
+            timing,
+            color_order: ColorOrder::default(),
+        }
+    }
+
+    /// Change the order of the color channels, applied from the next write.
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.color_order = color_order;
+    }
+
+    /// Give back the pin and delay.
+    pub fn release(self) -> (P, D) {
+        (self.pin, self.delay)
+    }
+
+    fn send_byte(&mut self, byte: u8) -> Result<(), P::Error> {
+        for bit in 0..8 {
+            let high = if byte & (0x80 >> bit) != 0 {
+                self.timing.t1h
+            } else {
+                self.timing.t0h
+            };
+            self.pin.set_high()?;
+            self.delay.delay_ns(high);
+            self.pin.set_low()?;
+            self.delay.delay_ns(self.timing.period - high);
+        }
+        Ok(())
+    }
+}
+
+impl<P: OutputPin, D: DelayNs> SmartLedsWrite for Ws2812BitBang<P, D> {
+    type Error = P::Error;
+    type Color = RGB8;
+
+    /// Send a frame, see the [module docs](self) for the timing caveats.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.pin.set_low()?;
+        for color in iterator {
+            for byte in self.color_order.bytes(color.into()) {
+                self.send_byte(byte)?;
+            }
+        }
+        self.delay.delay_us(self.timing.reset);
+        Ok(())
+    }
+}
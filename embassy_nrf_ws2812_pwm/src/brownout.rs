@@ -0,0 +1,132 @@
+//! Blank the strip when the supply is about to fail.
+//!
+//! The power-fail comparator (POFCON) raises the POFWARN event when
+//! VDD drops below a threshold, well before the brown-out reset. A
+//! strip left showing its last frame while the supply collapses can
+//! get stuck at full brightness and pull the supply down further, so
+//! blanking it on the warning gives the rest of the system (such as
+//! pending flash writes) the best chance to finish.
+//!
+//! The warning is raised by [InterruptHandler] in the `CLOCK_POWER`
+//! interrupt. With the SoftDevice Controller the MPSL owns that
+//! interrupt, bind both handlers, [InterruptHandler] first:
+//!
+//! ```ignore
+//! bind_interrupts!(struct Irqs {
+//!     CLOCK_POWER => brownout::InterruptHandler, nrf_sdc::mpsl::ClockInterruptHandler;
+//! });
+//! brownout::enable(Threshold::V28, Irqs);
+//! ```
+//!
+//! Only nRF52 chips have the comparator in the POWER peripheral, the
+//! module needs one of the `nrf52*` chip features of this crate.
+
+use crate::Ws2812;
+#[cfg(feature = "async")]
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "async")]
+use core::task::Poll;
+use embassy_nrf::interrupt::typelevel::{Binding, CLOCK_POWER, Handler, Interrupt};
+use embassy_nrf::pac;
+use embassy_nrf::pac::power::vals;
+#[cfg(feature = "async")]
+use embassy_sync::waitqueue::AtomicWaker;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// Set by [InterruptHandler] on the warning.
+static WARNED: AtomicBool = AtomicBool::new(false);
+#[cfg(feature = "async")]
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// VDD level raising the power-fail warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// 2.1 V.
+    V21,
+    /// 2.2 V.
+    V22,
+    /// 2.3 V.
+    V23,
+    /// 2.4 V.
+    V24,
+    /// 2.5 V.
+    V25,
+    /// 2.6 V.
+    V26,
+    /// 2.7 V.
+    V27,
+    /// 2.8 V.
+    V28,
+}
+
+impl Threshold {
+    fn to_pac(self) -> vals::Threshold {
+        match self {
+            Threshold::V21 => vals::Threshold::V21,
+            Threshold::V22 => vals::Threshold::V22,
+            Threshold::V23 => vals::Threshold::V23,
+            Threshold::V24 => vals::Threshold::V24,
+            Threshold::V25 => vals::Threshold::V25,
+            Threshold::V26 => vals::Threshold::V26,
+            Threshold::V27 => vals::Threshold::V27,
+            Threshold::V28 => vals::Threshold::V28,
+        }
+    }
+}
+
+/// Interrupt handler of the power-fail warning.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl Handler<CLOCK_POWER> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        // Clear only this event, the clock events are left to the MPSL
+        if pac::POWER.events_pofwarn().read() != 0 {
+            pac::POWER.events_pofwarn().write_value(0);
+            WARNED.store(true, Ordering::Release);
+            #[cfg(feature = "async")]
+            WAKER.wake();
+        }
+    }
+}
+
+/// Enable the power-fail comparator at `threshold` and its interrupt,
+/// `irq` binds [InterruptHandler] to `CLOCK_POWER`.
+pub fn enable(threshold: Threshold, _irq: impl Binding<CLOCK_POWER, InterruptHandler>) {
+    pac::POWER.events_pofwarn().write_value(0);
+    WARNED.store(false, Ordering::Release);
+    pac::POWER.pofcon().write(|w| {
+        w.set_pof(true);
+        w.set_threshold(threshold.to_pac());
+    });
+    pac::POWER.intenset().write(|w| w.set_pofwarn(true));
+    // Not unpended, a pending interrupt may be a clock event of the MPSL
+    unsafe { CLOCK_POWER::enable() };
+}
+
+/// Whether the supply dropped below the threshold since the last call.
+pub fn warning() -> bool {
+    WARNED.swap(false, Ordering::AcqRel)
+}
+
+/// Wait for the power-fail warning.
+#[cfg(feature = "async")]
+pub async fn wait() {
+    poll_fn(|cx| {
+        WAKER.register(cx.waker());
+        if warning() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Turn every LED off with the blocking write, ignoring errors
+/// as there is nothing left to do about them.
+pub fn blank<const LEDS: usize>(ws: &mut Ws2812<'_, LEDS>) {
+    let _ = SmartLedsWrite::write(ws, core::iter::repeat_n(RGB8::default(), LEDS));
+}
@@ -0,0 +1,277 @@
+//! Builder for configuring a [Ws2812] driver.
+
+use crate::color_order::ColorOrder;
+use crate::correction::Correction;
+use crate::error::Error;
+use crate::power::PowerModel;
+use crate::pwm::Prescaler;
+use crate::stream::Ws2812Stream;
+use crate::timing::Timing;
+use crate::{Buffer, Instance, InterruptHandler, OverflowPolicy, Samples, TailPolicy, Ws2812};
+use embassy_nrf::interrupt::typelevel::Binding;
+use embassy_nrf::{Peri, gpio::AnyPin, gpio::Level, gpio::OutputDrive, gpio::Pin};
+
+/// Configure a [Ws2812] driver before creating it.
+///
+/// ```ignore
+/// let ws = Ws2812Builder::new()
+///     .timing(Timing::WS2811)
+///     .color_order(ColorOrder::Rgb)
+///     .drive(OutputDrive::HighDrive)
+///     .build(p.PWM0, Irqs, p.P0_13, buf)?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ws2812Builder {
+    pub(crate) timing: Timing,
+    pub(crate) color_order: ColorOrder,
+    pub(crate) drive: OutputDrive,
+    pub(crate) inverted: bool,
+    pub(crate) refresh: u32,
+    pub(crate) gamma: f32,
+    pub(crate) brightness: u8,
+    pub(crate) correction: Correction,
+    pub(crate) tail: TailPolicy,
+    pub(crate) overflow: OverflowPolicy,
+    pub(crate) hold: bool,
+    // Prescaler has no defmt::Format impl
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub(crate) prescaler: Prescaler,
+    pub(crate) idle: Option<Level>,
+    pub(crate) power: PowerModel,
+    pub(crate) max_current: Option<u32>,
+}
+
+impl Default for Ws2812Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ws2812Builder {
+    /// Builder with the default WS2812B configuration.
+    pub const fn new() -> Self {
+        Self {
+            timing: Timing::WS2812B,
+            color_order: ColorOrder::Grb,
+            drive: OutputDrive::HighDrive0Standard1,
+            inverted: false,
+            refresh: 0,
+            gamma: 1.0,
+            brightness: u8::MAX,
+            correction: Correction::NONE,
+            tail: TailPolicy::Black,
+            overflow: OverflowPolicy::Truncate,
+            hold: false,
+            prescaler: Prescaler::Div1,
+            idle: None,
+            power: PowerModel::WS2812B,
+            max_current: None,
+        }
+    }
+
+    /// Bit timings of the LED chip.
+    ///
+    /// Replaces any reset time set with [Ws2812Builder::reset_micros].
+    pub const fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Order of the color channels.
+    pub const fn color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
+    }
+
+    /// Drive strength of the data pin.
+    pub const fn drive(mut self, drive: OutputDrive) -> Self {
+        self.drive = drive;
+        self
+    }
+
+    /// Time to hold the line idle to latch a frame.
+    pub const fn reset_micros(mut self, reset: u32) -> Self {
+        self.timing.reset = reset;
+        self
+    }
+
+    /// Invert the output for strips driven through an inverting
+    /// level shifter, the line idles high and bits start low.
+    pub const fn inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// Level the pin is driven to while no frame is sent, low by
+    /// default and high when [inverted](Ws2812Builder::inverted).
+    ///
+    /// Unless the last frame is held, the PWM is stopped after every
+    /// frame and the pin is held at this level instead of floating or
+    /// keeping the level of the last sample, which some strips read as
+    /// noise. Only change it for
+    /// strips that need the other level, a frame always latches with
+    /// the line at the inactive level of the bits.
+    pub const fn idle_level(mut self, level: Level) -> Self {
+        self.idle = Some(level);
+        self
+    }
+
+    /// Level the pin idles at.
+    pub(crate) const fn idle(&self) -> Level {
+        match self.idle {
+            Some(level) => level,
+            None if self.inverted => Level::High,
+            None => Level::Low,
+        }
+    }
+
+    /// Number of extra PWM periods each sample is held for.
+    ///
+    /// The pulse of a sample repeats for every extra period which
+    /// stretches the frame in coarse steps without changing the
+    /// [Timing], the default of `0` sends every bit once.
+    pub const fn refresh(mut self, refresh: u32) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Gamma correction applied while encoding, see [Ws2812::set_gamma].
+    pub const fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Brightness applied to every frame, see [Ws2812::set_brightness].
+    pub const fn brightness(mut self, brightness: u8) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    /// White balance applied to every frame, see [Ws2812::set_correction].
+    pub const fn correction(mut self, correction: Correction) -> Self {
+        self.correction = correction;
+        self
+    }
+
+    /// What to send past the end of short writes, see [TailPolicy].
+    pub const fn tail_policy(mut self, tail: TailPolicy) -> Self {
+        self.tail = tail;
+        self
+    }
+
+    /// What to do with colors past the end of the strip, see [OverflowPolicy].
+    pub const fn overflow_policy(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Keep repeating the last frame with the PWM looping the sequence,
+    /// without CPU involvement, until the next write stops the loop.
+    ///
+    /// Some clone LEDs show ghosting unless the frame is latched
+    /// over and over. The looping sequence reads the sample buffer
+    /// so this needs [Ws2812Builder::build], building an owned or
+    /// double buffered driver fails with [Error::InvalidConfig].
+    pub const fn hold_last_frame(mut self, hold: bool) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    /// Divide the 16 MHz PWM clock, `Div1` by default.
+    ///
+    /// Coarser ticks round the bit timings further, check the result
+    /// with [Timing::is_valid_with] and against the chip tolerances.
+    pub const fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Current drawn by the LEDs, used by [Ws2812::estimate_current_ma].
+    pub const fn power_model(mut self, model: PowerModel) -> Self {
+        self.power = model;
+        self
+    }
+
+    /// Limit the estimated current of every frame to `max_current` mA,
+    /// for example 500 mA for a strip powered from USB.
+    ///
+    /// Frames over the budget are scaled down evenly after encoding,
+    /// using the [power_model](Ws2812Builder::power_model).
+    /// Not applied by [Ws2812Builder::build_streaming].
+    pub const fn max_current_ma(mut self, max_current: u32) -> Self {
+        self.max_current = Some(max_current);
+        self
+    }
+
+    /// Create the driver.
+    pub fn build<'d, T: Instance, const LEDS: usize>(
+        self,
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Samples<LEDS>,
+    ) -> Result<Ws2812<'d, LEDS>, Error> {
+        Ws2812::with_config(pwm, pin, None, Buffer::Borrowed(buf), None, self)
+    }
+
+    /// Create a driver sending every frame on `backup` as well.
+    ///
+    /// WS2815 and other chips with a backup data input also receive
+    /// the data sent to the LED before them, so a dead LED doesn't cut
+    /// off the rest of the strip. The first LED has no LED before it,
+    /// wire its backup input to `backup`, for example `p.P0_14.into()`.
+    pub fn build_with_backup<'d, T: Instance, const LEDS: usize>(
+        self,
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        backup: Peri<'d, AnyPin>,
+        buf: &'d mut Samples<LEDS>,
+    ) -> Result<Ws2812<'d, LEDS>, Error> {
+        Ws2812::with_config(pwm, pin, Some(backup), Buffer::Borrowed(buf), None, self)
+    }
+
+    /// Create a driver which alternates between two sample buffers.
+    ///
+    /// Writes wait for the previous frame instead of their own, so
+    /// encoding a frame overlaps sending the one before it. Without a
+    /// shadow buffer writes should cover the whole strip, as
+    /// [TailPolicy::KeepPrevious] and [Ws2812::set_range] see the frame
+    /// before last.
+    pub fn build_double_buffered<'d, T: Instance, const LEDS: usize>(
+        self,
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Samples<LEDS>,
+        back: &'d mut Samples<LEDS>,
+    ) -> Result<Ws2812<'d, LEDS>, Error> {
+        Ws2812::with_config(pwm, pin, None, Buffer::Borrowed(buf), Some(back), self)
+    }
+
+    /// Create a driver which owns its sample buffer, see [Ws2812::new_owned].
+    pub fn build_owned<'d, T: Instance, const LEDS: usize>(
+        self,
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+    ) -> Result<Ws2812<'d, LEDS>, Error> {
+        Ws2812::with_config(pwm, pin, None, Buffer::Owned([[0; 24]; LEDS]), None, self)
+    }
+
+    /// Create a driver streaming frames of any length through two
+    /// small buffers, see [Ws2812Stream].
+    ///
+    /// Every write sends a whole frame, so the tail, overflow,
+    /// hold and buffering options don't apply.
+    pub fn build_streaming<'d, T: Instance, const LEDS: usize>(
+        self,
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        bufs: &'d mut [Samples<LEDS>; 2],
+    ) -> Result<Ws2812Stream<'d, LEDS>, Error> {
+        Ws2812Stream::with_config(pwm, pin, bufs, self)
+    }
+}
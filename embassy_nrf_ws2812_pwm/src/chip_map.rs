@@ -0,0 +1,81 @@
+//! Strips mixing RGB and RGBW chips on one data line.
+//!
+//! A [ChipMap] lists consecutive ranges of LEDs and their chip type
+//! so a frame can switch between 24 and 32 bit encoding part way
+//! through, for example for an RGBW ring chained after an RGB strip.
+//! Frames are encoded with [Ws2812::encode_mixed](crate::Ws2812::encode_mixed)
+//! into a buffer of [ChipMap::buffer_len] samples and sent with
+//! [Ws2812::transmit](crate::Ws2812::transmit).
+
+/// Type of chip in a range of LEDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    /// Three channels in the color order of the driver.
+    Rgb,
+    /// Three channels in the color order of the driver then white.
+    Rgbw,
+}
+
+impl Chip {
+    /// Number of PWM samples per LED.
+    pub const fn samples(&self) -> usize {
+        match self {
+            Chip::Rgb => 24,
+            Chip::Rgbw => 32,
+        }
+    }
+}
+
+/// Consecutive LEDs of the same chip type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipRange {
+    pub chip: Chip,
+    pub len: usize,
+}
+
+/// Chip types along a strip, in wiring order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipMap<'a> {
+    ranges: &'a [ChipRange],
+}
+
+impl<'a> ChipMap<'a> {
+    /// Map made of `ranges` in wiring order.
+    pub const fn new(ranges: &'a [ChipRange]) -> Self {
+        Self { ranges }
+    }
+
+    /// Number of LEDs.
+    pub const fn len(&self) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < self.ranges.len() {
+            len += self.ranges[i].len;
+            i += 1;
+        }
+        len
+    }
+
+    /// Whether the map has no LEDs.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of PWM samples needed for a frame.
+    pub const fn buffer_len(&self) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < self.ranges.len() {
+            len += self.ranges[i].len * self.ranges[i].chip.samples();
+            i += 1;
+        }
+        len
+    }
+
+    /// Chip type of every LED in wiring order.
+    pub fn chips(&self) -> impl Iterator<Item = Chip> + 'a {
+        self.ranges
+            .iter()
+            .flat_map(|range| core::iter::repeat_n(range.chip, range.len))
+    }
+}
@@ -0,0 +1,115 @@
+//! Time sources for animations.
+//!
+//! Animations take a [Clock] rather than reading the embassy time
+//! driver directly so frames can be rendered with a mocked clock (for
+//! example on the host) as well as on the device. Clocks return the
+//! crate's own [Instant], counted in microseconds, so the trait is
+//! available without the `async` feature and without a time driver.
+//! Waiting goes through the clock too, so a mocked clock also controls
+//! how long a [FramePacer](crate::pacing::FramePacer) sleeps.
+
+use core::future::{Future, ready};
+#[cfg(feature = "async")]
+use core::ops::Add;
+#[cfg(feature = "async")]
+use embassy_time::Duration;
+
+/// Point in time in microseconds since an arbitrary start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instant {
+    micros: u64,
+}
+
+impl Instant {
+    /// Instant `micros` microseconds after the start.
+    pub const fn from_micros(micros: u64) -> Self {
+        Self { micros }
+    }
+
+    /// Instant `millis` milliseconds after the start.
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::from_micros(millis * 1000)
+    }
+
+    /// Microseconds since the start.
+    pub const fn as_micros(&self) -> u64 {
+        self.micros
+    }
+
+    /// Microseconds from `earlier` to this instant, `0`
+    /// when `earlier` is later.
+    pub const fn micros_since(&self, earlier: Instant) -> u64 {
+        self.micros.saturating_sub(earlier.micros)
+    }
+
+    /// Time from `earlier` to this instant, zero when `earlier` is later.
+    #[cfg(feature = "async")]
+    pub const fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_micros(self.micros_since(earlier))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant::from_micros(self.micros + rhs.as_micros())
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<embassy_time::Instant> for Instant {
+    fn from(instant: embassy_time::Instant) -> Self {
+        Instant::from_micros(instant.as_micros())
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<Instant> for embassy_time::Instant {
+    fn from(instant: Instant) -> Self {
+        embassy_time::Instant::from_micros(instant.as_micros())
+    }
+}
+
+/// Source of the current time.
+pub trait Clock {
+    /// Current time.
+    fn now(&self) -> Instant;
+
+    /// Wait until the clock reaches `at`, returns right
+    /// away when it is already past.
+    fn sleep_until(&self, at: Instant) -> impl Future<Output = ()>;
+}
+
+/// Clock backed by the embassy time driver.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "async")]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        embassy_time::Instant::now().into()
+    }
+
+    fn sleep_until(&self, at: Instant) -> impl Future<Output = ()> {
+        embassy_time::Timer::at(at.into())
+    }
+}
+
+/// Any `now()` closure can be used as a clock. Sleeping on it returns
+/// right away, the caller moves the mocked time between frames.
+impl<F> Clock for F
+where
+    F: Fn() -> Instant,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+
+    fn sleep_until(&self, _at: Instant) -> impl Future<Output = ()> {
+        ready(())
+    }
+}
@@ -0,0 +1,79 @@
+//! Order in which color channels are sent to the LEDs.
+
+use smart_leds::{RGB, RGB8};
+
+/// Order of the color channels on the wire.
+///
+/// WS2812 chips expect green, red then blue but many clones
+/// and other chips use a different order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    #[default]
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+/// A single color channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl ColorOrder {
+    /// Color order from the channels seen when only the first,
+    /// second and third byte on the wire are set.
+    ///
+    /// Returns `None` if a channel is repeated.
+    pub const fn from_channels(channels: [Channel; 3]) -> Option<Self> {
+        use Channel::*;
+        Some(match channels {
+            [Red, Green, Blue] => ColorOrder::Rgb,
+            [Red, Blue, Green] => ColorOrder::Rbg,
+            [Green, Red, Blue] => ColorOrder::Grb,
+            [Green, Blue, Red] => ColorOrder::Gbr,
+            [Blue, Red, Green] => ColorOrder::Brg,
+            [Blue, Green, Red] => ColorOrder::Bgr,
+            _ => return None,
+        })
+    }
+
+    /// Channel bytes of a color in transmission order.
+    pub const fn bytes(&self, color: RGB8) -> [u8; 3] {
+        self.channels(color)
+    }
+
+    /// Color of channel bytes in transmission order,
+    /// the inverse of [ColorOrder::bytes].
+    pub const fn color(&self, bytes: [u8; 3]) -> RGB8 {
+        let (r, g, b) = match (self, bytes) {
+            (ColorOrder::Rgb, [r, g, b]) => (r, g, b),
+            (ColorOrder::Rbg, [r, b, g]) => (r, g, b),
+            (ColorOrder::Grb, [g, r, b]) => (r, g, b),
+            (ColorOrder::Gbr, [g, b, r]) => (r, g, b),
+            (ColorOrder::Brg, [b, r, g]) => (r, g, b),
+            (ColorOrder::Bgr, [b, g, r]) => (r, g, b),
+        };
+        RGB8 { r, g, b }
+    }
+
+    /// Channels of a color of any depth in transmission order.
+    pub const fn channels<T: Copy>(&self, color: RGB<T>) -> [T; 3] {
+        let RGB { r, g, b } = color;
+        match self {
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Rbg => [r, b, g],
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Gbr => [g, b, r],
+            ColorOrder::Brg => [b, r, g],
+            ColorOrder::Bgr => [b, g, r],
+        }
+    }
+}
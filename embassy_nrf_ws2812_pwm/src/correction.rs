@@ -0,0 +1,106 @@
+//! White balance correction applied while encoding.
+//!
+//! The red, green and blue dies of an LED differ in efficiency between
+//! chip types and production batches, so full white comes out tinted.
+//! A [Correction] scales each channel to balance it. Corrections for
+//! the LEDs and for the color temperature of the light combine with
+//! [Correction::combine].
+//!
+//! ```ignore
+//! let correction = Correction::TYPICAL_LED_STRIP.combine(Correction::from_kelvin(3000));
+//! ws.set_correction(correction);
+//! ```
+
+use libm::{logf, powf};
+use smart_leds::RGB8;
+
+use crate::scale;
+
+/// Scale of each color channel, `255` leaves a channel unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Correction {
+    /// Scale of the red channel.
+    pub red: u8,
+    /// Scale of the green channel.
+    pub green: u8,
+    /// Scale of the blue channel.
+    pub blue: u8,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl Correction {
+    /// No correction.
+    pub const NONE: Self = Self::new(255, 255, 255);
+    /// Typical 5050 SMD strips, the `TypicalLEDStrip` profile of FastLED.
+    pub const TYPICAL_LED_STRIP: Self = Self::new(255, 176, 240);
+    /// Typical through hole pixel strings.
+    pub const TYPICAL_PIXEL_STRING: Self = Self::new(255, 224, 140);
+
+    /// Candle light, about 1900 K.
+    pub const CANDLE: Self = Self::new(255, 147, 41);
+    /// 100 W tungsten bulb, about 2850 K.
+    pub const TUNGSTEN_100W: Self = Self::new(255, 214, 170);
+    /// Halogen bulb, about 3200 K.
+    pub const HALOGEN: Self = Self::new(255, 241, 224);
+    /// Overcast sky, about 7000 K.
+    pub const OVERCAST_SKY: Self = Self::new(201, 226, 255);
+    /// Clear blue sky, about 20000 K.
+    pub const CLEAR_BLUE_SKY: Self = Self::new(64, 156, 255);
+
+    /// Correction scaling each channel by about `value / 255`, measure a
+    /// white LED with a colorimeter or match it to another by eye.
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// White point of a black body at `kelvin`, from 1000 K to 40000 K.
+    ///
+    /// Uses the curve fit by Tanner Helland, good enough to warm up
+    /// or cool down white but not a calibrated measurement.
+    pub fn from_kelvin(kelvin: u32) -> Self {
+        let temp = kelvin.clamp(1000, 40000) as f32 / 100.0;
+        let red = if temp <= 66.0 {
+            255.0
+        } else {
+            329.69873 * powf(temp - 60.0, -0.13320476)
+        };
+        let green = if temp <= 66.0 {
+            99.4708 * logf(temp) - 161.11957
+        } else {
+            288.12216 * powf(temp - 60.0, -0.075514846)
+        };
+        let blue = if temp >= 66.0 {
+            255.0
+        } else if temp <= 19.0 {
+            0.0
+        } else {
+            138.51773 * logf(temp - 10.0) - 305.0448
+        };
+        // Float to int casts saturate, clamping to 0..=255
+        Self::new(red as u8, green as u8, blue as u8)
+    }
+
+    /// Apply `other` on top of this correction.
+    pub const fn combine(self, other: Self) -> Self {
+        Self::new(
+            scale(self.red, other.red),
+            scale(self.green, other.green),
+            scale(self.blue, other.blue),
+        )
+    }
+
+    /// Scale each channel of `color`.
+    pub const fn apply(&self, color: RGB8) -> RGB8 {
+        RGB8 {
+            r: scale(color.r, self.red),
+            g: scale(color.g, self.green),
+            b: scale(color.b, self.blue),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Multi-sensor status strip.
+//!
+//! A [Dashboard] shows a set of named sensor channels on regions of
+//! the strip. Each [Gauge] maps its readings through a [Modulation]
+//! onto a [GradientPalette], so a temperature can run from blue to red
+//! next to a light level running from dark to white. Gauges are
+//! registered at runtime, fed readings as they arrive and the whole
+//! dashboard is rendered into a frame at the refresh rate.
+
+use crate::modulation::{Curve, Modulation, Target};
+use crate::palette::GradientPalette;
+use core::ops::Range;
+use smart_leds::RGB8;
+
+/// Error registering or updating a gauge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DashboardError {
+    /// Every channel is in use.
+    Full,
+    /// A gauge with the same name is already registered.
+    Duplicate,
+    /// No gauge with the name is registered.
+    NotFound,
+}
+
+/// How a gauge shows its reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// Light part of the region, each LED in the color of its position.
+    #[default]
+    Bar,
+    /// Light the whole region in the color of the reading.
+    Fill,
+}
+
+/// Sensor channel shown on a region of the strip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gauge {
+    name: &'static str,
+    region: Range<usize>,
+    scale: Modulation,
+    palette: GradientPalette,
+    style: Style,
+    position: Option<u8>,
+}
+
+impl Gauge {
+    /// Gauge mapping readings from `min` to `max` onto `palette`
+    /// linearly, shown as a bar on the LEDs in `region`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max` or either is NaN.
+    pub const fn new(
+        name: &'static str,
+        region: Range<usize>,
+        min: f32,
+        max: f32,
+        palette: GradientPalette,
+    ) -> Self {
+        Self {
+            name,
+            region,
+            scale: Modulation::new(Target::Palette, Curve::Linear, min, max, 0, u8::MAX),
+            palette,
+            style: Style::Bar,
+            position: None,
+        }
+    }
+
+    /// Map readings through a different curve.
+    pub const fn curve(mut self, curve: Curve) -> Self {
+        self.scale.curve = curve;
+        self
+    }
+
+    /// Smooth readings, see [Modulation::with_smoothing].
+    pub const fn smoothing(mut self, alpha: f32) -> Self {
+        self.scale = self.scale.with_smoothing(alpha);
+        self
+    }
+
+    /// Show the reading with a different [Style].
+    pub const fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Name of the sensor channel.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn render(&self, out: &mut [RGB8]) {
+        let end = self.region.end.min(out.len());
+        let Some(region) = out.get_mut(self.region.start.min(end)..end) else {
+            return;
+        };
+        let Some(position) = self.position else {
+            // No reading yet
+            region.fill(RGB8::default());
+            return;
+        };
+        match self.style {
+            Style::Bar => {
+                let len = region.len();
+                let lit = (position as usize * len).div_ceil(u8::MAX as usize);
+                for (i, color) in region.iter_mut().enumerate() {
+                    *color = if i < lit {
+                        self.palette.color_at((i * 255 / len) as u8)
+                    } else {
+                        RGB8::default()
+                    };
+                }
+            }
+            Style::Fill => region.fill(self.palette.color_at(position)),
+        }
+    }
+}
+
+/// Up to `CHANNELS` gauges sharing a strip.
+pub struct Dashboard<const CHANNELS: usize> {
+    gauges: [Option<Gauge>; CHANNELS],
+}
+
+impl<const CHANNELS: usize> Default for Dashboard<CHANNELS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CHANNELS: usize> Dashboard<CHANNELS> {
+    /// Create an empty dashboard.
+    pub const fn new() -> Self {
+        Self {
+            gauges: [const { None }; CHANNELS],
+        }
+    }
+
+    /// Add a gauge.
+    pub fn register(&mut self, gauge: Gauge) -> Result<(), DashboardError> {
+        if self.find(gauge.name).is_some() {
+            return Err(DashboardError::Duplicate);
+        }
+        let slot = self
+            .gauges
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(DashboardError::Full)?;
+        *slot = Some(gauge);
+        Ok(())
+    }
+
+    /// Remove a gauge, its region is no longer rendered.
+    pub fn remove(&mut self, name: &str) -> Result<Gauge, DashboardError> {
+        self.gauges
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(|gauge| gauge.name == name))
+            .and_then(Option::take)
+            .ok_or(DashboardError::NotFound)
+    }
+
+    /// Feed a reading to the gauge of a sensor channel.
+    pub fn update(&mut self, name: &str, value: f32) -> Result<(), DashboardError> {
+        let gauge = self.find(name).ok_or(DashboardError::NotFound)?;
+        gauge.position = Some(gauge.scale.update(value));
+        Ok(())
+    }
+
+    /// Render every gauge into a frame, LEDs outside
+    /// the regions of the gauges are left unchanged.
+    pub fn render(&self, out: &mut [RGB8]) {
+        for gauge in self.gauges.iter().flatten() {
+            gauge.render(out);
+        }
+    }
+
+    /// Registered gauges.
+    pub fn gauges(&self) -> impl Iterator<Item = &Gauge> {
+        self.gauges.iter().flatten()
+    }
+
+    fn find(&mut self, name: &str) -> Option<&mut Gauge> {
+        self.gauges
+            .iter_mut()
+            .flatten()
+            .find(|gauge| gauge.name == name)
+    }
+}
@@ -0,0 +1,181 @@
+//! Reset diagnostics for devices installed in the field.
+//!
+//! The reset reason is read from `RESETREAS` and boot and crash
+//! counters are kept in a `.uninit` RAM section that survives soft
+//! resets, watchdog resets and lockups. RAM is not retained through
+//! a power-on or brown-out reset so the counters start again from zero.
+//! [boot_info] needs the `nrf52*` or `nrf5340-app-*` chip feature of
+//! this crate.
+//!
+//! [DeadlineStats] count frames that missed their deadline, see
+//! [FramePacer](crate::pacing::FramePacer).
+
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+use core::mem::MaybeUninit;
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+use core::ptr;
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+use embassy_nrf::pac;
+use smart_leds::RGB8;
+
+/// Marker for valid counters in retained RAM.
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+const MAGIC: u32 = 0x5752_4553;
+
+/// Cause of the last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power-on or brown-out reset, no `RESETREAS` bits set.
+    PowerOn,
+    /// Reset pin.
+    Pin,
+    /// Watchdog timeout.
+    Watchdog,
+    /// Software reset request.
+    SoftReset,
+    /// CPU lockup.
+    Lockup,
+    /// Wake up from system off.
+    SystemOff,
+    /// Debug interface.
+    Debug,
+}
+
+impl ResetReason {
+    /// Whether the reset was caused by the firmware misbehaving.
+    pub fn is_crash(&self) -> bool {
+        matches!(self, ResetReason::Watchdog | ResetReason::Lockup)
+    }
+}
+
+/// Reset reason and counters read at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootInfo {
+    /// Cause of the last reset.
+    pub reason: ResetReason,
+    /// Boots since the counters were last lost.
+    pub boots: u32,
+    /// Crashes since the counters were last lost.
+    pub crashes: u32,
+}
+
+impl BootInfo {
+    /// Color to flash at boot to indicate an abnormal reset.
+    pub fn color(&self) -> Option<RGB8> {
+        match self.reason {
+            ResetReason::Watchdog => Some(RGB8::new(255, 96, 0)),
+            ResetReason::Lockup => Some(RGB8::new(255, 0, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Frame deadline counters of a [FramePacer](crate::pacing::FramePacer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DeadlineStats {
+    /// Frames paced.
+    pub frames: u32,
+    /// Frames whose render, encode and transmit overran the frame period.
+    pub missed: u32,
+    /// Frame slots skipped to catch up.
+    pub skipped: u32,
+    /// Times the frame rate was reduced.
+    pub slowed: u32,
+    /// Frames rendered at half resolution.
+    pub half_resolution: u32,
+}
+
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+#[repr(C)]
+struct Retained {
+    magic: u32,
+    boots: u32,
+    crashes: u32,
+}
+
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+#[unsafe(link_section = ".uninit.diagnostics")]
+static mut RETAINED: MaybeUninit<Retained> = MaybeUninit::uninit();
+
+/// Read and clear the reset reason and update the retained counters.
+///
+/// Call this once, early at boot, before anything else reads `RESETREAS`.
+#[cfg(any(feature = "_nrf52", feature = "_nrf5340-app"))]
+pub fn boot_info() -> BootInfo {
+    let reason = reset_reason();
+
+    // SAFETY: only accessed here, once at boot, through volatile
+    // reads and writes as the memory may hold anything after power on.
+    unsafe {
+        let retained = (&raw mut RETAINED).cast::<Retained>();
+        let magic = ptr::read_volatile(&raw const (*retained).magic);
+        if reason == ResetReason::PowerOn || magic != MAGIC {
+            ptr::write_volatile(&raw mut (*retained).magic, MAGIC);
+            ptr::write_volatile(&raw mut (*retained).boots, 0);
+            ptr::write_volatile(&raw mut (*retained).crashes, 0);
+        }
+
+        let boots = ptr::read_volatile(&raw const (*retained).boots).wrapping_add(1);
+        let mut crashes = ptr::read_volatile(&raw const (*retained).crashes);
+        if reason.is_crash() {
+            crashes = crashes.wrapping_add(1);
+        }
+        ptr::write_volatile(&raw mut (*retained).boots, boots);
+        ptr::write_volatile(&raw mut (*retained).crashes, crashes);
+
+        BootInfo {
+            reason,
+            boots,
+            crashes,
+        }
+    }
+}
+
+/// Read and clear `RESETREAS` in the POWER peripheral.
+#[cfg(feature = "_nrf52")]
+fn reset_reason() -> ResetReason {
+    let resetreas = pac::POWER.resetreas().read();
+    // Bits are cleared by writing ones
+    pac::POWER.resetreas().write_value(resetreas);
+
+    if resetreas.dog() {
+        ResetReason::Watchdog
+    } else if resetreas.lockup() {
+        ResetReason::Lockup
+    } else if resetreas.sreq() {
+        ResetReason::SoftReset
+    } else if resetreas.resetpin() {
+        ResetReason::Pin
+    } else if resetreas.off() {
+        ResetReason::SystemOff
+    } else if resetreas.dif() {
+        ResetReason::Debug
+    } else {
+        ResetReason::PowerOn
+    }
+}
+
+/// Read and clear `RESETREAS` in the RESET peripheral, which has
+/// one bit per watchdog.
+#[cfg(feature = "_nrf5340-app")]
+fn reset_reason() -> ResetReason {
+    let resetreas = pac::RESET.resetreas().read();
+    // Bits are cleared by writing ones
+    pac::RESET.resetreas().write_value(resetreas);
+
+    if resetreas.dog0() || resetreas.dog1() {
+        ResetReason::Watchdog
+    } else if resetreas.lockup() {
+        ResetReason::Lockup
+    } else if resetreas.sreq() {
+        ResetReason::SoftReset
+    } else if resetreas.resetpin() {
+        ResetReason::Pin
+    } else if resetreas.off() {
+        ResetReason::SystemOff
+    } else if resetreas.dif() {
+        ResetReason::Debug
+    } else {
+        ResetReason::PowerOn
+    }
+}
@@ -0,0 +1,62 @@
+//! Placing sample buffers where EasyDMA can read them.
+//!
+//! EasyDMA only reads data RAM, a sample buffer in flash or in a RAM
+//! region the PWM can't reach fails every write. On nRF52 every static
+//! or stack buffer is fine. On nRF53 and nRF91 the linker script may
+//! map RAM blocks the peripherals can't access, or that belong to the
+//! other security domain, and the buffer has to go in a section placed
+//! in a DMA capable block. [dma_buffer!](crate::dma_buffer) puts the
+//! samples in such a section, and the drivers check borrowed buffers
+//! when they are created instead of failing at the first write.
+
+use crate::error::Error;
+use crate::pwm;
+use core::ops::Range;
+
+/// Addresses EasyDMA can read, the data RAM of every nRF chip.
+///
+/// Chips with TrustZone may still restrict parts of this range
+/// to the secure domain, which can't be checked from here.
+pub const DMA_RAM: Range<usize> = 0x2000_0000..0x3000_0000;
+
+/// Whether `buf` lies entirely in [DMA_RAM].
+pub fn in_dma_ram<T>(buf: &[T]) -> bool {
+    let range = buf.as_ptr_range();
+    DMA_RAM.start <= range.start as usize && range.end as usize <= DMA_RAM.end
+}
+
+/// Fail with [pwm::Error::BufferNotInRAM] when `buf` is outside [DMA_RAM].
+pub(crate) fn check<T>(buf: &[T]) -> Result<(), Error> {
+    if in_dma_ram(buf) {
+        Ok(())
+    } else {
+        Err(Error::Pwm(pwm::Error::BufferNotInRAM))
+    }
+}
+
+/// Create a static sample buffer for a number of LEDs, evaluating to
+/// `&'static mut Samples<num_leds>`.
+///
+/// An optional linker section places the buffer in a DMA capable RAM
+/// block defined in `memory.x`. The section must be initialized at
+/// startup like `.bss`, not `NOLOAD`. Each expansion owns its buffer
+/// so it panics if evaluated twice.
+///
+/// ```ignore
+/// let buf = dma_buffer!(60, ".dma_ram");
+/// let mut ws = Ws2812::new(p.PWM0, Irqs, p.P0_13, buf)?;
+/// ```
+#[macro_export]
+macro_rules! dma_buffer {
+    ($num_leds:expr $(,)?) => {{
+        static BUFFER: $crate::static_cell::StaticCell<$crate::Samples<$num_leds>> =
+            $crate::static_cell::StaticCell::new();
+        BUFFER.init([[0; 24]; $num_leds])
+    }};
+    ($num_leds:expr, $section:literal $(,)?) => {{
+        #[unsafe(link_section = $section)]
+        static BUFFER: $crate::static_cell::StaticCell<$crate::Samples<$num_leds>> =
+            $crate::static_cell::StaticCell::new();
+        BUFFER.init([[0; 24]; $num_leds])
+    }};
+}
@@ -0,0 +1,658 @@
+//! Animated effects.
+//!
+//! An [Effect] renders a frame for a point in time into a slice of
+//! colors. Effects can render into an offscreen [Texture] which other
+//! effects sample, so a [Mirror] or [Kaleidoscope] can reuse any
+//! effect as its source instead of generating colors itself.
+//!
+//! The common strip animations are built in, [Rainbow], [ColorWipe],
+//! [TheaterChase], [Breathe], [Sparkle], [Meteor] and [Fire], and an
+//! [EffectRunner] renders and writes an effect at a fixed frame rate.
+//!
+//! ```ignore
+//! let mut runner = EffectRunner::<_, NUM_LEDS>::new(SystemClock, Rainbow::new(32, Duration::from_secs(2)), 60);
+//! let error = runner.run(&mut ws).await;
+//! ```
+
+use crate::clock::{Clock, Instant, SystemClock};
+use crate::correction::Correction;
+use crate::pacing::{DeadlinePolicy, FramePacer};
+use crate::scale;
+use core::f32::consts::TAU;
+use embassy_time::Duration;
+use libm::cosf;
+use smart_leds::hsv::{Hsv, hsv2rgb};
+use smart_leds::{RGB8, SmartLedsWriteAsync};
+
+/// Animation rendering frames on demand.
+pub trait Effect {
+    /// Render the frame at time `t` since the effect started into `out`.
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]);
+}
+
+/// Offscreen buffer of `LEN` colors rendered by an effect.
+pub struct Texture<E, const LEN: usize> {
+    effect: E,
+    pixels: [RGB8; LEN],
+}
+
+impl<E: Effect, const LEN: usize> Texture<E, LEN> {
+    /// Texture rendered by `effect`.
+    pub const fn new(effect: E) -> Self {
+        const { assert!(LEN > 0, "texture must not be empty") };
+        Self {
+            effect,
+            pixels: [RGB8::new(0, 0, 0); LEN],
+        }
+    }
+
+    /// Render the frame at time `t` into the texture.
+    pub fn render(&mut self, t: Duration) {
+        self.effect.frame(t, &mut self.pixels);
+    }
+
+    /// Colors of the last rendered frame.
+    pub fn pixels(&self) -> &[RGB8; LEN] {
+        &self.pixels
+    }
+
+    /// Color at a position from `0` (start) to `255` (end)
+    /// regardless of the size of the texture.
+    pub fn sample(&self, position: u8) -> RGB8 {
+        self.pixels[position as usize * LEN / 256]
+    }
+}
+
+/// Stretches the source across the first half of the
+/// output and mirrors it onto the second half.
+pub struct Mirror<E, const LEN: usize> {
+    source: Texture<E, LEN>,
+}
+
+impl<E: Effect, const LEN: usize> Mirror<E, LEN> {
+    /// Mirror an effect rendered at `LEN` colors.
+    pub const fn new(source: E) -> Self {
+        Self {
+            source: Texture::new(source),
+        }
+    }
+}
+
+impl<E: Effect, const LEN: usize> Effect for Mirror<E, LEN> {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        self.source.render(t);
+        let len = out.len();
+        let half = len.div_ceil(2);
+        for i in 0..half {
+            let color = self.source.sample((i * 256 / half) as u8);
+            out[i] = color;
+            out[len - 1 - i] = color;
+        }
+    }
+}
+
+/// Repeats the source across `segments` segments,
+/// reversing every other segment.
+pub struct Kaleidoscope<E, const LEN: usize> {
+    source: Texture<E, LEN>,
+    segments: usize,
+}
+
+impl<E: Effect, const LEN: usize> Kaleidoscope<E, LEN> {
+    /// Kaleidoscope of an effect rendered at `LEN` colors.
+    pub const fn new(source: E, segments: usize) -> Self {
+        Self {
+            source: Texture::new(source),
+            segments: if segments == 0 { 1 } else { segments },
+        }
+    }
+}
+
+impl<E: Effect, const LEN: usize> Effect for Kaleidoscope<E, LEN> {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        self.source.render(t);
+        let segment_len = out.len().div_ceil(self.segments).max(1);
+        for (i, color) in out.iter_mut().enumerate() {
+            let offset = i % segment_len;
+            let offset = if (i / segment_len) % 2 == 1 {
+                segment_len - 1 - offset
+            } else {
+                offset
+            };
+            *color = self.source.sample((offset * 256 / segment_len) as u8);
+        }
+    }
+}
+
+/// Shortest time between the start of two [Strobe] flashes.
+///
+/// Photosensitivity guidelines (WCAG 2.3.1) allow at most three
+/// flashes in any one second period.
+pub const MIN_FLASH_PERIOD: Duration = Duration::from_millis(334);
+
+/// Longest part of a [Strobe] period the light is on, in percent.
+pub const MAX_FLASH_DUTY: u8 = 50;
+
+/// Flashes a color, optionally in bursts separated by a pause
+/// for alert patterns.
+///
+/// The flash period and duty cycle are clamped to [MIN_FLASH_PERIOD]
+/// and [MAX_FLASH_DUTY] when the strobe is created, so parameters
+/// coming from a remote command can't exceed the safety limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strobe {
+    color: RGB8,
+    period: u64,
+    on: u64,
+    flashes: u64,
+    pause: u64,
+}
+
+impl Strobe {
+    /// Flash `color` continuously every `period`, staying
+    /// on for `duty` percent of the period.
+    pub const fn new(color: RGB8, period: Duration, duty: u8) -> Self {
+        let min = MIN_FLASH_PERIOD.as_ticks();
+        let period = if period.as_ticks() < min {
+            min
+        } else {
+            period.as_ticks()
+        };
+        let duty = if duty > MAX_FLASH_DUTY {
+            MAX_FLASH_DUTY
+        } else {
+            duty
+        };
+        Self {
+            color,
+            period,
+            on: period * duty as u64 / 100,
+            flashes: 0,
+            pause: 0,
+        }
+    }
+
+    /// Flash in bursts of `flashes` followed by `pause` off.
+    pub const fn with_bursts(mut self, flashes: u8, pause: Duration) -> Self {
+        self.flashes = flashes as u64;
+        self.pause = pause.as_ticks();
+        self
+    }
+}
+
+impl Effect for Strobe {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let burst = self.flashes * self.period;
+        let t = if self.flashes == 0 {
+            t.as_ticks()
+        } else {
+            t.as_ticks() % (burst + self.pause)
+        };
+        let lit = (self.flashes == 0 || t < burst) && t % self.period < self.on;
+        out.fill(if lit { self.color } else { RGB8::default() });
+    }
+}
+
+/// Level of each channel of a stereo audio frame, `255` is full scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StereoLevel {
+    /// Left channel.
+    pub left: u8,
+    /// Right channel.
+    pub right: u8,
+}
+
+/// Peak level held for a while before falling.
+#[derive(Debug, Clone, Copy, Default)]
+struct Peak {
+    level: u8,
+    at: Duration,
+}
+
+impl Peak {
+    fn update(&mut self, level: u8, t: Duration, hold: Duration) -> u8 {
+        let held = t.checked_sub(self.at).unwrap_or_default();
+        let fallen = if held > hold {
+            // Fall at full scale per second once the hold time is over
+            let fall = (held - hold).as_millis() * 255 / 1000;
+            self.level.saturating_sub(fall.min(255) as u8)
+        } else {
+            self.level
+        };
+        if level >= fallen {
+            *self = Peak { level, at: t };
+        }
+        fallen.max(level)
+    }
+}
+
+/// Stereo VU meter with peak hold.
+///
+/// As an [Effect] the left channel grows from the middle of the strip
+/// towards the start and the right channel towards the end, use
+/// [StereoVu::render] to drive one strip per channel instead.
+pub struct StereoVu {
+    level: StereoLevel,
+    peaks: [Peak; 2],
+    hold: Duration,
+    low: RGB8,
+    high: RGB8,
+    peak: RGB8,
+}
+
+impl StereoVu {
+    /// Meter fading from `low` to `high` along each channel
+    /// with the peak shown in `peak` for `hold`.
+    pub const fn new(low: RGB8, high: RGB8, peak: RGB8, hold: Duration) -> Self {
+        Self {
+            level: StereoLevel { left: 0, right: 0 },
+            peaks: [Peak {
+                level: 0,
+                at: Duration::from_ticks(0),
+            }; 2],
+            hold,
+            low,
+            high,
+            peak,
+        }
+    }
+
+    /// Set the level of the latest audio frame.
+    pub fn set_level(&mut self, level: StereoLevel) {
+        self.level = level;
+    }
+
+    /// Render each channel onto its own strip, starting at index 0.
+    pub fn render(&mut self, t: Duration, left: &mut [RGB8], right: &mut [RGB8]) {
+        let levels = [self.level.left, self.level.right];
+        for ((out, peak), level) in [left, right].into_iter().zip(&mut self.peaks).zip(levels) {
+            let peak = peak.update(level, t, self.hold);
+            let len = out.len();
+            let lit = level as usize * len / 255;
+            for (i, color) in out.iter_mut().enumerate() {
+                *color = if i < lit {
+                    let position = (i * 255 / len.max(2).saturating_sub(1)).min(255) as u8;
+                    blend(self.low, self.high, position)
+                } else {
+                    RGB8::default()
+                };
+            }
+            if peak > 0
+                && let Some(color) = out.get_mut((peak as usize * len / 255).saturating_sub(1))
+            {
+                *color = self.peak;
+            }
+        }
+    }
+}
+
+impl Effect for StereoVu {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let (left, right) = out.split_at_mut(out.len() / 2);
+        self.render(t, left, right);
+        left.reverse();
+    }
+}
+
+/// Hues cycling along the strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rainbow {
+    spread: u8,
+    cycle: Duration,
+    value: u8,
+}
+
+impl Rainbow {
+    /// Rainbow advancing the hue by `spread` from one LED to the
+    /// next and going through every hue once per `cycle`.
+    pub const fn new(spread: u8, cycle: Duration) -> Self {
+        Self {
+            spread,
+            cycle,
+            value: 255,
+        }
+    }
+
+    /// Value of the colors, from `0` (off) to `255` (full).
+    pub const fn value(mut self, value: u8) -> Self {
+        self.value = value;
+        self
+    }
+}
+
+impl Effect for Rainbow {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let offset = (t.as_ticks() * 256 / self.cycle.as_ticks().max(1)) as u8;
+        for (i, color) in out.iter_mut().enumerate() {
+            *color = hsv2rgb(Hsv {
+                hue: offset.wrapping_add((i as u8).wrapping_mul(self.spread)),
+                sat: 255,
+                val: self.value,
+            });
+        }
+    }
+}
+
+/// Lights the LEDs one after the other, then
+/// turns them off again in the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorWipe {
+    color: RGB8,
+    step: Duration,
+}
+
+impl ColorWipe {
+    /// Wipe `color` along the strip, one LED every `step`.
+    pub const fn new(color: RGB8, step: Duration) -> Self {
+        Self { color, step }
+    }
+}
+
+impl Effect for ColorWipe {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let len = out.len();
+        let step = (t.as_ticks() / self.step.as_ticks().max(1)) as usize % (2 * len).max(1);
+        for (i, color) in out.iter_mut().enumerate() {
+            let lit = if step < len {
+                i <= step
+            } else {
+                i > step - len
+            };
+            *color = if lit { self.color } else { RGB8::default() };
+        }
+    }
+}
+
+/// Every third LED lit, marching along the strip
+/// like the lights around a theater marquee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TheaterChase {
+    color: RGB8,
+    step: Duration,
+}
+
+impl TheaterChase {
+    /// Chase in `color` moving one LED every `step`.
+    pub const fn new(color: RGB8, step: Duration) -> Self {
+        Self { color, step }
+    }
+}
+
+impl Effect for TheaterChase {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let offset = (t.as_ticks() / self.step.as_ticks().max(1)) as usize % 3;
+        for (i, color) in out.iter_mut().enumerate() {
+            *color = if i % 3 == offset {
+                self.color
+            } else {
+                RGB8::default()
+            };
+        }
+    }
+}
+
+/// Fades a color in and out smoothly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breathe {
+    color: RGB8,
+    period: Duration,
+}
+
+impl Breathe {
+    /// Breathe `color`, going from off to full and back once per `period`.
+    pub const fn new(color: RGB8, period: Duration) -> Self {
+        Self { color, period }
+    }
+}
+
+impl Effect for Breathe {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        let period = self.period.as_ticks().max(1);
+        let phase = (t.as_ticks() % period) as f32 / period as f32;
+        let level = ((1.0 - cosf(phase * TAU)) * 127.5) as u8;
+        out.fill(Correction::new(level, level, level).apply(self.color));
+    }
+}
+
+/// Random LEDs flashing on a background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sparkle {
+    color: RGB8,
+    background: RGB8,
+    density: u8,
+    rng: Rng,
+}
+
+impl Sparkle {
+    /// Sparkle lighting each LED in `color` with a chance of `density`
+    /// in 256 every frame, `seed` picks the random sequence.
+    pub const fn new(color: RGB8, density: u8, seed: u32) -> Self {
+        Self {
+            color,
+            background: RGB8 { r: 0, g: 0, b: 0 },
+            density,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Color of the LEDs not sparkling.
+    pub const fn background(mut self, background: RGB8) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+impl Effect for Sparkle {
+    fn frame(&mut self, _t: Duration, out: &mut [RGB8]) {
+        for color in out {
+            *color = if self.rng.next_u8() < self.density {
+                self.color
+            } else {
+                self.background
+            };
+        }
+    }
+}
+
+/// A bright head running along the strip with a fading trail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Meteor {
+    color: RGB8,
+    step: Duration,
+    decay: u8,
+}
+
+impl Meteor {
+    /// Meteor in `color` moving one LED every `step`, each LED of the
+    /// trail keeping `decay` in 256 of the brightness of the one before.
+    pub const fn new(color: RGB8, step: Duration, decay: u8) -> Self {
+        Self { color, step, decay }
+    }
+}
+
+impl Effect for Meteor {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        out.fill(RGB8::default());
+        let len = out.len();
+        // The trail ends where it fades out, or after a strip length
+        let mut trail = 0;
+        let mut level = u8::MAX;
+        while level > 0 && trail < len {
+            level = scale(level, self.decay);
+            trail += 1;
+        }
+        let head = (t.as_ticks() / self.step.as_ticks().max(1)) as usize % (len + trail).max(1);
+        let mut level = u8::MAX;
+        for offset in 0..=trail {
+            if let Some(color) = head.checked_sub(offset).and_then(|i| out.get_mut(i)) {
+                *color = Correction::new(level, level, level).apply(self.color);
+            }
+            level = scale(level, self.decay);
+        }
+    }
+}
+
+/// Flickering flames rising from the start of the strip, the
+/// Fire2012 simulation of FastLED on `LEN` cells of heat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fire<const LEN: usize> {
+    heat: [u8; LEN],
+    cooling: u8,
+    sparking: u8,
+    rng: Rng,
+}
+
+impl<const LEN: usize> Fire<LEN> {
+    /// Fire losing heat faster with a higher `cooling` (about 20 to 100)
+    /// and igniting more often with a higher `sparking` (about 50 to 200),
+    /// `seed` picks the random sequence.
+    pub const fn new(cooling: u8, sparking: u8, seed: u32) -> Self {
+        const { assert!(LEN > 0, "fire must not be empty") };
+        Self {
+            heat: [0; LEN],
+            cooling,
+            sparking,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Advance the simulation by one step.
+    fn step(&mut self) {
+        let cooling = (self.cooling as usize * 10 / LEN + 2).min(255) as u8;
+        for heat in &mut self.heat {
+            *heat = heat.saturating_sub(self.rng.below(cooling));
+        }
+        for k in (2..LEN).rev() {
+            self.heat[k] = ((self.heat[k - 1] as u16 + 2 * self.heat[k - 2] as u16) / 3) as u8;
+        }
+        if self.rng.next_u8() < self.sparking {
+            let cell = self.rng.below(7.min(LEN) as u8) as usize;
+            let spark = 160 + self.rng.below(96);
+            self.heat[cell] = self.heat[cell].saturating_add(spark);
+        }
+    }
+}
+
+impl<const LEN: usize> Effect for Fire<LEN> {
+    fn frame(&mut self, _t: Duration, out: &mut [RGB8]) {
+        self.step();
+        let len = out.len();
+        for (i, color) in out.iter_mut().enumerate() {
+            *color = heat_color(self.heat[i * LEN / len]);
+        }
+    }
+}
+
+/// Black body color of a heat from `0` (black)
+/// through red and yellow to `255` (white).
+fn heat_color(heat: u8) -> RGB8 {
+    let heat = scale(heat, 191);
+    let ramp = (heat & 0x3f) << 2;
+    match heat {
+        0x80.. => RGB8::new(255, 255, ramp),
+        0x40.. => RGB8::new(255, ramp, 0),
+        _ => RGB8::new(ramp, 0, 0),
+    }
+}
+
+/// Xorshift generator for effects that only need to look random.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rng(u32);
+
+impl Rng {
+    const fn new(seed: u32) -> Self {
+        // Xorshift is stuck at zero
+        Self(if seed == 0 { 0x9e37_79b9 } else { seed })
+    }
+
+    fn next_u8(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 24) as u8
+    }
+
+    /// Random number below `n`, `0` when `n` is `0`.
+    fn below(&mut self, n: u8) -> u8 {
+        ((self.next_u8() as u16 * n as u16) >> 8) as u8
+    }
+}
+
+/// Renders an effect and writes it at a fixed frame rate.
+///
+/// Frames are started by a [FramePacer] skipping the frames that
+/// can't be rendered in time, the effect is rendered for the time
+/// since the runner was created.
+pub struct EffectRunner<E, const LEDS: usize, C = SystemClock> {
+    effect: E,
+    clock: C,
+    pacer: FramePacer<C>,
+    start: Instant,
+    frame: [RGB8; LEDS],
+}
+
+impl<E: Effect, const LEDS: usize, C: Clock + Copy> EffectRunner<E, LEDS, C> {
+    /// Runner showing `effect` at `fps` frames per second.
+    pub fn new(clock: C, effect: E, fps: u16) -> Self {
+        let period = Duration::from_hz(fps.max(1) as u64);
+        Self {
+            effect,
+            clock,
+            pacer: FramePacer::new(clock, period, DeadlinePolicy::Skip),
+            start: clock.now(),
+            frame: [RGB8::default(); LEDS],
+        }
+    }
+
+    /// The effect, for example to change its parameters.
+    pub fn effect_mut(&mut self) -> &mut E {
+        &mut self.effect
+    }
+
+    /// Show `effect` from its start.
+    pub fn set_effect(&mut self, effect: E) {
+        self.effect = effect;
+        self.start = self.clock.now();
+    }
+
+    /// Time the effect has been running for, to continue
+    /// it in a [Crossfade](crate::transition::Crossfade).
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.start)
+    }
+
+    /// Pacer starting the frames, for its deadline counters.
+    pub fn pacer(&self) -> &FramePacer<C> {
+        &self.pacer
+    }
+
+    /// Render the frame for the current time.
+    pub fn render(&mut self) -> &[RGB8; LEDS] {
+        let t = self.elapsed();
+        self.effect.frame(t, &mut self.frame);
+        &self.frame
+    }
+
+    /// Render and write a frame every frame period until a write fails.
+    ///
+    /// The runner can be run again after handling the error.
+    pub async fn run<D>(&mut self, ws: &mut D) -> D::Error
+    where
+        D: SmartLedsWriteAsync<Color = RGB8>,
+    {
+        loop {
+            self.pacer.wait().await;
+            let frame = *self.render();
+            if let Err(e) = ws.write(frame).await {
+                return e;
+            }
+        }
+    }
+}
+
+/// Linear blend from `a` (`0`) to `b` (`255`).
+pub(crate) fn blend(a: RGB8, b: RGB8, amount: u8) -> RGB8 {
+    let mix =
+        |a: u8, b: u8| ((a as u16 * (255 - amount) as u16 + b as u16 * amount as u16) / 255) as u8;
+    RGB8::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
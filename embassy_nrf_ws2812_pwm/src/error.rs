@@ -0,0 +1,69 @@
+//! Errors returned by the driver.
+
+use crate::pwm;
+use core::fmt;
+#[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+use embassy_nrf::i2s;
+#[cfg(not(feature = "_nrf54l"))]
+use embassy_nrf::spim;
+
+/// Driver error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Error from the PWM peripheral.
+    Pwm(pwm::Error),
+    /// Error from the I2S peripheral of `ws2812_i2s`.
+    #[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+    I2s(i2s::Error),
+    /// Error from the SPIM peripheral of [ws2812_spi](crate::ws2812_spi)
+    /// or [apa102](crate::apa102).
+    #[cfg(not(feature = "_nrf54l"))]
+    Spim(spim::Error),
+    /// Configuration the PWM can't generate, for example
+    /// bit timings that don't fit in a PWM period.
+    InvalidConfig,
+    /// A write had more colors than the strip has LEDs, holds the
+    /// number of colors or the most the iterator could yield.
+    TooManyPixels(usize),
+    /// The driver is in use by another task, see
+    /// [SharedWs2812::try_write](crate::shared::SharedWs2812::try_write).
+    Busy,
+}
+
+impl From<pwm::Error> for Error {
+    fn from(value: pwm::Error) -> Self {
+        Error::Pwm(value)
+    }
+}
+
+#[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+impl From<i2s::Error> for Error {
+    fn from(value: i2s::Error) -> Self {
+        Error::I2s(value)
+    }
+}
+
+#[cfg(not(feature = "_nrf54l"))]
+impl From<spim::Error> for Error {
+    fn from(value: spim::Error) -> Self {
+        Error::Spim(value)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pwm(e) => write!(f, "PWM error: {:?}", e),
+            #[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+            Error::I2s(e) => write!(f, "I2S error: {:?}", e),
+            #[cfg(not(feature = "_nrf54l"))]
+            Error::Spim(e) => write!(f, "SPIM error: {:?}", e),
+            Error::InvalidConfig => write!(f, "invalid configuration"),
+            Error::TooManyPixels(count) => write!(f, "too many pixels: {}", count),
+            Error::Busy => write!(f, "driver busy"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
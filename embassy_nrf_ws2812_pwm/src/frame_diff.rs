@@ -0,0 +1,86 @@
+//! Compact frame differences for mirroring over slow links.
+//!
+//! A diff lists the runs of pixels that changed since the last frame,
+//! each run is a header followed by its colors as RGB bytes:
+//!
+//! | Offset | Size | Field                       |
+//! |--------|------|-----------------------------|
+//! | 0      | 2    | Index of the first LED (LE) |
+//! | 2      | 1    | Number of LEDs `n`          |
+//! | 3      | 3n   | Colors                      |
+//!
+//! An unchanged frame is an empty diff, so a companion device
+//! mirroring the strip over BLE only spends airtime on changes.
+
+use smart_leds::RGB8;
+
+const RUN_HEADER_LEN: usize = 3;
+
+/// Error creating or applying a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DiffError {
+    /// The diff doesn't fit in the output buffer, send the full frame.
+    Full,
+    /// A run is cut short.
+    Length,
+    /// A run is past the end of the frame.
+    Range,
+}
+
+/// Write the runs of pixels in `next` that differ from `last` to `out`
+/// then update `last` to `next`.
+///
+/// Frames must be the same length and have at most 65536 LEDs.
+/// Returns the length of the diff. On error `last` is unchanged.
+pub fn diff(last: &mut [RGB8], next: &[RGB8], out: &mut [u8]) -> Result<usize, DiffError> {
+    let len = last.len().min(next.len());
+    let mut written = 0;
+    let mut index = 0;
+    while index < len {
+        if last[index] == next[index] {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < len && index - start < u8::MAX as usize && last[index] != next[index] {
+            index += 1;
+        }
+
+        let run = &next[start..index];
+        let end = written + RUN_HEADER_LEN + run.len() * 3;
+        let bytes = out.get_mut(written..end).ok_or(DiffError::Full)?;
+        let (header, colors) = bytes.split_at_mut(RUN_HEADER_LEN);
+        header[..2].copy_from_slice(&(start as u16).to_le_bytes());
+        header[2] = run.len() as u8;
+        for (rgb, color) in colors.chunks_exact_mut(3).zip(run) {
+            rgb.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+        written = end;
+    }
+    last[..len].copy_from_slice(&next[..len]);
+    Ok(written)
+}
+
+/// Apply a diff made by [diff] to a frame.
+///
+/// Runs before an invalid run have already been applied on error.
+pub fn apply(frame: &mut [RGB8], mut diff: &[u8]) -> Result<(), DiffError> {
+    while let Some((header, rest)) = diff.split_first_chunk::<RUN_HEADER_LEN>() {
+        let start = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let count = header[2] as usize;
+        let (colors, rest) = rest.split_at_checked(count * 3).ok_or(DiffError::Length)?;
+        let pixels = frame
+            .get_mut(start..start + count)
+            .ok_or(DiffError::Range)?;
+        for (pixel, rgb) in pixels.iter_mut().zip(colors.chunks_exact(3)) {
+            *pixel = RGB8::new(rgb[0], rgb[1], rgb[2]);
+        }
+        diff = rest;
+    }
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(DiffError::Length)
+    }
+}
@@ -0,0 +1,17 @@
+//! Gamma correction applied while encoding.
+//!
+//! LED brightness is linear in the PWM duty cycle but perceived
+//! brightness is not, so without correction the low end of a fade
+//! jumps in visible steps and the top end barely changes.
+
+use libm::{powf, roundf};
+
+/// Lookup table mapping channel values through `gamma`.
+pub fn gamma_table(gamma: f32) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        let normalized = i as f32 / 255.0;
+        *value = roundf(powf(normalized, gamma) * 255.0) as u8;
+    }
+    table
+}
@@ -0,0 +1,125 @@
+//! Keep LED state across firmware update reboots.
+//!
+//! Before resetting into the bootloader to apply an update (for
+//! example after `FirmwareUpdater::mark_updated` with embassy-boot)
+//! the application calls [stash] with its LED state, such as the
+//! current scene. The new firmware calls [take] at boot and resumes
+//! from the state instead of its defaults, so a lamp comes back as it
+//! was after an update.
+//!
+//! The state lives in a `.uninit` RAM section like the counters of
+//! the [diagnostics](crate::diagnostics) module. The bootloader must
+//! not use that RAM, place the section at the end of RAM in the
+//! linker script of the application and outside the RAM region of the
+//! bootloader. A checksum guards against the state being clobbered
+//! anyway and it is lost on power-on or brown-out resets.
+
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// Maximum length of the stashed state.
+pub const HANDOFF_LEN: usize = 16;
+
+/// Marker for stashed state waiting to be resumed.
+const MAGIC: u32 = 0x5753_4854;
+
+#[repr(C)]
+struct Retained {
+    magic: u32,
+    len: u32,
+    checksum: u32,
+    state: [u8; HANDOFF_LEN],
+}
+
+#[unsafe(link_section = ".uninit.handoff")]
+static mut RETAINED: MaybeUninit<Retained> = MaybeUninit::uninit();
+
+/// Stash up to [HANDOFF_LEN] bytes of state to resume after the next reset.
+///
+/// Call it just before resetting, longer states are truncated.
+pub fn stash(state: &[u8]) {
+    let len = state.len().min(HANDOFF_LEN);
+    let mut bytes = [0; HANDOFF_LEN];
+    bytes[..len].copy_from_slice(&state[..len]);
+
+    // SAFETY: the retained state is only accessed through volatile
+    // reads and writes of plain data, here and in take.
+    unsafe {
+        let retained = (&raw mut RETAINED).cast::<Retained>();
+        ptr::write_volatile(&raw mut (*retained).state, bytes);
+        ptr::write_volatile(&raw mut (*retained).len, len as u32);
+        ptr::write_volatile(&raw mut (*retained).checksum, checksum(&bytes, len));
+        ptr::write_volatile(&raw mut (*retained).magic, MAGIC);
+    }
+}
+
+/// Take the state stashed before the last reset.
+///
+/// Returns the state and its length, or `None` if nothing was stashed
+/// or it didn't survive. Either way the stash is cleared so the state
+/// is only resumed once.
+pub fn take() -> Option<([u8; HANDOFF_LEN], usize)> {
+    // SAFETY: see stash, the memory may hold anything after power on
+    // which the magic and checksum reject.
+    unsafe {
+        let retained = (&raw mut RETAINED).cast::<Retained>();
+        let magic = ptr::read_volatile(&raw const (*retained).magic);
+        ptr::write_volatile(&raw mut (*retained).magic, 0);
+        if magic != MAGIC {
+            return None;
+        }
+        let len = ptr::read_volatile(&raw const (*retained).len) as usize;
+        let state = ptr::read_volatile(&raw const (*retained).state);
+        let valid = len <= HANDOFF_LEN
+            && ptr::read_volatile(&raw const (*retained).checksum) == checksum(&state, len);
+        valid.then_some((state, len))
+    }
+}
+
+/// FNV-1a hash of the length and state.
+fn checksum(state: &[u8; HANDOFF_LEN], len: usize) -> u32 {
+    (len as u32)
+        .to_le_bytes()
+        .iter()
+        .chain(state)
+        .fold(0x811c_9dc5, |hash, &byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_covers_length() {
+        let state = [0; HANDOFF_LEN];
+        assert_ne!(checksum(&state, 0), checksum(&state, 1));
+        let mut other = state;
+        other[HANDOFF_LEN - 1] = 1;
+        assert_ne!(checksum(&state, 1), checksum(&other, 1));
+    }
+
+    // One test as the tests share the retained state
+    #[test]
+    fn stash_is_taken_once() {
+        stash(&[1, 2, 3]);
+        let mut expected = [0; HANDOFF_LEN];
+        expected[..3].copy_from_slice(&[1, 2, 3]);
+        assert_eq!(take(), Some((expected, 3)));
+        assert_eq!(take(), None);
+
+        // Longer states are truncated
+        stash(&[7; HANDOFF_LEN + 4]);
+        assert_eq!(take(), Some(([7; HANDOFF_LEN], HANDOFF_LEN)));
+
+        // A clobbered state is rejected
+        stash(&[1, 2, 3]);
+        // SAFETY: the test owns the retained state
+        unsafe {
+            let retained = (&raw mut RETAINED).cast::<Retained>();
+            ptr::write_volatile(&raw mut (*retained).state[0], 9);
+        }
+        assert_eq!(take(), None);
+    }
+}
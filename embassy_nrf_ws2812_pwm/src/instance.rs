@@ -0,0 +1,187 @@
+//! PWM instances and the interrupt telling the drivers a sequence ended.
+//!
+//! embassy-nrf doesn't handle the interrupt of the sequence PWM nor
+//! expose the state of its events, so the drivers read the events
+//! through the registers of the [Instance] and [InterruptHandler]
+//! wakes them.
+
+use crate::pwm;
+#[cfg(feature = "_nrf54l")]
+use crate::pwm::peripherals;
+use core::marker::PhantomData;
+#[cfg(feature = "async")]
+use core::{
+    future::poll_fn,
+    task::{Context, Poll},
+};
+use embassy_nrf::interrupt::typelevel::{Handler, Interrupt};
+use embassy_nrf::pac;
+#[cfg(not(feature = "_nrf54l"))]
+use embassy_nrf::peripherals;
+#[cfg(feature = "async")]
+use embassy_sync::waitqueue::AtomicWaker;
+
+mod sealed {
+    use embassy_nrf::pac;
+    #[cfg(feature = "async")]
+    use embassy_sync::waitqueue::AtomicWaker;
+
+    pub trait Sealed {
+        fn regs() -> pac::pwm::Pwm;
+        #[cfg(feature = "async")]
+        fn waker() -> &'static AtomicWaker;
+    }
+}
+
+/// PWM instance the drivers can wait on.
+pub trait Instance: pwm::Instance + sealed::Sealed {}
+
+macro_rules! impl_instance {
+    ($type:ident) => {
+        impl sealed::Sealed for peripherals::$type {
+            fn regs() -> pac::pwm::Pwm {
+                pac::$type
+            }
+            #[cfg(feature = "async")]
+            fn waker() -> &'static AtomicWaker {
+                static WAKER: AtomicWaker = AtomicWaker::new();
+                &WAKER
+            }
+        }
+        impl Instance for peripherals::$type {}
+    };
+}
+
+#[cfg(not(feature = "_nrf54l"))]
+impl_instance!(PWM0);
+#[cfg(any(
+    feature = "nrf52832",
+    feature = "nrf52833",
+    feature = "nrf52840",
+    feature = "_nrf5340-app",
+    feature = "_nrf91"
+))]
+impl_instance!(PWM1);
+#[cfg(any(
+    feature = "nrf52832",
+    feature = "nrf52833",
+    feature = "nrf52840",
+    feature = "_nrf5340-app",
+    feature = "_nrf91"
+))]
+impl_instance!(PWM2);
+#[cfg(any(
+    feature = "nrf52833",
+    feature = "nrf52840",
+    feature = "_nrf5340-app",
+    feature = "_nrf91"
+))]
+impl_instance!(PWM3);
+#[cfg(feature = "_nrf54l")]
+impl_instance!(PWM20);
+#[cfg(feature = "_nrf54l")]
+impl_instance!(PWM21);
+#[cfg(feature = "_nrf54l")]
+impl_instance!(PWM22);
+
+/// Interrupt handler of a PWM instance used by a driver.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The events stay set for the drivers to read, disabling
+        // their interrupts keeps the handler from firing again
+        let regs = <T as sealed::Sealed>::regs();
+        let loops_done = regs.events_loopsdone().read() != 0;
+        let seq0_end = regs.events_seqend(0).read() != 0;
+        let seq1_end = regs.events_seqend(1).read() != 0;
+        regs.intenclr().write(|w| {
+            w.set_loopsdone(loops_done);
+            w.set_seqend0(seq0_end);
+            w.set_seqend1(seq1_end);
+        });
+        #[cfg(feature = "async")]
+        T::waker().wake();
+    }
+}
+
+/// PWM event a driver waits on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// The last loop ended, after the end delay of its last sequence.
+    LoopsDone,
+    /// EasyDMA loaded the last sample of sequence `n`, before its
+    /// end delay.
+    SeqEnd(usize),
+}
+
+/// Events of the PWM instance of a driver.
+#[derive(Clone, Copy)]
+pub(crate) struct Events {
+    regs: pac::pwm::Pwm,
+    #[cfg(feature = "async")]
+    waker: &'static AtomicWaker,
+}
+
+impl Events {
+    /// Events of `T`, enabling its interrupt.
+    pub(crate) fn new<T: Instance>() -> Self {
+        T::Interrupt::unpend();
+        // SAFETY: the handler only reads events and wakes the driver
+        unsafe { T::Interrupt::enable() };
+        Self {
+            regs: <T as sealed::Sealed>::regs(),
+            #[cfg(feature = "async")]
+            waker: T::waker(),
+        }
+    }
+
+    fn reg(&self, event: Event) -> pac::common::Reg<u32, pac::common::RW> {
+        match event {
+            Event::LoopsDone => self.regs.events_loopsdone(),
+            Event::SeqEnd(n) => self.regs.events_seqend(n),
+        }
+    }
+
+    /// Clear `event` before starting a sequence.
+    pub(crate) fn clear(&self, event: Event) {
+        self.reg(event).write_value(0);
+    }
+
+    /// Whether `event` happened since it was cleared.
+    pub(crate) fn is_triggered(&self, event: Event) -> bool {
+        self.reg(event).read() != 0
+    }
+
+    /// Whether `event` happened, otherwise wake `cx` from
+    /// the PWM interrupt once it does.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll(&self, event: Event, cx: &mut Context<'_>) -> Poll<()> {
+        self.waker.register(cx.waker());
+        if self.is_triggered(event) {
+            return Poll::Ready(());
+        }
+        // Enabling the interrupt of an event already set fires
+        // it right away, so an event raised since the check
+        // isn't missed
+        self.regs.intenset().write(|w| match event {
+            Event::LoopsDone => w.set_loopsdone(true),
+            Event::SeqEnd(0) => w.set_seqend0(true),
+            Event::SeqEnd(_) => w.set_seqend1(true),
+        });
+        Poll::Pending
+    }
+
+    /// Wait for `event`, sleeping until the PWM interrupt.
+    #[cfg(feature = "async")]
+    pub(crate) async fn wait(&self, event: Event) {
+        poll_fn(|cx| self.poll(event, cx)).await
+    }
+
+    /// Blocking version of [Events::wait].
+    pub(crate) fn wait_blocking(&self, event: Event) {
+        while !self.is_triggered(event) {}
+    }
+}
@@ -0,0 +1,1427 @@
+//! Async driver for WS2812 (aka Neopixel) LEDs using the
+//! sequence PWM peripheral on nRF chips.
+//!
+//! Disabling the default `async` feature drops the embassy-time and
+//! embassy-sync dependencies, leaving the blocking [SmartLedsWrite]
+//! implementation for use with RTIC or without an executor. The
+//! `bitbang` feature adds the `bitbang` module, a software driver
+//! for any pin. The `text` feature adds the `text` module scrolling
+//! text across a matrix in a small built in font. The `executor`
+//! feature adds the `priority` module with interrupt executors for
+//! encoding and transmitting at different priorities. The `defmt` feature
+//! implements `defmt::Format` for the errors and configuration types
+//! so they can be logged directly.
+//!
+//! The chip is selected with a feature of this crate, for example
+//! `nrf52840`, `nrf5340-app-s`, `nrf9160-s` or `nrf54l15-app-s`, which
+//! enables the same feature of embassy-nrf along with the chip specific
+//! modules such as `brownout`. Runtime features of embassy-nrf like `rt`
+//! and the time driver are left to the application. On the nRF54L series
+//! the drivers use PWM20 to PWM22 through [pwm], the SPIM and TIMER
+//! backends aren't available.
+//!
+//! The PWM drivers sleep until the PWM interrupt reports the end of a
+//! frame, bind [InterruptHandler] to the interrupt of the instance:
+//!
+//! ```ignore
+//! bind_interrupts!(struct Irqs {
+//!     PWM0 => embassy_nrf_ws2812_pwm::InterruptHandler<peripherals::PWM0>;
+//! });
+//! let mut ws = ws2812!(p.PWM0, Irqs, p.P0_13, 8)?;
+//! ```
+#![cfg_attr(not(test), no_std)]
+// The PWM configuration of the nRF54L is local to the crate, so it
+// isn't non_exhaustive to the drivers setting it field by field.
+#![cfg_attr(feature = "_nrf54l", allow(clippy::field_reassign_with_default))]
+
+pub mod ambilight;
+// embassy-nrf has no SPIM driver for the nRF54L series.
+#[cfg(not(feature = "_nrf54l"))]
+pub mod apa102;
+#[cfg(feature = "async")]
+pub mod assets;
+#[cfg(feature = "bitbang")]
+pub mod bitbang;
+#[cfg(feature = "_nrf52")]
+pub mod brownout;
+pub mod builder;
+pub mod chip_map;
+pub mod clock;
+pub mod color_order;
+pub mod correction;
+pub mod dashboard;
+pub mod diagnostics;
+pub mod dma;
+#[cfg(feature = "async")]
+pub mod effects;
+pub mod error;
+pub mod frame_diff;
+pub mod gamma;
+pub mod handoff;
+mod instance;
+#[cfg(feature = "async")]
+pub mod mailbox;
+pub mod matrix;
+pub mod modulation;
+#[cfg(feature = "async")]
+pub mod pacing;
+pub mod palette;
+pub mod panic_led;
+pub mod parallel;
+pub mod parts;
+pub mod power;
+#[cfg(feature = "executor")]
+pub mod priority;
+pub mod pwm;
+pub mod quad;
+#[cfg(feature = "async")]
+pub mod queue;
+#[cfg(feature = "async")]
+pub mod recording;
+pub mod rgbw;
+#[cfg(feature = "async")]
+pub mod runner;
+pub mod segments;
+#[cfg(feature = "async")]
+pub mod shared;
+pub mod stream;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod timing;
+pub mod tm1814;
+#[cfg(feature = "async")]
+pub mod transition;
+pub mod ucs8903;
+pub mod white;
+// Only these chips have an I2S peripheral under embassy-nrf.
+#[cfg(all(
+    feature = "async",
+    any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840")
+))]
+pub mod ws2812_i2s;
+// embassy-nrf has no SPIM, TIMER, GPIOTE or PPI driver for the nRF54L series.
+#[cfg(not(feature = "_nrf54l"))]
+pub mod ws2812_spi;
+#[cfg(not(feature = "_nrf54l"))]
+pub mod ws2812_timer;
+
+use builder::Ws2812Builder;
+use chip_map::{Chip, ChipMap};
+use color_order::ColorOrder;
+use core::ops::{Deref, DerefMut, Range};
+use correction::Correction;
+use embassy_nrf::{
+    Peri,
+    gpio::{AnyPin, Level, Pin},
+    interrupt::typelevel::Binding,
+    pac,
+};
+#[cfg(feature = "async")]
+use embassy_time::{Duration, Instant, Timer};
+use error::Error;
+use gamma::gamma_table;
+use instance::{Event, Events};
+pub use instance::{Instance, InterruptHandler};
+use power::{LoadMeter, PowerModel};
+use pwm::{
+    Config, Prescaler, Sequence, SequenceConfig, SequenceLoad, SequenceMode, SequencePwm,
+    Sequencer, SingleSequencer, StartSequence,
+};
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, RGBW, SmartLedsWrite};
+use timing::{POLARITY, Timing};
+
+/// Longest sequence the PWM can send, in samples. A [Ws2812]
+/// buffer holds up to 1365 LEDs, use [stream] for longer strips.
+pub const MAX_SAMPLES: usize = 32767;
+
+/// Number of PWM samples needed to drive `num_leds` LEDs.
+pub const fn buffer_len(num_leds: usize) -> usize {
+    num_leds * 24
+}
+
+/// Sample buffer of a [Ws2812] driving `LEDS` LEDs, 24 samples per LED.
+pub type Samples<const LEDS: usize> = [[u16; 24]; LEDS];
+
+#[doc(hidden)]
+pub use {smart_leds, static_cell};
+
+/// Create a [Ws2812] driver for a number of LEDs with static buffers.
+///
+/// Expands to `StaticCell`s for the [Samples] and for a shadow buffer backing [Ws2812::pixels_mut], and a call to
+/// [Ws2812::new], evaluating to `Result<Ws2812<'static, _>, Error>`.
+/// Each expansion owns its buffers so it panics if evaluated twice.
+///
+/// ```ignore
+/// bind_interrupts!(struct Irqs {
+///     PWM0 => embassy_nrf_ws2812_pwm::InterruptHandler<peripherals::PWM0>;
+/// });
+/// let mut ws = ws2812!(p.PWM0, Irqs, p.P0_13, 8).expect("to create WS2812 driver");
+/// ```
+#[macro_export]
+macro_rules! ws2812 {
+    ($pwm:expr, $irq:expr, $pin:expr, $num_leds:expr $(,)?) => {{
+        static BUFFER: $crate::static_cell::StaticCell<$crate::Samples<$num_leds>> =
+            $crate::static_cell::StaticCell::new();
+        static PIXELS: $crate::static_cell::StaticCell<[$crate::smart_leds::RGB8; $num_leds]> =
+            $crate::static_cell::StaticCell::new();
+        $crate::Ws2812::new($pwm, $irq, $pin, BUFFER.init([[0; 24]; $num_leds]))
+            .map(|ws| ws.with_shadow(PIXELS.init([$crate::smart_leds::RGB8::default(); $num_leds])))
+    }};
+}
+
+/// Sample buffer either borrowed from the caller or owned by the
+/// driver, dereferencing to the samples of every LED in order.
+///
+/// Chips with other than 24 samples per LED lay out their own buffer
+/// and pass its samples as a slice.
+enum Buffer<'d, const LEDS: usize> {
+    Borrowed(&'d mut Samples<LEDS>),
+    Owned(Samples<LEDS>),
+    Slice(&'d mut [u16]),
+}
+
+impl<const LEDS: usize> Deref for Buffer<'_, LEDS> {
+    type Target = [u16];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Buffer::Borrowed(buf) => buf.as_flattened(),
+            Buffer::Owned(buf) => buf.as_flattened(),
+            Buffer::Slice(buf) => buf,
+        }
+    }
+}
+
+impl<const LEDS: usize> DerefMut for Buffer<'_, LEDS> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Buffer::Borrowed(buf) => buf.as_flattened_mut(),
+            Buffer::Owned(buf) => buf.as_flattened_mut(),
+            Buffer::Slice(buf) => buf,
+        }
+    }
+}
+
+/// What to send to the LEDs past the end of a write with fewer
+/// colors than the strip has LEDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TailPolicy {
+    /// Turn the remaining LEDs off.
+    #[default]
+    Black,
+    /// Leave the remaining LEDs at their previous colors.
+    KeepPrevious,
+    /// Repeat the written colors along the rest of the strip.
+    Repeat,
+}
+
+/// What to do with colors past the end of the strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OverflowPolicy {
+    /// Ignore the extra colors.
+    #[default]
+    Truncate,
+    /// Fail the write with [Error::TooManyPixels] before anything is
+    /// encoded. The length comes from the `size_hint` of the iterator,
+    /// which has to bound it to the strip, like the iterators of
+    /// slices and arrays or `take`.
+    Error,
+}
+
+/// WS2812 driver for a strip of `LEDS` LEDs.
+///
+/// The driver borrows the PWM instance, pin and buffers for `'d`,
+/// dropping it stops the PWM and gives them back. To share the PWM
+/// instance with other drivers keep them in a
+/// [Ws2812Parts](parts::Ws2812Parts).
+///
+/// With [Ws2812Builder::build_double_buffered] writes return as soon
+/// as the frame has started, so the next frame is encoded while
+/// EasyDMA sends the previous one from the other buffer.
+///
+/// The async writes are cancel safe, dropping a write future stops
+/// the sequence and the driver stays usable. The strip may show a
+/// partial frame until the next write.
+pub struct Ws2812<'d, const LEDS: usize> {
+    pwm: SequencePwm<'d>,
+    events: Events,
+    buf: Buffer<'d, LEDS>,
+    framing: Framing,
+    encoder: Encoder,
+    shadow: Option<&'d mut [RGB8]>,
+    mask: Option<&'d [u8]>,
+    dirty: bool,
+    tail: TailPolicy,
+    overflow: OverflowPolicy,
+    back: Option<&'d mut Samples<LEDS>>,
+    in_flight: bool,
+    hold: bool,
+    looping: bool,
+    power: PowerModel,
+    limiter: Limiter,
+    #[cfg(feature = "async")]
+    min_interval: Option<Duration>,
+    #[cfg(feature = "async")]
+    last_frame: Option<Instant>,
+}
+
+impl<'d, const LEDS: usize> Ws2812<'d, LEDS> {
+    /// Create a new driver using the default [Timing] (WS2812B).
+    ///
+    /// The PWM instance and pin are moved into the driver so creating
+    /// a second driver (or any other user) on the same instance or pin
+    /// fails to compile. Any GPIO can be routed to any PWM instance on
+    /// nRF chips so there are no invalid pin and instance combinations.
+    /// `irq` binds the [InterruptHandler] of the PWM instance.
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Samples<LEDS>,
+    ) -> Result<Self, Error> {
+        Ws2812Builder::new().build(pwm, irq, pin, buf)
+    }
+
+    /// Create a new driver for a chip with different bit timings.
+    pub fn new_with_timing<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Samples<LEDS>,
+        timing: Timing,
+    ) -> Result<Self, Error> {
+        Ws2812Builder::new()
+            .timing(timing)
+            .build(pwm, irq, pin, buf)
+    }
+
+    /// Create a new driver which owns its sample buffer.
+    ///
+    /// The buffer is part of the driver so there is no need for a
+    /// separate `StaticCell`, but the driver is then `LEDS * 48` bytes
+    /// and should itself live in a static or a task rather than on
+    /// a small stack. Use [Ws2812::new] to place the buffer in a
+    /// specific RAM section.
+    pub fn new_owned<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+    ) -> Result<Self, Error> {
+        Ws2812Builder::new().build_owned(pwm, irq, pin)
+    }
+
+    /// Create the driver once the interrupt binding was checked
+    /// by the public constructors.
+    pub(crate) fn with_config<T: Instance>(
+        pwm: Peri<'d, T>,
+        pin: Peri<'d, impl Pin>,
+        backup: Option<Peri<'d, AnyPin>>,
+        buf: Buffer<'d, LEDS>,
+        back: Option<&'d mut Samples<LEDS>>,
+        builder: Ws2812Builder,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(
+                buffer_len(LEDS) <= MAX_SAMPLES,
+                "buffer longer than a PWM sequence"
+            )
+        };
+
+        let timing = builder.timing;
+        if !timing.is_valid_with(builder.prescaler) {
+            return Err(Error::InvalidConfig);
+        }
+        if !matches!(buf, Buffer::Owned(_)) {
+            dma::check(&buf[..])?;
+        }
+        if let Some(back) = &back {
+            dma::check(back.as_flattened())?;
+        }
+        // A looping frame is read from the buffer until the next write
+        // so it must not move with the driver or alternate buffers
+        if builder.hold && (back.is_some() || matches!(buf, Buffer::Owned(_))) {
+            return Err(Error::InvalidConfig);
+        }
+
+        let mut config = Config::default();
+        config.sequence_load = SequenceLoad::Common;
+        config.prescaler = builder.prescaler;
+        config.max_duty = timing.period_ticks(builder.prescaler);
+        config.ch0_drive = builder.drive;
+
+        let idle = [Some(IdlePin::of(&*pin)), backup.as_deref().map(IdlePin::of)];
+        let pwm = match backup {
+            // Common load sends every sample to both channels
+            Some(backup) => {
+                config.ch1_drive = builder.drive;
+                SequencePwm::new_2ch(pwm, pin, backup, config)?
+            }
+            None => SequencePwm::new_1ch(pwm, pin, config)?,
+        };
+        for pin in idle.into_iter().flatten() {
+            pin.set(builder.idle());
+        }
+        let encoder = Encoder::new(
+            &timing,
+            builder.prescaler,
+            builder.color_order,
+            builder.inverted,
+        );
+        let mut ws = Self {
+            pwm,
+            events: Events::new::<T>(),
+            buf,
+            framing: Framing::new(&timing, builder.prescaler, builder.refresh, &encoder, 1),
+            encoder,
+            shadow: None,
+            mask: None,
+            dirty: false,
+            tail: builder.tail,
+            overflow: builder.overflow,
+            back,
+            in_flight: false,
+            hold: builder.hold,
+            looping: false,
+            power: builder.power,
+            limiter: Limiter::new(builder.max_current),
+            #[cfg(feature = "async")]
+            min_interval: None,
+            #[cfg(feature = "async")]
+            last_frame: None,
+        };
+        ws.set_gamma(builder.gamma);
+        ws.set_brightness(builder.brightness);
+        ws.set_correction(builder.correction);
+        Ok(ws)
+    }
+
+    /// Order of the color channels on the wire.
+    pub fn color_order(&self) -> ColorOrder {
+        self.encoder.color_order
+    }
+
+    /// Change the order of the color channels, applied from the next write.
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.encoder.color_order = color_order;
+        self.dirty = true;
+    }
+
+    /// Change what is sent past the end of short writes.
+    pub fn set_tail_policy(&mut self, tail: TailPolicy) {
+        self.tail = tail;
+    }
+
+    /// Brightness applied to every frame, from `0` (off) to `255` (full).
+    pub fn brightness(&self) -> u8 {
+        self.encoder.brightness
+    }
+
+    /// Scale every color while encoding, applied from the next write.
+    ///
+    /// The scaling is applied before gamma correction and the
+    /// shadow buffer keeps the unscaled colors.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.encoder.brightness = brightness;
+        self.dirty = true;
+    }
+
+    /// White balance applied to every frame.
+    pub fn correction(&self) -> Correction {
+        self.encoder.correction
+    }
+
+    /// Scale each color channel while encoding, applied from the next
+    /// write, to match strips from different batches.
+    ///
+    /// The correction is applied before brightness and gamma, the
+    /// shadow buffer keeps the uncorrected colors.
+    pub fn set_correction(&mut self, correction: Correction) {
+        self.encoder.correction = correction;
+        self.dirty = true;
+    }
+
+    /// Gamma correct channel values while encoding, applied from the next write.
+    ///
+    /// A gamma of around `2.2` makes fades look even, `1.0` disables correction.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.encoder.gamma = (gamma != 1.0).then(|| gamma_table(gamma));
+        self.dirty = true;
+    }
+
+    /// Estimated current of `frame` in mA with the mask, correction,
+    /// brightness and gamma of this driver applied, using the
+    /// [PowerModel] of the builder.
+    ///
+    /// Lets battery or USB powered projects log the draw of a frame
+    /// before sending it.
+    pub fn estimate_current_ma(&self, frame: &[RGB8]) -> u32 {
+        let levels = frame.iter().enumerate().map(|(index, color)| {
+            let color = masked(self.mask, index, *color);
+            let color = self.encoder.correction.apply(color);
+            RGB8::new(
+                self.encoder.level(color.r),
+                self.encoder.level(color.g),
+                self.encoder.level(color.b),
+            )
+        });
+        self.power.current_ma(levels)
+    }
+
+    /// Estimated current of the last frame written per power feed in
+    /// mA, where feed `i` powers the LEDs up to `ends[i]`, see
+    /// [LoadMeter].
+    ///
+    /// The levels are read back from the samples, so they include the
+    /// mask, correction, brightness, gamma and current limit of this
+    /// driver.
+    pub fn estimate_feed_current_ma<const FEEDS: usize>(
+        &self,
+        ends: [usize; FEEDS],
+    ) -> [u32; FEEDS] {
+        // Double buffered writes swap the frame just sent to the back
+        let samples = match self.back.as_deref() {
+            Some(back) => back.as_flattened(),
+            None => &self.buf,
+        };
+        let mut meter = LoadMeter::new(self.power, ends);
+        for chunk in samples.chunks_exact(24) {
+            meter.update(self.encoder.decode_pixel(chunk));
+        }
+        meter.finish()
+    }
+
+    /// Current budget in mA frames are scaled down to, see
+    /// [Ws2812Builder::max_current_ma].
+    pub fn max_current_ma(&self) -> Option<u32> {
+        self.limiter.max_current
+    }
+
+    /// Change the current budget, applied from the next write.
+    ///
+    /// `None` sends frames unscaled, for example when a board
+    /// switches from USB to a bigger supply.
+    pub fn set_max_current_ma(&mut self, max_current: Option<u32>) {
+        self.limiter = Limiter::new(max_current);
+        self.dirty = true;
+    }
+
+    /// Limit the async writes to `fps` frames per second,
+    /// `0` removes the limit.
+    ///
+    /// A frame waits until `1 / fps` has passed since the previous
+    /// one started, so an animation loop without a timer of its own
+    /// doesn't keep the PWM and CPU busy sending frames back to back
+    /// on a short strip. Use a [FramePacer](pacing::FramePacer) for
+    /// a steady frame rate.
+    #[cfg(feature = "async")]
+    pub fn set_max_fps(&mut self, fps: u16) {
+        self.min_interval = (fps > 0).then(|| Duration::from_hz(fps as u64));
+    }
+
+    /// Wait until the interval set with [Ws2812::set_max_fps]
+    /// has passed since the previous frame started.
+    #[cfg(feature = "async")]
+    async fn wait_frame_interval(&mut self) {
+        if let Some(interval) = self.min_interval {
+            if let Some(last) = self.last_frame {
+                Timer::at(last + interval).await;
+            }
+            self.last_frame = Some(Instant::now());
+        }
+    }
+
+    /// Keep a shadow copy of the colors of the last frame.
+    ///
+    /// Writes compare the new colors to the shadow buffer and only
+    /// encode the LEDs that changed, so moving a few pixels along a
+    /// long strip is cheap.
+    pub fn with_shadow(mut self, shadow: &'d mut [RGB8; LEDS]) -> Self {
+        shadow.fill(RGB8::default());
+        self.shadow = Some(shadow);
+        self.dirty = true;
+        self
+    }
+
+    /// Scale every LED by its value in `mask` while encoding,
+    /// applied from the next write. `None` removes the mask.
+    ///
+    /// Dims LEDs behind a thicker diffuser or fades out the ends of
+    /// a light bar without scaling every frame in the application.
+    /// LEDs past the end of the mask are unchanged and the shadow
+    /// buffer keeps the unmasked colors. Without a shadow buffer
+    /// [TailPolicy::Repeat] repeats the masked colors.
+    pub fn set_mask(&mut self, mask: Option<&'d [u8]>) {
+        self.mask = mask;
+        self.dirty = true;
+    }
+
+    /// Colors of the last frame when a shadow buffer is attached.
+    pub fn shadow(&self) -> Option<&[RGB8]> {
+        self.shadow.as_deref()
+    }
+
+    /// Mutable access to the shadow buffer.
+    ///
+    /// The frame is marked dirty so the next call to [Ws2812::flush]
+    /// encodes the modified colors.
+    pub fn shadow_mut(&mut self) -> Option<&mut [RGB8]> {
+        let shadow = self.shadow.as_deref_mut()?;
+        self.dirty = true;
+        Some(shadow)
+    }
+
+    /// Colors of the current frame.
+    ///
+    /// # Panics
+    ///
+    /// If no shadow buffer is attached with [Ws2812::with_shadow].
+    pub fn pixels(&self) -> &[RGB8] {
+        self.shadow().expect("shadow buffer to be attached")
+    }
+
+    /// Framebuffer of the current frame, changes are encoded
+    /// and sent by the next [Ws2812::flush].
+    ///
+    /// Effects can read back the current colors to blend with them.
+    ///
+    /// # Panics
+    ///
+    /// If no shadow buffer is attached with [Ws2812::with_shadow].
+    pub fn pixels_mut(&mut self) -> &mut [RGB8] {
+        self.shadow_mut().expect("shadow buffer to be attached")
+    }
+
+    /// Change the color of one LED in the encoded frame without
+    /// re-encoding the others, sent by the next [Ws2812::flush].
+    ///
+    /// Indexes past the end of the strip are ignored.
+    pub fn set_pixel(&mut self, index: usize, color: RGB8) {
+        self.set_range(index..index.saturating_add(1), [color]);
+    }
+
+    /// Change the colors of a range of LEDs in the encoded frame,
+    /// sent by the next [Ws2812::flush].
+    ///
+    /// The part of the range past the end of the strip is ignored
+    /// and LEDs in the range without a color are left unchanged.
+    pub fn set_range<T, I>(&mut self, range: Range<usize>, iterator: T)
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        self.stop_loop();
+        let end = range.end.min(LEDS);
+        let range = range.start.min(end)..end;
+        let chunks = self.buf[range.start * 24..range.end * 24].chunks_exact_mut(24);
+        for ((index, chunk), color) in range.clone().zip(chunks).zip(iterator) {
+            let color = color.into();
+            if let Some(pixel) = self.shadow.as_deref_mut().and_then(|s| s.get_mut(index)) {
+                *pixel = color;
+            }
+            self.limiter
+                .encode_pixel(&self.encoder, chunk, masked(self.mask, index, color));
+        }
+        self.limit_current();
+    }
+
+    /// Set every LED to `color` and transmit the frame.
+    #[cfg(feature = "async")]
+    pub async fn fill(&mut self, color: RGB8) -> Result<(), Error> {
+        self.write_buffer(core::iter::repeat_n(color, LEDS))?;
+        Ok(self.transmit_frame().await?)
+    }
+
+    /// Turn every LED off.
+    #[cfg(feature = "async")]
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        self.fill(RGB8::default()).await
+    }
+
+    /// Transmit the current frame, re-encoding the
+    /// shadow buffer first if it was modified.
+    #[cfg(feature = "async")]
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.encode_shadow();
+        Ok(self.transmit_frame().await?)
+    }
+
+    /// Blocking version of [Ws2812::flush].
+    pub fn flush_blocking(&mut self) -> Result<(), Error> {
+        self.encode_shadow();
+        Ok(self.transmit_frame_blocking()?)
+    }
+
+    /// Stop the PWM until the next write or [Ws2812::resume].
+    ///
+    /// The PWM only runs while a frame is sent, except for a frame held
+    /// with [Ws2812Builder::hold_last_frame] which keeps it running and
+    /// the high frequency clock requested. Suspending stops the loop,
+    /// the LEDs keep the frame they latched last, and waits for a frame
+    /// still sent by a double buffered driver, so the chip can sleep
+    /// at its lowest current until the LEDs change again.
+    pub fn suspend(&mut self) {
+        self.wait_in_flight_blocking();
+        self.stop_loop();
+    }
+
+    /// Hold the current frame again after [Ws2812::suspend].
+    ///
+    /// Only needed with [Ws2812Builder::hold_last_frame], without it
+    /// the next write starts the PWM.
+    pub fn resume(&mut self) -> Result<(), Error> {
+        if self.hold && !self.looping {
+            self.transmit_frame_blocking()?;
+        }
+        Ok(())
+    }
+
+    /// Start writing a frame without waiting for it to be sent.
+    ///
+    /// The frame is sent by EasyDMA while the returned [PendingWrite]
+    /// borrows the driver, check [PendingWrite::poll_done] between
+    /// other work. Dropping it waits for the frame to finish.
+    pub fn start_write<T, I>(&mut self, iterator: T) -> Result<PendingWrite<'_, 'd>, Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        self.write_buffer(iterator.into_iter().map(Into::into))?;
+        self.wait_in_flight_blocking();
+        self.stop_loop();
+        let seq = self.framing.start(
+            &mut self.pwm,
+            &self.events,
+            &self.buf,
+            SequenceMode::Loop(1),
+        )?;
+        Ok(PendingWrite {
+            _seq: seq,
+            events: self.events,
+        })
+    }
+
+    fn encode_shadow(&mut self) {
+        if self.shadow.is_some() && self.dirty {
+            self.stop_loop();
+        }
+        if let Some(shadow) = self.shadow.as_deref()
+            && self.dirty
+        {
+            let colors = shadow.iter().enumerate();
+            let colors = colors.map(|(index, &color)| masked(self.mask, index, color));
+            self.limiter.reset();
+            self.encoder.encode(&mut self.buf, colors);
+            self.dirty = false;
+            self.limit_current();
+        }
+    }
+
+    /// Encode a frame, applying the [TailPolicy] and [OverflowPolicy].
+    fn write_buffer(&mut self, mut iter: impl Iterator<Item = RGB8>) -> Result<(), Error> {
+        if self.overflow == OverflowPolicy::Error {
+            let (lower, upper) = iter.size_hint();
+            if upper.is_none_or(|upper| upper > LEDS) {
+                return Err(Error::TooManyPixels(upper.unwrap_or(lower)));
+            }
+        }
+        self.stop_loop();
+        match self.shadow.as_deref_mut() {
+            Some(shadow) => {
+                // The samples match the shadow buffer unless it or the
+                // encoder changed, then only changed LEDs are encoded
+                let full = self.dirty;
+                if full {
+                    self.limiter.reset();
+                }
+                let mut len = 0;
+                for (index, chunk) in self.buf.chunks_exact_mut(24).enumerate() {
+                    let next = if len == index { iter.next() } else { None };
+                    let color = match next {
+                        Some(color) => {
+                            len += 1;
+                            color
+                        }
+                        None => match self.tail {
+                            TailPolicy::Black => RGB8::default(),
+                            TailPolicy::Repeat if len > 0 => shadow[index - len],
+                            TailPolicy::Repeat | TailPolicy::KeepPrevious => shadow[index],
+                        },
+                    };
+                    if full || shadow[index] != color {
+                        shadow[index] = color;
+                        self.limiter.encode_pixel(
+                            &self.encoder,
+                            chunk,
+                            masked(self.mask, index, color),
+                        );
+                    }
+                }
+            }
+            None => {
+                // Only a kept tail stays at the scale of the previous frame
+                if self.tail != TailPolicy::KeepPrevious {
+                    self.limiter.reset();
+                }
+                let mask = self.mask;
+                let colors = iter.by_ref().enumerate();
+                let colors = colors.map(|(index, color)| masked(mask, index, color));
+                let len = self.encoder.encode(&mut self.buf, colors);
+                let samples = len * 24;
+                self.limiter.apply(&self.encoder, &mut self.buf[..samples]);
+                match self.tail {
+                    TailPolicy::Black => {
+                        let tail = core::iter::repeat(RGB8::default());
+                        self.encoder.encode(&mut self.buf[samples..], tail);
+                    }
+                    TailPolicy::KeepPrevious => {}
+                    TailPolicy::Repeat => repeat_tail(&mut self.buf, samples),
+                }
+            }
+        }
+        self.dirty = false;
+        self.limit_current();
+        Ok(())
+    }
+
+    /// Scale the encoded frame down when its estimated current is
+    /// over the budget set with [Ws2812Builder::max_current_ma].
+    ///
+    /// The colors are read back from the samples so frames are
+    /// limited however they were written. The samples of a limited
+    /// frame no longer match the shadow buffer, which is marked
+    /// dirty so the next write encodes every LED again.
+    fn limit_current(&mut self) {
+        if self
+            .limiter
+            .limit(&self.encoder, &self.power, &mut self.buf)
+        {
+            self.dirty = self.shadow.is_some();
+        }
+    }
+
+    /// Encode colors into a separate sample buffer using the
+    /// timing, color order and polarity of this driver.
+    ///
+    /// Buffers can be prepared ahead of time and sent later with
+    /// [Ws2812::transmit], each LED takes 24 samples.
+    pub fn encode_into<T, I>(&self, buf: &mut [u16], iterator: T)
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        self.encoder
+            .encode(buf, iterator.into_iter().map(Into::into));
+    }
+
+    /// Encode colors for a strip mixing RGB and RGBW chips.
+    ///
+    /// `buf` needs [ChipMap::buffer_len] samples. RGB chips show
+    /// the white channel mixed into the red, green and blue channels.
+    pub fn encode_mixed<T>(&self, buf: &mut [u16], map: &ChipMap, iterator: T)
+    where
+        T: IntoIterator<Item = RGBW<u8>>,
+    {
+        let mut samples = buf;
+        for (chip, color) in map.chips().zip(iterator) {
+            let Some((chunk, rest)) = samples.split_at_mut_checked(chip.samples()) else {
+                break;
+            };
+            let rgb = RGB8::new(color.r, color.g, color.b);
+            match chip {
+                Chip::Rgb => {
+                    let w = color.a.0;
+                    let rgb = RGB8::new(
+                        rgb.r.saturating_add(w),
+                        rgb.g.saturating_add(w),
+                        rgb.b.saturating_add(w),
+                    );
+                    self.encoder.encode_pixel(chunk, rgb);
+                }
+                Chip::Rgbw => {
+                    let (rgb_chunk, white) = chunk.split_at_mut(24);
+                    self.encoder.encode_pixel(rgb_chunk, rgb);
+                    self.encoder.encode_byte(white, color.a.0);
+                }
+            }
+            samples = rest;
+        }
+    }
+
+    /// Transmit a buffer prepared with [Ws2812::encode_into].
+    ///
+    /// The buffer must be in RAM for EasyDMA.
+    #[cfg(feature = "async")]
+    pub async fn transmit(&mut self, buf: &[u16]) -> Result<(), Error> {
+        self.wait_frame_interval().await;
+        self.wait_in_flight().await;
+        self.stop_loop();
+        Ok(play(&mut self.pwm, &self.events, buf, &self.framing).await?)
+    }
+
+    #[cfg(feature = "async")]
+    async fn transmit_frame(&mut self) -> Result<(), pwm::Error> {
+        self.wait_frame_interval().await;
+        self.wait_in_flight().await;
+        self.stop_loop();
+        if self.back.is_some() {
+            return self.start_in_flight();
+        }
+        if self.hold {
+            // The loop raises LOOPSDONE after the reset of its first
+            // pass, once the frame has latched
+            let seq = self.framing.start(
+                &mut self.pwm,
+                &self.events,
+                &self.buf,
+                SequenceMode::Infinite,
+            )?;
+            self.events.wait(Event::LoopsDone).await;
+            // Dropping the sequencer would stop the loop, the buffer is
+            // borrowed for 'd so the loop can outlive this call
+            core::mem::forget(seq);
+            self.looping = true;
+            return Ok(());
+        }
+        play(&mut self.pwm, &self.events, &self.buf, &self.framing).await
+    }
+
+    fn transmit_frame_blocking(&mut self) -> Result<(), pwm::Error> {
+        self.wait_in_flight_blocking();
+        self.stop_loop();
+        if self.back.is_some() {
+            return self.start_in_flight();
+        }
+        if self.hold {
+            let seq = self.framing.start(
+                &mut self.pwm,
+                &self.events,
+                &self.buf,
+                SequenceMode::Infinite,
+            )?;
+            self.events.wait_blocking(Event::LoopsDone);
+            // Dropping the sequencer would stop the loop, the buffer is
+            // borrowed for 'd so the loop can outlive this call
+            core::mem::forget(seq);
+            self.looping = true;
+            return Ok(());
+        }
+        play_blocking(&mut self.pwm, &self.events, &self.buf, &self.framing)
+    }
+
+    /// Stop a frame left looping by [Ws2812Builder::hold_last_frame]
+    /// before the buffer is changed.
+    fn stop_loop(&mut self) {
+        if self.looping {
+            // The sequencer stops the PWM when dropped
+            drop(SingleSequencer::new(
+                &mut self.pwm,
+                &self.buf,
+                SequenceConfig::default(),
+            ));
+            self.looping = false;
+        }
+    }
+
+    /// Start sending the encoded frame without waiting for it and swap
+    /// buffers so the next frame is encoded while this one is sent.
+    ///
+    /// The next frame waits for LOOPSDONE in [Ws2812::wait_in_flight],
+    /// after the reset of this one, so back to back frames still latch.
+    fn start_in_flight(&mut self) -> Result<(), pwm::Error> {
+        let seq = self.framing.start(
+            &mut self.pwm,
+            &self.events,
+            &self.buf,
+            SequenceMode::Loop(1),
+        )?;
+        // Dropping the sequencer would stop the frame, both buffers are
+        // borrowed for 'd so the frame can outlive this call
+        core::mem::forget(seq);
+        self.in_flight = true;
+
+        if let (Buffer::Borrowed(front), Some(back)) = (&mut self.buf, self.back.as_mut()) {
+            core::mem::swap(front, back);
+        }
+        // The new front buffer holds the frame before last
+        self.dirty = self.shadow.is_some();
+        Ok(())
+    }
+
+    /// Wait for a frame started by double buffering to finish,
+    /// including its reset.
+    #[cfg(feature = "async")]
+    async fn wait_in_flight(&mut self) {
+        if self.in_flight {
+            self.events.wait(Event::LoopsDone).await;
+            self.in_flight = false;
+        }
+    }
+
+    /// Blocking version of [Ws2812::wait_in_flight].
+    fn wait_in_flight_blocking(&mut self) {
+        if self.in_flight {
+            self.events.wait_blocking(Event::LoopsDone);
+            self.in_flight = false;
+        }
+    }
+}
+
+/// Frame being sent by [Ws2812::start_write].
+pub struct PendingWrite<'a, 'd> {
+    _seq: Sequencer<'d, 'a>,
+    events: Events,
+}
+
+impl PendingWrite<'_, '_> {
+    /// Whether the frame including the reset period has been sent.
+    pub fn poll_done(&self) -> bool {
+        self.events.is_triggered(Event::LoopsDone)
+    }
+
+    /// Block until the frame has been sent.
+    pub fn wait(self) {
+        // Dropping waits for the frame
+    }
+}
+
+impl Drop for PendingWrite<'_, '_> {
+    fn drop(&mut self) {
+        self.events.wait_blocking(Event::LoopsDone);
+    }
+}
+
+/// Idle samples of both polarities sent as the reset sequence, four
+/// make up a period of individually loaded channels. Kept in RAM for
+/// EasyDMA and never written.
+static mut IDLE_SAMPLES: [[u16; 4]; 2] = [[POLARITY; 4], [0; 4]];
+
+/// GPIO output of a PWM pin, the level of the line
+/// whenever the PWM isn't driving it.
+#[derive(Clone, Copy)]
+pub(crate) struct IdlePin {
+    port: pac::gpio::Gpio,
+    pin: usize,
+}
+
+impl IdlePin {
+    pub(crate) fn of(pin: &impl Pin) -> Self {
+        let port = match pin.port() {
+            #[cfg(any(
+                feature = "nrf52833",
+                feature = "nrf52840",
+                feature = "_nrf5340-app",
+                feature = "_nrf54l"
+            ))]
+            embassy_nrf::gpio::Port::Port1 => pac::P1,
+            #[cfg(feature = "_nrf54l")]
+            embassy_nrf::gpio::Port::Port2 => pac::P2,
+            _ => pac::P0,
+        };
+        Self {
+            port,
+            pin: pin.pin() as usize,
+        }
+    }
+
+    /// Hold the line at `level` between frames, embassy-nrf
+    /// drives the pins low when creating a [SequencePwm].
+    pub(crate) fn set(self, level: Level) {
+        match level {
+            Level::High => self.port.outset().write(|w| w.set_pin(self.pin, true)),
+            Level::Low => self.port.outclr().write(|w| w.set_pin(self.pin, true)),
+        }
+    }
+}
+
+/// How a driver sends a frame: the samples, each held for `refresh + 1`
+/// periods, followed by a reset sequence latching them.
+///
+/// The PWM keeps the last sample of a sequence during its end delay, so
+/// an end delay after the frame would repeat its last bit. The reset is
+/// a second sequence of idle samples instead, its end delay holding the
+/// line idle for the reset time. LOOPSDONE follows that end delay, so a
+/// frame started after it always follows a full reset.
+#[derive(Debug, Clone, Copy)]
+struct Framing {
+    refresh: u32,
+    idle: &'static [u16],
+    end_delay: u32,
+}
+
+impl Framing {
+    /// Framing for `timing`, with `channels` samples per PWM period.
+    fn new(
+        timing: &Timing,
+        prescaler: Prescaler,
+        refresh: u32,
+        encoder: &Encoder,
+        channels: usize,
+    ) -> Self {
+        let polarity = (encoder.zero & POLARITY == 0) as usize;
+        let samples = (&raw const IDLE_SAMPLES).cast::<[u16; 4]>();
+        // SAFETY: `channels` samples of one polarity, never written
+        let idle = unsafe { core::slice::from_raw_parts(samples.add(polarity).cast(), channels) };
+        Self {
+            refresh,
+            idle,
+            // The idle samples take the first period of the reset
+            end_delay: timing.reset_periods(prescaler).saturating_sub(1),
+        }
+    }
+
+    /// Start sending `words` followed by the reset, once with
+    /// `Loop(1)` or until stopped with `Infinite`.
+    fn start<'d, 's>(
+        &self,
+        pwm: &'s mut SequencePwm<'d>,
+        events: &Events,
+        words: &'s [u16],
+        mode: SequenceMode,
+    ) -> Result<Sequencer<'d, 's>, pwm::Error> {
+        let mut frame = SequenceConfig::default();
+        frame.refresh = self.refresh;
+        let mut reset = SequenceConfig::default();
+        reset.end_delay = self.end_delay;
+        events.clear(Event::LoopsDone);
+        let seq = Sequencer::new(
+            pwm,
+            Sequence::new(words, frame),
+            Some(Sequence::new(self.idle, reset)),
+        );
+        seq.start(StartSequence::Zero, mode)?;
+        Ok(seq)
+    }
+}
+
+/// Play a frame once and wait for it to finish.
+///
+/// The frame and its reset run as one loop, which raises LOOPSDONE
+/// after the end delay of the reset sequence. SEQEND is raised when
+/// the last sample of a sequence is loaded, before its end delay.
+///
+/// The sequencer only borrows the PWM so dropping the future stops
+/// the sequence (and EasyDMA) and the driver stays usable.
+#[cfg(feature = "async")]
+async fn play(
+    pwm: &mut SequencePwm<'_>,
+    events: &Events,
+    words: &[u16],
+    framing: &Framing,
+) -> Result<(), pwm::Error> {
+    let _seq = framing.start(pwm, events, words, SequenceMode::Loop(1))?;
+    events.wait(Event::LoopsDone).await;
+    Ok(())
+}
+
+/// Play a frame once blocking until it has finished.
+fn play_blocking(
+    pwm: &mut SequencePwm<'_>,
+    events: &Events,
+    words: &[u16],
+    framing: &Framing,
+) -> Result<(), pwm::Error> {
+    let _seq = framing.start(pwm, events, words, SequenceMode::Loop(1))?;
+    events.wait_blocking(Event::LoopsDone);
+    Ok(())
+}
+
+/// Encodes colors into PWM samples.
+///
+/// Channel values are copied a nibble at a time from a table of the
+/// 4 samples of every nibble, which takes 128 bytes and is several
+/// times faster than testing each bit.
+#[derive(Debug, Clone)]
+struct Encoder {
+    zero: u16,
+    color_order: ColorOrder,
+    correction: Correction,
+    gamma: Option<[u8; 256]>,
+    brightness: u8,
+    samples: [[u16; 4]; 16],
+}
+
+impl Encoder {
+    fn new(timing: &Timing, prescaler: Prescaler, color_order: ColorOrder, inverted: bool) -> Self {
+        let polarity = if inverted { 0 } else { POLARITY };
+        let zero = polarity | timing.zero_ticks(prescaler);
+        let one = polarity | timing.one_ticks(prescaler);
+        let mut samples = [[zero; 4]; 16];
+        for (nibble, samples) in samples.iter_mut().enumerate() {
+            for (bit, sample) in samples.iter_mut().enumerate() {
+                if nibble & (0x8 >> bit) != 0 {
+                    *sample = one;
+                }
+            }
+        }
+        Self {
+            zero,
+            color_order,
+            correction: Correction::NONE,
+            gamma: None,
+            brightness: u8::MAX,
+            samples,
+        }
+    }
+
+    /// Encode colors into `buf`, returns the number of LEDs encoded.
+    fn encode(&self, buf: &mut [u16], iter: impl Iterator<Item = RGB8>) -> usize {
+        let mut len = 0;
+        // Chunks first so no color is taken from `iter` once the buffer is full
+        for (chunk, color) in buf.chunks_exact_mut(24).zip(iter) {
+            self.encode_pixel(chunk, color);
+            len += 1;
+        }
+        len
+    }
+
+    /// Encode one color into the 24 samples of an LED.
+    fn encode_pixel(&self, chunk: &mut [u16], color: RGB8) {
+        for (byte, samples) in self
+            .color_order
+            .bytes(self.correction.apply(color))
+            .into_iter()
+            .zip(chunk.chunks_exact_mut(8))
+        {
+            self.encode_byte(samples, byte);
+        }
+    }
+
+    /// Encode one channel into 8 samples, most significant bit first.
+    fn encode_byte(&self, samples: &mut [u16], byte: u8) {
+        self.encode_raw(samples, self.level(byte));
+    }
+
+    /// Channel value sent for `byte` after brightness and gamma.
+    fn level(&self, byte: u8) -> u8 {
+        let byte = scale(byte, self.brightness);
+        match &self.gamma {
+            Some(table) => table[byte as usize],
+            None => byte,
+        }
+    }
+
+    /// Encode a byte into 8 samples without brightness or gamma.
+    fn encode_raw(&self, samples: &mut [u16], byte: u8) {
+        let (high, low) = samples.split_at_mut(4);
+        high.copy_from_slice(&self.samples[(byte >> 4) as usize]);
+        low.copy_from_slice(&self.samples[(byte & 0xf) as usize]);
+    }
+
+    /// Byte sent by 8 samples, the inverse of [Encoder::encode_raw].
+    fn decode_raw(&self, samples: &[u16]) -> u8 {
+        samples
+            .iter()
+            .fold(0, |byte, &sample| byte << 1 | (sample != self.zero) as u8)
+    }
+
+    /// Channel levels sent by the 24 samples of an LED.
+    fn decode_pixel(&self, chunk: &[u16]) -> RGB8 {
+        let mut bytes = [0; 3];
+        for (byte, samples) in bytes.iter_mut().zip(chunk.chunks_exact(8)) {
+            *byte = self.decode_raw(samples);
+        }
+        self.color_order.color(bytes)
+    }
+
+    /// Scale every channel level of `samples` by `ratio` in 1/256.
+    fn scale(&self, samples: &mut [u16], ratio: u32) {
+        for samples in samples.chunks_exact_mut(8) {
+            let level = self.decode_raw(samples);
+            self.encode_raw(samples, ((level as u32 * ratio) >> 8) as u8);
+        }
+    }
+}
+
+/// Current budget of the frames and the scale it applied to the
+/// frame in the samples.
+///
+/// LEDs encoded into a limited frame are scaled the same way, so
+/// changing a few LEDs doesn't dim the rest of the frame again.
+#[derive(Clone, Copy)]
+struct Limiter {
+    max_current: Option<u32>,
+    /// Scale of the encoded frame in 1/256, 256 leaves it unscaled.
+    scale: u32,
+}
+
+impl Limiter {
+    const fn new(max_current: Option<u32>) -> Self {
+        Self {
+            max_current,
+            scale: 256,
+        }
+    }
+
+    /// Start a frame encoding every LED again.
+    fn reset(&mut self) {
+        self.scale = 256;
+    }
+
+    /// Encode one LED at the scale of the frame.
+    fn encode_pixel(&self, encoder: &Encoder, chunk: &mut [u16], color: RGB8) {
+        encoder.encode_pixel(chunk, color);
+        self.apply(encoder, chunk);
+    }
+
+    /// Scale LEDs encoded from unscaled colors like the rest of the frame.
+    fn apply(&self, encoder: &Encoder, samples: &mut [u16]) {
+        if self.scale < 256 {
+            encoder.scale(samples, self.scale);
+        }
+    }
+
+    /// Scale the frame down when its estimated current is over the
+    /// budget, returns whether it was scaled.
+    fn limit(&mut self, encoder: &Encoder, power: &PowerModel, buf: &mut [u16]) -> bool {
+        let Some(max_current) = self.max_current else {
+            return false;
+        };
+        let levels = buf
+            .chunks_exact(24)
+            .map(|chunk| encoder.decode_pixel(chunk));
+        let current = power.current_ma(levels);
+        // Only the color channels scale, the idle current stays
+        let idle = power.idle as u32 * (buf.len() / 24) as u32;
+        if current <= max_current || current <= idle {
+            return false;
+        }
+        let ratio = max_current.saturating_sub(idle) * 256 / (current - idle);
+        encoder.scale(buf, ratio);
+        self.scale = self.scale * ratio / 256;
+        true
+    }
+}
+
+/// Scale `color` by the mask value of LED `index`, LEDs
+/// past the end of the mask are left unchanged.
+fn masked(mask: Option<&[u8]>, index: usize, color: RGB8) -> RGB8 {
+    match mask.and_then(|mask| mask.get(index)) {
+        Some(&value) => Correction::new(value, value, value).apply(color),
+        None => color,
+    }
+}
+
+/// Repeat the first `len` items of `buf` until it is full,
+/// an empty start leaves the buffer unchanged.
+fn repeat_tail<T: Copy>(buf: &mut [T], len: usize) {
+    if len == 0 {
+        return;
+    }
+    for i in len..buf.len() {
+        buf[i] = buf[i - len];
+    }
+}
+
+/// Scale a channel value by `brightness / 256`, full brightness is lossless.
+const fn scale(value: u8, brightness: u8) -> u8 {
+    ((value as u16 * (brightness as u16 + 1)) >> 8) as u8
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Ws2812<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.write_buffer(iterator.into_iter().map(Into::into))?;
+        Ok(self.transmit_frame().await?)
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Ws2812<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.write_buffer(iterator.into_iter().map(Into::into))?;
+        Ok(self.transmit_frame_blocking()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoder() -> Encoder {
+        Encoder::new(&Timing::WS2812B, Prescaler::Div1, ColorOrder::Grb, false)
+    }
+
+    #[test]
+    fn encode_raw_sends_msb_first() {
+        let encoder = encoder();
+        let zero = POLARITY | 6;
+        let one = POLARITY | 13;
+        let mut samples = [0; 8];
+        encoder.encode_raw(&mut samples, 0b1010_0011);
+        assert_eq!(samples, [one, zero, one, zero, zero, zero, one, one]);
+    }
+
+    #[test]
+    fn encode_raw_decodes_every_byte() {
+        let encoder = encoder();
+        let mut samples = [0; 8];
+        for byte in 0..=u8::MAX {
+            encoder.encode_raw(&mut samples, byte);
+            assert_eq!(encoder.decode_raw(&samples), byte);
+        }
+    }
+
+    #[test]
+    fn encode_pixel_decodes_in_color_order() {
+        let encoder = encoder();
+        let color = RGB8::new(1, 2, 3);
+        let mut chunk = [0; 24];
+        encoder.encode_pixel(&mut chunk, color);
+        assert_eq!(encoder.decode_raw(&chunk[..8]), 2);
+        assert_eq!(encoder.decode_pixel(&chunk), color);
+    }
+
+    #[test]
+    fn limited_pixels_match_a_limited_write() {
+        let encoder = encoder();
+        let power = PowerModel::WS2812B;
+        // One white LED draws 43 mA, two are over the budget
+        let white = RGB8::new(255, 255, 255);
+        let mut limiter = Limiter::new(Some(60));
+        let mut written = [0; 48];
+        encoder.encode(&mut written, [white, white].into_iter());
+        assert!(limiter.limit(&encoder, &power, &mut written));
+
+        // set_pixel twice on a blank frame
+        let mut set = Limiter::new(Some(60));
+        let mut pixels = [0; 48];
+        encoder.encode(&mut pixels, core::iter::repeat(RGB8::default()));
+        for index in 0..2 {
+            set.encode_pixel(&encoder, &mut pixels[index * 24..][..24], white);
+            set.limit(&encoder, &power, &mut pixels);
+        }
+        assert_eq!(pixels, written);
+
+        // and twice more on the limited frame
+        for index in 0..2 {
+            set.encode_pixel(&encoder, &mut pixels[index * 24..][..24], white);
+            set.limit(&encoder, &power, &mut pixels);
+        }
+        assert_eq!(pixels, written);
+    }
+
+    #[test]
+    fn inverted_encoder_clears_polarity() {
+        let encoder = Encoder::new(&Timing::WS2812B, Prescaler::Div1, ColorOrder::Grb, true);
+        let mut samples = [0; 8];
+        encoder.encode_raw(&mut samples, 0xf0);
+        assert_eq!(samples, [13, 13, 13, 13, 6, 6, 6, 6]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Lock-free single slot mailbox for submitting frames from an interrupt.
+//!
+//! The mailbox is a triple buffer: the sender always has a frame to
+//! write into, the receiver always has a frame to read from and a
+//! third frame in the middle is swapped atomically between them. The
+//! sender never blocks or allocates so it can run in a hot interrupt
+//! handler (for example radio packet reception) and when frames are
+//! sent faster than they are displayed only the newest is kept.
+
+use core::cell::UnsafeCell;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::Poll;
+use embassy_sync::waitqueue::AtomicWaker;
+use smart_leds::RGB8;
+
+/// Bits holding the index of the middle frame.
+const INDEX: u8 = 0b011;
+
+/// Bit set when the middle frame has not been received yet.
+const NEW: u8 = 0b100;
+
+/// Triple buffered mailbox holding frames of `LEDS` colors.
+pub struct Mailbox<const LEDS: usize> {
+    frames: [UnsafeCell<[RGB8; LEDS]>; 3],
+    state: AtomicU8,
+    waker: AtomicWaker,
+}
+
+// SAFETY: the sender and receiver each own one frame index and
+// only the middle index is shared, exchanged with atomic swaps.
+unsafe impl<const LEDS: usize> Sync for Mailbox<LEDS> {}
+
+impl<const LEDS: usize> Default for Mailbox<LEDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LEDS: usize> Mailbox<LEDS> {
+    /// Create an empty mailbox.
+    pub const fn new() -> Self {
+        Self {
+            frames: [const { UnsafeCell::new([RGB8::new(0, 0, 0); LEDS]) }; 3],
+            state: AtomicU8::new(1),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Split the mailbox into its sending and receiving halves.
+    pub fn split(&'static mut self) -> (FrameSender<LEDS>, FrameReceiver<LEDS>) {
+        let mailbox: &'static Self = self;
+        (
+            FrameSender { mailbox, back: 0 },
+            FrameReceiver { mailbox, front: 2 },
+        )
+    }
+}
+
+/// Sending half of a [Mailbox], safe to use from an interrupt handler.
+pub struct FrameSender<const LEDS: usize> {
+    mailbox: &'static Mailbox<LEDS>,
+    back: u8,
+}
+
+impl<const LEDS: usize> FrameSender<LEDS> {
+    /// Frame to fill in before calling [FrameSender::send].
+    pub fn frame(&mut self) -> &mut [RGB8; LEDS] {
+        // SAFETY: the back frame is only accessed by the sender.
+        unsafe { &mut *self.mailbox.frames[self.back as usize].get() }
+    }
+
+    /// Publish the frame, replacing a previous frame
+    /// that the receiver has not picked up yet.
+    pub fn send(&mut self) {
+        let previous = self.mailbox.state.swap(self.back | NEW, Ordering::AcqRel);
+        self.back = previous & INDEX;
+        self.mailbox.waker.wake();
+    }
+}
+
+/// Receiving half of a [Mailbox].
+pub struct FrameReceiver<const LEDS: usize> {
+    mailbox: &'static Mailbox<LEDS>,
+    front: u8,
+}
+
+impl<const LEDS: usize> FrameReceiver<LEDS> {
+    /// Take the newest frame if one was sent since the last call.
+    pub fn try_receive(&mut self) -> Option<&[RGB8; LEDS]> {
+        if self.take() {
+            Some(self.current())
+        } else {
+            None
+        }
+    }
+
+    /// Wait for a new frame.
+    pub async fn receive(&mut self) -> &[RGB8; LEDS] {
+        poll_fn(|cx| {
+            self.mailbox.waker.register(cx.waker());
+            if self.take() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        self.current()
+    }
+
+    fn take(&mut self) -> bool {
+        if self.mailbox.state.load(Ordering::Acquire) & NEW == 0 {
+            return false;
+        }
+        let previous = self.mailbox.state.swap(self.front, Ordering::AcqRel);
+        self.front = previous & INDEX;
+        true
+    }
+
+    fn current(&self) -> &[RGB8; LEDS] {
+        // SAFETY: the front frame is only accessed by the receiver.
+        unsafe { &*self.mailbox.frames[self.front as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::pin::pin;
+    use core::task::{Context, Waker};
+    use std::boxed::Box;
+
+    const RED: RGB8 = RGB8::new(255, 0, 0);
+    const GREEN: RGB8 = RGB8::new(0, 255, 0);
+    const BLUE: RGB8 = RGB8::new(0, 0, 255);
+
+    fn split() -> (FrameSender<2>, FrameReceiver<2>) {
+        Box::leak(Box::new(Mailbox::new())).split()
+    }
+
+    #[test]
+    fn empty_until_sent() {
+        let (_, mut rx) = split();
+        assert_eq!(rx.try_receive(), None);
+    }
+
+    #[test]
+    fn keeps_newest_frame() {
+        let (mut tx, mut rx) = split();
+        for color in [RED, GREEN, BLUE] {
+            tx.frame().fill(color);
+            tx.send();
+        }
+        assert_eq!(rx.try_receive(), Some(&[BLUE; 2]));
+        assert_eq!(rx.try_receive(), None);
+    }
+
+    #[test]
+    fn sender_skips_received_frame() {
+        let (mut tx, mut rx) = split();
+        tx.frame().fill(RED);
+        tx.send();
+        let received = rx.try_receive().unwrap();
+
+        tx.frame().fill(GREEN);
+        tx.send();
+        tx.frame().fill(BLUE);
+        assert_eq!(received, &[RED; 2]);
+        assert_eq!(rx.try_receive(), Some(&[GREEN; 2]));
+    }
+
+    #[test]
+    fn receive_waits_for_send() {
+        let (mut tx, mut rx) = split();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(pin!(rx.receive()).poll(&mut cx).is_pending());
+
+        tx.frame().fill(RED);
+        tx.send();
+        assert_eq!(pin!(rx.receive()).poll(&mut cx), Poll::Ready(&[RED; 2]));
+    }
+}
@@ -0,0 +1,446 @@
+//! 2D panels built from a strip.
+//!
+//! WS2812 panels chain their LEDs row by row or column by column,
+//! starting in one of the corners. Most 8×8 and 16×16 panels are wired
+//! serpentine, every other row runs backwards so the data line zig-zags
+//! across the panel. A [Layout] describes the wiring and maps `(x, y)`
+//! coordinates, `x` to the right and `y` down from the top left, to
+//! strip indices. A [Matrix] keeps a frame addressed by coordinates and
+//! yields it in strip order for any driver.
+//!
+//! ```ignore
+//! let mut matrix = Matrix::<8, 8>::new(Layout::SERPENTINE);
+//! matrix.set(3, 4, colors::RED);
+//! ws.write(matrix.iter()).await?;
+//! ```
+//!
+//! Larger displays chain several panels. [Tiles] places each panel,
+//! with its own layout and mounted rotated or flipped, in one logical
+//! frame, the panels in the order the data line runs through them:
+//!
+//! ```ignore
+//! let panel = |x, y, rotation| Tile::new(x, y, 16, 16, Layout::SERPENTINE).rotation(rotation);
+//! let tiles = Tiles::new([
+//!     panel(0, 0, Rotation::None),
+//!     panel(16, 0, Rotation::None),
+//!     panel(16, 16, Rotation::Cw180),
+//!     panel(0, 16, Rotation::Cw180),
+//! ]);
+//! let mut matrix = Matrix::<32, 32, _>::new(tiles);
+//! ```
+
+use smart_leds::RGB8;
+
+/// Corner of the panel holding the first LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Origin {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Maps the coordinates of a `width` × `height` frame to strip indices.
+pub trait Mapping {
+    /// Strip index of the LED at `(x, y)`, `None` when there is no LED.
+    fn index(&self, x: usize, y: usize, width: usize, height: usize) -> Option<usize>;
+
+    /// Coordinates of the LED at strip `index`, `None` past the last LED.
+    fn position(&self, index: usize, width: usize, height: usize) -> Option<(usize, usize)>;
+}
+
+/// Wiring of the LEDs of a panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Layout {
+    /// Corner of the first LED.
+    pub origin: Origin,
+    /// Whether the LEDs run along columns instead of rows.
+    pub column_major: bool,
+    /// Whether every other row (or column) runs backwards.
+    pub serpentine: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::ROWS
+    }
+}
+
+impl Layout {
+    /// Rows from the top left, every row left to right.
+    pub const ROWS: Self = Self::new(Origin::TopLeft, false, false);
+    /// Rows from the top left, zig-zagging.
+    pub const SERPENTINE: Self = Self::new(Origin::TopLeft, false, true);
+
+    /// Layout starting at `origin`.
+    pub const fn new(origin: Origin, column_major: bool, serpentine: bool) -> Self {
+        Self {
+            origin,
+            column_major,
+            serpentine,
+        }
+    }
+
+    /// Strip index of the LED at `(x, y)` on a `width` × `height`
+    /// panel, `None` outside the panel.
+    pub const fn index(&self, x: usize, y: usize, width: usize, height: usize) -> Option<usize> {
+        if x >= width || y >= height {
+            return None;
+        }
+        let (x, y) = self.flip(x, y, width, height);
+        let (line, along, line_len) = if self.column_major {
+            (x, y, height)
+        } else {
+            (y, x, width)
+        };
+        let along = if self.serpentine && line % 2 == 1 {
+            line_len - 1 - along
+        } else {
+            along
+        };
+        Some(line * line_len + along)
+    }
+
+    /// Coordinates of the LED at strip `index` on a `width` × `height`
+    /// panel, the inverse of [Layout::index].
+    pub const fn position(
+        &self,
+        index: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)> {
+        if index >= width * height {
+            return None;
+        }
+        let line_len = if self.column_major { height } else { width };
+        let line = index / line_len;
+        let along = index % line_len;
+        let along = if self.serpentine && line % 2 == 1 {
+            line_len - 1 - along
+        } else {
+            along
+        };
+        let (x, y) = if self.column_major {
+            (line, along)
+        } else {
+            (along, line)
+        };
+        Some(self.flip(x, y, width, height))
+    }
+
+    /// Move the origin to the top left and back, flipping is its own inverse.
+    const fn flip(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        let x = match self.origin {
+            Origin::TopRight | Origin::BottomRight => width - 1 - x,
+            Origin::TopLeft | Origin::BottomLeft => x,
+        };
+        let y = match self.origin {
+            Origin::BottomLeft | Origin::BottomRight => height - 1 - y,
+            Origin::TopLeft | Origin::TopRight => y,
+        };
+        (x, y)
+    }
+}
+
+impl Mapping for Layout {
+    fn index(&self, x: usize, y: usize, width: usize, height: usize) -> Option<usize> {
+        Layout::index(self, x, y, width, height)
+    }
+
+    fn position(&self, index: usize, width: usize, height: usize) -> Option<(usize, usize)> {
+        Layout::position(self, index, width, height)
+    }
+}
+
+/// Clockwise rotation of a mounted panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// Panel of a tiled display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Tile {
+    /// Column of the top left LED of the mounted panel in the frame.
+    pub x: usize,
+    /// Row of the top left LED of the mounted panel in the frame.
+    pub y: usize,
+    /// Columns of the panel before rotation.
+    pub width: usize,
+    /// Rows of the panel before rotation.
+    pub height: usize,
+    /// Wiring of the panel before rotation.
+    pub layout: Layout,
+    /// Rotation of the mounted panel.
+    pub rotation: Rotation,
+    /// Whether the mounted panel is mirrored left to right, after rotation.
+    pub flip_x: bool,
+    /// Whether the mounted panel is mirrored top to bottom, after rotation.
+    pub flip_y: bool,
+}
+
+impl Tile {
+    /// Upright `width` × `height` panel with its top left LED at `(x, y)`.
+    pub const fn new(x: usize, y: usize, width: usize, height: usize, layout: Layout) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            layout,
+            rotation: Rotation::None,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+
+    /// Panel mounted rotated clockwise by `rotation`.
+    pub const fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Panel mounted mirrored, applied after the rotation.
+    pub const fn flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Number of LEDs of the panel.
+    pub const fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Whether the panel has no LEDs.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Columns and rows of the mounted panel.
+    const fn mounted(&self) -> (usize, usize) {
+        match self.rotation {
+            Rotation::None | Rotation::Cw180 => (self.width, self.height),
+            Rotation::Cw90 | Rotation::Cw270 => (self.height, self.width),
+        }
+    }
+
+    /// Strip index within the panel of the LED at `(x, y)` of the
+    /// frame, `None` outside the panel.
+    const fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let (mounted_width, mounted_height) = self.mounted();
+        if x < self.x || y < self.y || x - self.x >= mounted_width || y - self.y >= mounted_height {
+            return None;
+        }
+        let (u, v) = self.unflip(x - self.x, y - self.y);
+        let (w, h) = (self.width, self.height);
+        let (x, y) = match self.rotation {
+            Rotation::None => (u, v),
+            Rotation::Cw90 => (v, h - 1 - u),
+            Rotation::Cw180 => (w - 1 - u, h - 1 - v),
+            Rotation::Cw270 => (w - 1 - v, u),
+        };
+        self.layout.index(x, y, w, h)
+    }
+
+    /// Coordinates in the frame of the LED at strip `index`
+    /// within the panel, the inverse of [Tile::index].
+    const fn position(&self, index: usize) -> Option<(usize, usize)> {
+        let (w, h) = (self.width, self.height);
+        let Some((x, y)) = self.layout.position(index, w, h) else {
+            return None;
+        };
+        let (u, v) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (h - 1 - y, x),
+            Rotation::Cw180 => (w - 1 - x, h - 1 - y),
+            Rotation::Cw270 => (y, w - 1 - x),
+        };
+        let (u, v) = self.unflip(u, v);
+        Some((self.x + u, self.y + v))
+    }
+
+    /// Apply or undo the flips of the mounted panel.
+    const fn unflip(&self, u: usize, v: usize) -> (usize, usize) {
+        let (mounted_width, mounted_height) = self.mounted();
+        let u = if self.flip_x {
+            mounted_width - 1 - u
+        } else {
+            u
+        };
+        let v = if self.flip_y {
+            mounted_height - 1 - v
+        } else {
+            v
+        };
+        (u, v)
+    }
+}
+
+/// Panels chained into one frame, in the order of the data line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tiles<const TILES: usize> {
+    tiles: [Tile; TILES],
+}
+
+impl<const TILES: usize> Tiles<TILES> {
+    /// Chain `tiles`, the first panel is connected to the driver.
+    pub const fn new(tiles: [Tile; TILES]) -> Self {
+        Self { tiles }
+    }
+
+    /// Panels of the display.
+    pub fn tiles(&self) -> &[Tile; TILES] {
+        &self.tiles
+    }
+}
+
+impl<const TILES: usize> Mapping for Tiles<TILES> {
+    fn index(&self, x: usize, y: usize, _width: usize, _height: usize) -> Option<usize> {
+        let mut offset = 0;
+        for tile in &self.tiles {
+            if let Some(index) = tile.index(x, y) {
+                return Some(offset + index);
+            }
+            offset += tile.len();
+        }
+        None
+    }
+
+    fn position(&self, index: usize, _width: usize, _height: usize) -> Option<(usize, usize)> {
+        let mut index = index;
+        for tile in &self.tiles {
+            if index < tile.len() {
+                return tile.position(index);
+            }
+            index -= tile.len();
+        }
+        None
+    }
+}
+
+/// Frame of a `W` × `H` display addressed by coordinates,
+/// wired as described by the [Mapping] `M`.
+#[derive(Debug, Clone)]
+pub struct Matrix<const W: usize, const H: usize, M = Layout> {
+    pixels: [[RGB8; W]; H],
+    mapping: M,
+}
+
+impl<const W: usize, const H: usize, M: Mapping> Matrix<W, H, M> {
+    /// Black frame for a display wired as `mapping`.
+    pub const fn new(mapping: M) -> Self {
+        Self {
+            pixels: [[RGB8 { r: 0, g: 0, b: 0 }; W]; H],
+            mapping,
+        }
+    }
+
+    /// Wiring of the display.
+    pub fn mapping(&self) -> &M {
+        &self.mapping
+    }
+
+    /// Strip index of the LED at `(x, y)`, `None` when there is no LED.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        self.mapping.index(x, y, W, H)
+    }
+
+    /// Color at `(x, y)`, `None` outside the panel.
+    pub fn get(&self, x: usize, y: usize) -> Option<RGB8> {
+        self.pixels.get(y)?.get(x).copied()
+    }
+
+    /// Set the color at `(x, y)`, coordinates outside the panel are ignored.
+    pub fn set(&mut self, x: usize, y: usize, color: RGB8) {
+        if let Some(pixel) = self.pixels.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *pixel = color;
+        }
+    }
+
+    /// Set every LED to `color`.
+    pub fn fill(&mut self, color: RGB8) {
+        self.pixels = [[color; W]; H];
+    }
+
+    /// Turn every LED off.
+    pub fn clear(&mut self) {
+        self.fill(RGB8::default());
+    }
+
+    /// Rows of the frame, top to bottom.
+    pub fn pixels_mut(&mut self) -> &mut [[RGB8; W]; H] {
+        &mut self.pixels
+    }
+
+    /// Colors of the frame in strip order, LEDs of panels
+    /// reaching past the frame are turned off.
+    pub fn iter(&self) -> impl Iterator<Item = RGB8> + '_ {
+        (0..).map_while(|index| {
+            let (x, y) = self.mapping.position(index, W, H)?;
+            Some(self.get(x, y).unwrap_or_default())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINS: [Origin; 4] = [
+        Origin::TopLeft,
+        Origin::TopRight,
+        Origin::BottomLeft,
+        Origin::BottomRight,
+    ];
+
+    #[test]
+    fn position_inverts_index() {
+        for origin in ORIGINS {
+            for column_major in [false, true] {
+                for serpentine in [false, true] {
+                    let layout = Layout::new(origin, column_major, serpentine);
+                    for (width, height) in [(1, 1), (3, 2), (4, 5)] {
+                        for index in 0..width * height {
+                            let (x, y) = layout.position(index, width, height).unwrap();
+                            assert_eq!(layout.index(x, y, width, height), Some(index));
+                        }
+                        assert_eq!(layout.position(width * height, width, height), None);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn serpentine_reverses_odd_rows() {
+        let layout = Layout::SERPENTINE;
+        assert_eq!(layout.index(3, 0, 4, 3), Some(3));
+        assert_eq!(layout.index(3, 1, 4, 3), Some(4));
+        assert_eq!(layout.index(0, 1, 4, 3), Some(7));
+        assert_eq!(layout.index(0, 2, 4, 3), Some(8));
+    }
+
+    #[test]
+    fn bottom_right_columns() {
+        let layout = Layout::new(Origin::BottomRight, true, false);
+        assert_eq!(layout.index(3, 2, 4, 3), Some(0));
+        assert_eq!(layout.index(3, 0, 4, 3), Some(2));
+        assert_eq!(layout.index(2, 2, 4, 3), Some(3));
+    }
+
+    #[test]
+    fn outside_panel() {
+        assert_eq!(Layout::ROWS.index(4, 0, 4, 3), None);
+        assert_eq!(Layout::ROWS.index(0, 3, 4, 3), None);
+    }
+}
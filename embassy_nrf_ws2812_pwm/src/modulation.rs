@@ -0,0 +1,183 @@
+//! Modulate effect parameters from sensor readings.
+//!
+//! A [Modulation] binds a sensor value (lux, microphone level,
+//! temperature etc.) to an effect parameter by smoothing the
+//! reading and mapping it through a [Curve] onto an output range.
+
+use libm::{logf, roundf};
+
+/// Effect parameter driven by a modulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Overall brightness.
+    Brightness,
+    /// Animation speed.
+    Speed,
+    /// Position within a palette.
+    Palette,
+}
+
+/// Shape of the mapping from the input range to the output range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// Output follows the input linearly.
+    Linear,
+    /// Output follows the logarithm of the input which
+    /// matches how we perceive light and sound levels.
+    Logarithmic,
+    /// Linear interpolation between `(input, output)` breakpoints
+    /// sorted by input where the output is normalized to `0..=1`.
+    Breakpoints(&'static [(f32, f32)]),
+}
+
+impl Curve {
+    /// Normalize a value already clamped to `min..=max` into `0..=1`.
+    fn normalize(&self, value: f32, min: f32, max: f32) -> f32 {
+        match self {
+            Curve::Linear => (value - min) / (max - min),
+            Curve::Logarithmic => {
+                // +1 to avoid log(0)
+                let ln_min = logf(min + 1.0);
+                let ln_max = logf(max + 1.0);
+                (logf(value + 1.0) - ln_min) / (ln_max - ln_min)
+            }
+            Curve::Breakpoints(points) => {
+                let Some((&first, rest)) = points.split_first() else {
+                    return 0.0;
+                };
+                let (mut x0, mut y0) = first;
+                if value <= x0 {
+                    return y0;
+                }
+                for &(x1, y1) in rest {
+                    if value <= x1 {
+                        let t = (value - x0) / (x1 - x0);
+                        return y0 + t * (y1 - y0);
+                    }
+                    (x0, y0) = (x1, y1);
+                }
+                y0
+            }
+        }
+    }
+}
+
+/// Binds a sensor value to an effect parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modulation {
+    /// Parameter being modulated.
+    pub target: Target,
+    /// Mapping curve.
+    pub curve: Curve,
+    /// Input value mapped to `min_out`.
+    pub min_in: f32,
+    /// Input value mapped to `max_out`.
+    pub max_in: f32,
+    /// Output for the minimum input.
+    pub min_out: u8,
+    /// Output for the maximum input, may be less than
+    /// `min_out` to invert the mapping.
+    pub max_out: u8,
+    /// Exponential smoothing factor in `0..=1`, `1` disables smoothing.
+    pub alpha: f32,
+    smoothed: Option<f32>,
+}
+
+impl Modulation {
+    /// Create a modulation without smoothing.
+    ///
+    /// # Panics
+    ///
+    /// If `min_in` is greater than `max_in` or either is NaN.
+    pub const fn new(
+        target: Target,
+        curve: Curve,
+        min_in: f32,
+        max_in: f32,
+        min_out: u8,
+        max_out: u8,
+    ) -> Self {
+        assert!(min_in <= max_in, "input range must not be reversed");
+        Self {
+            target,
+            curve,
+            min_in,
+            max_in,
+            min_out,
+            max_out,
+            alpha: 1.0,
+            smoothed: None,
+        }
+    }
+
+    /// Smooth readings with an exponential moving average.
+    pub const fn with_smoothing(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Feed a sensor reading and get the mapped output.
+    pub fn update(&mut self, value: f32) -> u8 {
+        let previous = self.smoothed.unwrap_or(value);
+        let smoothed = previous + self.alpha * (value - previous);
+        self.smoothed = Some(smoothed);
+        self.map(smoothed)
+    }
+
+    /// Map a value to the output range without smoothing.
+    pub fn map(&self, value: f32) -> u8 {
+        // Not `clamp`, which panics if the public range was reversed later
+        let clamped = value.max(self.min_in).min(self.max_in);
+        let norm = self.curve.normalize(clamped, self.min_in, self.max_in);
+        let out_range = self.max_out as f32 - self.min_out as f32;
+        let out = self.min_out as f32 + norm * out_range;
+        roundf(out).clamp(u8::MIN as f32, u8::MAX as f32) as u8
+    }
+}
+
+/// Turns lights on only when it is dark, for example to gate a
+/// scheduled on time for window or holiday lighting so it waits
+/// for dusk.
+///
+/// It becomes dark once a reading drops below `on_below` and light
+/// again once a reading rises above `off_above`, the gap between
+/// them stops the lights flickering while the light level settles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DarknessGate {
+    /// Reading below which it is dark.
+    pub on_below: f32,
+    /// Reading above which it is light again.
+    pub off_above: f32,
+    dark: bool,
+}
+
+impl DarknessGate {
+    /// Create a gate which starts out light.
+    pub const fn new(on_below: f32, off_above: f32) -> Self {
+        Self {
+            on_below,
+            off_above,
+            dark: false,
+        }
+    }
+
+    /// Feed a sensor reading, returns whether it is dark.
+    pub fn update(&mut self, value: f32) -> bool {
+        if value < self.on_below {
+            self.dark = true;
+        } else if value > self.off_above {
+            self.dark = false;
+        }
+        self.dark
+    }
+
+    /// Whether lights scheduled to be on should be on for a reading.
+    pub fn gate(&mut self, scheduled: bool, value: f32) -> bool {
+        self.update(value) && scheduled
+    }
+
+    /// Whether the last reading was dark.
+    pub fn is_dark(&self) -> bool {
+        self.dark
+    }
+}
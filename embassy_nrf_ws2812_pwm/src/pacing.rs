@@ -0,0 +1,308 @@
+//! Frame pacing with a policy for missed deadlines.
+//!
+//! A [FramePacer] starts a frame every period. When rendering, encoding
+//! and transmitting a frame takes longer than the period the next
+//! deadline has already passed and the [DeadlinePolicy] decides how to
+//! catch up, so an overloaded system degrades in a predictable way
+//! instead of drifting or stuttering. Missed deadlines are counted in
+//! [DeadlineStats].
+
+use crate::clock::{Clock, Instant};
+use crate::diagnostics::DeadlineStats;
+use embassy_time::Duration;
+use smart_leds::RGB8;
+
+/// On time frames before a degraded pacer tries to recover.
+const RECOVER_AFTER: u8 = 32;
+
+/// What to do when a frame missed its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadlinePolicy {
+    /// Start the next frame right away, the animation
+    /// falls behind by the time the frame overran.
+    Drift,
+    /// Skip the frame slots that have passed and keep the frame rate,
+    /// the animation stays on time but shows fewer frames.
+    #[default]
+    Skip,
+    /// Double the frame period, up to `max_period`, and go back
+    /// towards the configured period once frames are on time again.
+    ReduceRate {
+        /// Longest frame period.
+        max_period: Duration,
+    },
+    /// Render at [Resolution::Half] until frames are on time again.
+    HalfResolution,
+}
+
+/// Resolution to render a frame at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// Every LED.
+    #[default]
+    Full,
+    /// Every other LED, render the first half of the
+    /// frame and spread it with [Resolution::expand].
+    Half,
+}
+
+impl Resolution {
+    /// Number of colors to render for a frame of `len` LEDs.
+    pub fn len(self, len: usize) -> usize {
+        match self {
+            Resolution::Full => len,
+            Resolution::Half => len.div_ceil(2),
+        }
+    }
+
+    /// Spread colors rendered into the first [Resolution::len] LEDs
+    /// of `frame` across the whole frame, doubling every color.
+    ///
+    /// For a matrix render each row at half resolution
+    /// and expand the rows one at a time.
+    pub fn expand(self, frame: &mut [RGB8]) {
+        if self == Resolution::Half {
+            for i in (0..frame.len()).rev() {
+                frame[i] = frame[i / 2];
+            }
+        }
+    }
+}
+
+/// Starts frames at a fixed period.
+///
+/// ```ignore
+/// let mut pacer = FramePacer::new(SystemClock, Duration::from_hz(60), DeadlinePolicy::Skip);
+/// loop {
+///     let resolution = pacer.wait().await;
+///     render(&mut frame[..resolution.len(LEDS)]);
+///     resolution.expand(&mut frame);
+///     ws.write(frame.iter().copied()).await?;
+/// }
+/// ```
+pub struct FramePacer<C> {
+    clock: C,
+    target: Duration,
+    period: Duration,
+    policy: DeadlinePolicy,
+    resolution: Resolution,
+    next: Option<Instant>,
+    on_time: u8,
+    stats: DeadlineStats,
+}
+
+impl<C: Clock> FramePacer<C> {
+    /// Pacer starting a frame every `period`.
+    pub const fn new(clock: C, period: Duration, policy: DeadlinePolicy) -> Self {
+        Self {
+            clock,
+            target: period,
+            period,
+            policy,
+            resolution: Resolution::Full,
+            next: None,
+            on_time: 0,
+            stats: DeadlineStats {
+                frames: 0,
+                missed: 0,
+                skipped: 0,
+                slowed: 0,
+                half_resolution: 0,
+            },
+        }
+    }
+
+    /// Wait for the start of the next frame, returns
+    /// the resolution to render the frame at.
+    pub async fn wait(&mut self) -> Resolution {
+        let now = self.clock.now();
+        let deadline = self.next.unwrap_or(now);
+        let start = if now > deadline {
+            self.missed(now, deadline)
+        } else {
+            self.recover();
+            deadline
+        };
+        self.clock.sleep_until(start).await;
+
+        self.next = Some(start + self.period);
+        self.stats.frames = self.stats.frames.wrapping_add(1);
+        if self.resolution == Resolution::Half {
+            self.stats.half_resolution = self.stats.half_resolution.wrapping_add(1);
+        }
+        self.resolution
+    }
+
+    /// Apply the policy to a frame that missed `deadline`,
+    /// returns when to start the next frame.
+    fn missed(&mut self, now: Instant, deadline: Instant) -> Instant {
+        self.stats.missed = self.stats.missed.wrapping_add(1);
+        self.on_time = 0;
+        match self.policy {
+            DeadlinePolicy::Drift => now,
+            DeadlinePolicy::Skip => {
+                let late = now.saturating_duration_since(deadline).as_ticks();
+                let slots = late.div_ceil(self.period.as_ticks().max(1));
+                self.stats.skipped = self.stats.skipped.wrapping_add(slots as u32);
+                deadline + Duration::from_ticks(slots * self.period.as_ticks())
+            }
+            DeadlinePolicy::ReduceRate { max_period } => {
+                if self.period < max_period {
+                    self.period = (self.period * 2).min(max_period);
+                    self.stats.slowed = self.stats.slowed.wrapping_add(1);
+                }
+                now
+            }
+            DeadlinePolicy::HalfResolution => {
+                self.resolution = Resolution::Half;
+                now
+            }
+        }
+    }
+
+    /// Step back towards the configured rate and resolution
+    /// after enough frames were on time.
+    fn recover(&mut self) {
+        if self.period == self.target && self.resolution == Resolution::Full {
+            return;
+        }
+        self.on_time = self.on_time.saturating_add(1);
+        if self.on_time >= RECOVER_AFTER {
+            self.on_time = 0;
+            self.period = (self.period / 2).max(self.target);
+            self.resolution = Resolution::Full;
+        }
+    }
+
+    /// Frame period currently used.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Deadline counters.
+    pub fn stats(&self) -> DeadlineStats {
+        self.stats
+    }
+
+    /// Reset the counters.
+    pub fn reset_stats(&mut self) {
+        self.stats = DeadlineStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    /// Start a frame at `millis`, the mocked clock never sleeps.
+    fn frame_at(pacer: &mut FramePacer<impl Clock>, now: &Cell<u64>, millis: u64) -> Resolution {
+        now.set(millis * 1000);
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(pacer.wait()).poll(&mut cx) {
+            Poll::Ready(resolution) => resolution,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    /// Start frames on time until the pacer recovered.
+    fn recover(pacer: &mut FramePacer<impl Clock>, now: &Cell<u64>) {
+        for _ in 0..RECOVER_AFTER {
+            let next = pacer.next.unwrap().as_micros() / 1000;
+            frame_at(pacer, now, next);
+        }
+    }
+
+    #[test]
+    fn on_time_frames() {
+        let now = Cell::new(0);
+        let mut pacer = FramePacer::new(
+            || Instant::from_micros(now.get()),
+            PERIOD,
+            DeadlinePolicy::Skip,
+        );
+        for millis in [0, 10, 15, 30] {
+            assert_eq!(frame_at(&mut pacer, &now, millis), Resolution::Full);
+        }
+        assert_eq!(pacer.stats().frames, 4);
+        assert_eq!(pacer.stats().missed, 0);
+    }
+
+    #[test]
+    fn drift_starts_late_frame_now() {
+        let now = Cell::new(0);
+        let mut pacer = FramePacer::new(
+            || Instant::from_micros(now.get()),
+            PERIOD,
+            DeadlinePolicy::Drift,
+        );
+        frame_at(&mut pacer, &now, 0);
+        frame_at(&mut pacer, &now, 35);
+        assert_eq!(pacer.next, Some(Instant::from_millis(45)));
+        assert_eq!(pacer.stats().missed, 1);
+        assert_eq!(pacer.stats().skipped, 0);
+    }
+
+    #[test]
+    fn skip_keeps_frame_slots() {
+        let now = Cell::new(0);
+        let mut pacer = FramePacer::new(
+            || Instant::from_micros(now.get()),
+            PERIOD,
+            DeadlinePolicy::Skip,
+        );
+        frame_at(&mut pacer, &now, 0);
+        // 25 ms past the 10 ms deadline, the frame waits for the 40 ms slot
+        frame_at(&mut pacer, &now, 35);
+        assert_eq!(pacer.next, Some(Instant::from_millis(50)));
+        assert_eq!(pacer.stats().missed, 1);
+        assert_eq!(pacer.stats().skipped, 3);
+    }
+
+    #[test]
+    fn reduce_rate_doubles_period() {
+        let now = Cell::new(0);
+        let policy = DeadlinePolicy::ReduceRate {
+            max_period: Duration::from_millis(30),
+        };
+        let mut pacer = FramePacer::new(|| Instant::from_micros(now.get()), PERIOD, policy);
+        frame_at(&mut pacer, &now, 0);
+        frame_at(&mut pacer, &now, 100);
+        assert_eq!(pacer.period(), Duration::from_millis(20));
+        frame_at(&mut pacer, &now, 200);
+        assert_eq!(pacer.period(), Duration::from_millis(30));
+        frame_at(&mut pacer, &now, 300);
+        assert_eq!(pacer.period(), Duration::from_millis(30));
+        assert_eq!(pacer.stats().slowed, 2);
+
+        recover(&mut pacer, &now);
+        assert_eq!(pacer.period(), Duration::from_millis(15));
+        recover(&mut pacer, &now);
+        assert_eq!(pacer.period(), PERIOD);
+    }
+
+    #[test]
+    fn half_resolution_until_on_time() {
+        let now = Cell::new(0);
+        let policy = DeadlinePolicy::HalfResolution;
+        let mut pacer = FramePacer::new(|| Instant::from_micros(now.get()), PERIOD, policy);
+        assert_eq!(frame_at(&mut pacer, &now, 0), Resolution::Full);
+        assert_eq!(frame_at(&mut pacer, &now, 100), Resolution::Half);
+
+        recover(&mut pacer, &now);
+        assert_eq!(pacer.resolution, Resolution::Full);
+        assert_eq!(pacer.stats().half_resolution, RECOVER_AFTER as u32);
+    }
+
+    #[test]
+    fn expand_doubles_colors() {
+        let mut frame = [1, 2, 3, 0, 0].map(|value| RGB8::new(value, 0, 0));
+        assert_eq!(Resolution::Half.len(frame.len()), 3);
+        Resolution::Half.expand(&mut frame);
+        assert_eq!(frame.map(|color| color.r), [1, 1, 2, 2, 3]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Gradient palettes in the WLED (and FastLED) byte format.
+//!
+//! A gradient palette is a list of `[position, r, g, b]` entries sorted
+//! by position, usually starting at 0 and ending at 255. Colors between
+//! entries are linearly interpolated.
+
+use smart_leds::RGB8;
+
+/// Maximum number of entries in a gradient palette.
+pub const MAX_STOPS: usize = 16;
+
+/// Error parsing a gradient palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PaletteError {
+    /// Empty or not a multiple of 4 bytes.
+    Length,
+    /// More than [MAX_STOPS] entries.
+    TooManyStops,
+    /// Entries are not sorted by position.
+    Order,
+}
+
+/// Gradient palette with up to [MAX_STOPS] entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GradientPalette {
+    stops: [(u8, RGB8); MAX_STOPS],
+    len: usize,
+}
+
+impl GradientPalette {
+    /// Parse a WLED gradient palette byte array.
+    pub fn from_wled(bytes: &[u8]) -> Result<Self, PaletteError> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+            return Err(PaletteError::Length);
+        }
+        let len = bytes.len() / 4;
+        if len > MAX_STOPS {
+            return Err(PaletteError::TooManyStops);
+        }
+
+        let mut stops = [(0, RGB8::default()); MAX_STOPS];
+        for (stop, entry) in stops.iter_mut().zip(bytes.chunks_exact(4)) {
+            *stop = (entry[0], RGB8::new(entry[1], entry[2], entry[3]));
+        }
+        if stops[..len].windows(2).any(|w| w[0].0 > w[1].0) {
+            return Err(PaletteError::Order);
+        }
+        Ok(Self { stops, len })
+    }
+
+    /// Entries of the palette.
+    pub fn stops(&self) -> &[(u8, RGB8)] {
+        &self.stops[..self.len]
+    }
+
+    /// Color at a position in the palette.
+    pub fn color_at(&self, position: u8) -> RGB8 {
+        let stops = self.stops();
+        let (mut pos0, mut color0) = stops[0];
+        if position <= pos0 {
+            return color0;
+        }
+        for &(pos1, color1) in &stops[1..] {
+            if position <= pos1 {
+                let t = (position - pos0) as u16 * 255 / (pos1 - pos0) as u16;
+                return RGB8::new(
+                    lerp(color0.r, color1.r, t),
+                    lerp(color0.g, color1.g, t),
+                    lerp(color0.b, color1.b, t),
+                );
+            }
+            (pos0, color0) = (pos1, color1);
+        }
+        color0
+    }
+
+    /// Spread the palette across `out` starting at `offset`.
+    pub fn fill(&self, out: &mut [RGB8], offset: u8) {
+        let len = out.len().max(1);
+        for (i, color) in out.iter_mut().enumerate() {
+            let position = offset.wrapping_add((i * 256 / len) as u8);
+            *color = self.color_at(position);
+        }
+    }
+}
+
+/// Interpolate between `a` and `b` where `t` is in `0..=255`.
+fn lerp(a: u8, b: u8, t: u16) -> u8 {
+    let (a, b, t) = (a as i32, b as i32, t as i32);
+    (a + (b - a) * t / 255) as u8
+}
@@ -0,0 +1,43 @@
+//! Show panics on the strip.
+//!
+//! Installed devices rarely have a debugger attached, so a panic
+//! handler can take over the strip and blink SOS in a distinct color
+//! using the blocking write path, which works without an executor.
+
+use crate::Ws2812;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// Length of a morse dot in milliseconds.
+const DOT_MS: u32 = 200;
+
+/// SOS in morse as (on, off) durations in dots.
+const SOS: [(u32, u32); 9] = [
+    (1, 1),
+    (1, 1),
+    (1, 3),
+    (3, 1),
+    (3, 1),
+    (3, 3),
+    (1, 1),
+    (1, 1),
+    (1, 7),
+];
+
+/// Blink SOS on every LED of the strip forever.
+///
+/// Panic handlers usually run with interrupts disabled so
+/// `delay_ms` should busy wait, for example with `cortex_m::asm::delay`.
+pub fn sos<const LEDS: usize>(
+    ws: &mut Ws2812<'_, LEDS>,
+    color: RGB8,
+    mut delay_ms: impl FnMut(u32),
+) -> ! {
+    loop {
+        for (on, off) in SOS {
+            let _ = SmartLedsWrite::write(ws, core::iter::repeat_n(color, LEDS));
+            delay_ms(on * DOT_MS);
+            let _ = SmartLedsWrite::write(ws, core::iter::repeat_n(RGB8::default(), LEDS));
+            delay_ms(off * DOT_MS);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Synchronized refresh of several strips.
+//!
+//! Video walls built from strips on separate PWM instances tear when
+//! the strips are written one after the other. [sync_write] encodes the
+//! frames of every strip first, then starts all sequences back to back
+//! and waits for all of them, so the strips latch their frames within
+//! a few microseconds of each other.
+
+use crate::Ws2812;
+use crate::error::Error;
+use crate::instance::Event;
+#[cfg(feature = "async")]
+use core::{
+    future::poll_fn,
+    task::{Context, Poll},
+};
+use smart_leds::RGB8;
+
+/// Strip taking part in a synchronized refresh.
+pub trait Strip {
+    /// Encode a frame without sending it.
+    fn prepare(&mut self, frame: &[RGB8]) -> Result<(), Error>;
+
+    /// Start sending the prepared frame without waiting for it.
+    fn start(&mut self) -> Result<(), Error>;
+
+    /// Whether the frame started last has been sent.
+    fn is_done(&self) -> bool;
+
+    /// Whether the frame started last has been sent, otherwise
+    /// wake `cx` once it has.
+    #[cfg(feature = "async")]
+    fn poll_done(&mut self, cx: &mut Context<'_>) -> Poll<()>;
+
+    /// Block until the frame started last has been sent.
+    fn wait_blocking(&mut self) {
+        while !self.is_done() {}
+    }
+}
+
+impl<const LEDS: usize> Strip for Ws2812<'_, LEDS> {
+    fn prepare(&mut self, frame: &[RGB8]) -> Result<(), Error> {
+        self.wait_in_flight_blocking();
+        self.write_buffer(frame.iter().copied())
+    }
+
+    fn start(&mut self) -> Result<(), Error> {
+        self.wait_in_flight_blocking();
+        Ok(self.start_in_flight()?)
+    }
+
+    fn is_done(&self) -> bool {
+        !self.in_flight || self.events.is_triggered(Event::LoopsDone)
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_done(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.in_flight {
+            core::task::ready!(self.events.poll(Event::LoopsDone, cx));
+            self.in_flight = false;
+        }
+        Poll::Ready(())
+    }
+
+    fn wait_blocking(&mut self) {
+        self.wait_in_flight_blocking();
+    }
+}
+
+/// Waits for every started strip when dropped, so the sequences never
+/// outlive the call even if the future is dropped before completing.
+struct Started<'a, 's> {
+    strips: &'a mut [&'s mut dyn Strip],
+}
+
+impl Drop for Started<'_, '_> {
+    fn drop(&mut self) {
+        for strip in self.strips.iter_mut() {
+            strip.wait_blocking();
+        }
+    }
+}
+
+/// Start every strip with the prepared frames.
+fn start<'a, 's>(
+    strips: &'a mut [&'s mut dyn Strip],
+    frames: &[&[RGB8]],
+) -> Result<Started<'a, 's>, Error> {
+    for (strip, frame) in strips.iter_mut().zip(frames) {
+        strip.prepare(frame)?;
+    }
+    let started = Started { strips };
+    for strip in started.strips.iter_mut().take(frames.len()) {
+        strip.start()?;
+    }
+    Ok(started)
+}
+
+/// Send `frames[i]` to `strips[i]`, starting all strips
+/// back to back and waiting until every frame has been sent.
+///
+/// Strips without a frame and frames without a strip are skipped.
+/// The task sleeps until the PWM interrupts of the strips report the
+/// end of their frames.
+///
+/// The frames are encoded before the first strip is started. When
+/// a strip fails to start the error is returned once the strips
+/// started before it have finished.
+/// Drivers in [hold_last_frame](crate::builder::Ws2812Builder::hold_last_frame)
+/// mode send the frame once instead of looping it.
+#[cfg(feature = "async")]
+pub async fn sync_write(strips: &mut [&mut dyn Strip], frames: &[&[RGB8]]) -> Result<(), Error> {
+    let started = start(strips, frames)?;
+    poll_fn(|cx| {
+        // Poll every strip so each one registers the waker
+        let mut done = true;
+        for strip in started.strips.iter_mut() {
+            done &= strip.poll_done(cx).is_ready();
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    })
+    .await;
+    Ok(())
+}
+
+/// Blocking version of [sync_write].
+pub fn sync_write_blocking(strips: &mut [&mut dyn Strip], frames: &[&[RGB8]]) -> Result<(), Error> {
+    // Dropping waits for every strip
+    start(strips, frames).map(drop)
+}
@@ -0,0 +1,67 @@
+//! Sharing a PWM instance with other drivers.
+//!
+//! [Ws2812Parts] owns the PWM instance, pin and sample buffer of a
+//! strip and lends them to a [Ws2812] driver for as long as it is
+//! needed. Once the driver is dropped the PWM instance can drive a
+//! buzzer or servo, and a new driver picks up the strip afterwards.
+//!
+//! ```ignore
+//! let mut parts = Ws2812Parts::from_parts(p.PWM0, Irqs, p.P0_13, buf);
+//! {
+//!     let mut ws = parts.driver(Ws2812Builder::new())?;
+//!     ws.write(frame.iter().copied()).await?;
+//! }
+//! let buzzer = SimplePwm::new_1ch(parts.pwm(), p.P0_15, &Default::default());
+//! ```
+
+use crate::builder::Ws2812Builder;
+use crate::error::Error;
+use crate::{Buffer, Instance, InterruptHandler, Samples, Ws2812};
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+
+/// PWM instance, pin and sample buffer of a strip.
+pub struct Ws2812Parts<'d, P: Instance, S: Pin, const LEDS: usize> {
+    pwm: Peri<'d, P>,
+    pin: Peri<'d, S>,
+    buf: &'d mut Samples<LEDS>,
+}
+
+impl<'d, P: Instance, S: Pin, const LEDS: usize> Ws2812Parts<'d, P, S, LEDS> {
+    /// Keep the parts of a strip for drivers created later.
+    pub fn from_parts(
+        pwm: Peri<'d, P>,
+        _irq: impl Binding<P::Interrupt, InterruptHandler<P>> + 'd,
+        pin: Peri<'d, S>,
+        buf: &'d mut Samples<LEDS>,
+    ) -> Self {
+        Self { pwm, pin, buf }
+    }
+
+    /// Create a driver borrowing the parts, dropping
+    /// it stops the PWM and gives them back.
+    ///
+    /// The buffer keeps the last frame encoded by the previous driver.
+    pub fn driver(&mut self, builder: Ws2812Builder) -> Result<Ws2812<'_, LEDS>, Error> {
+        let buf = Buffer::Borrowed(&mut *self.buf);
+        Ws2812::with_config(
+            self.pwm.reborrow(),
+            self.pin.reborrow(),
+            None,
+            buf,
+            None,
+            builder,
+        )
+    }
+
+    /// Lend the PWM instance to another driver.
+    pub fn pwm(&mut self) -> Peri<'_, P> {
+        self.pwm.reborrow()
+    }
+
+    /// Give back the PWM instance, pin and buffer.
+    pub fn release(self) -> (Peri<'d, P>, Peri<'d, S>, &'d mut Samples<LEDS>) {
+        (self.pwm, self.pin, self.buf)
+    }
+}
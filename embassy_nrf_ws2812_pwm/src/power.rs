@@ -0,0 +1,106 @@
+//! Estimate the current drawn by the strip.
+//!
+//! A [PowerModel] holds the current of one LED per color channel at
+//! full duty. Long strips get power injected at several points, a
+//! [LoadMeter] splits a frame into the segments fed by each injection
+//! point so installers can check the load is spread across the feeds.
+//!
+//! The drivers estimate the current of a frame before sending it with
+//! [Ws2812::estimate_current_ma](crate::Ws2812::estimate_current_ma),
+//! meter the frame they sent per feed with
+//! [Ws2812::estimate_feed_current_ma](crate::Ws2812::estimate_feed_current_ma),
+//! set the model of the LEDs with
+//! [Ws2812Builder::power_model](crate::builder::Ws2812Builder::power_model)
+//! and scale frames down to a budget with
+//! [Ws2812Builder::max_current_ma](crate::builder::Ws2812Builder::max_current_ma).
+
+use smart_leds::RGB8;
+
+/// Current of one LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerModel {
+    /// Red channel at full duty in mA.
+    pub red: u16,
+    /// Green channel at full duty in mA.
+    pub green: u16,
+    /// Blue channel at full duty in mA.
+    pub blue: u16,
+    /// LED turned off in mA.
+    pub idle: u16,
+}
+
+impl PowerModel {
+    /// Typical WS2812B at 5 V.
+    pub const WS2812B: Self = Self::new(16, 11, 15, 1);
+
+    /// Model with the current of each channel at full duty, measure
+    /// a few LEDs of the strip for chips without a preset.
+    pub const fn new(red: u16, green: u16, blue: u16, idle: u16) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            idle,
+        }
+    }
+
+    /// Current of a frame in mA.
+    pub fn current_ma(&self, frame: impl IntoIterator<Item = RGB8>) -> u32 {
+        let scaled: u32 = frame.into_iter().map(|color| self.scaled(color)).sum();
+        scaled / 255
+    }
+
+    /// Current of one LED in mA × 255, dividing once per
+    /// frame keeps the rounding error of dim LEDs small.
+    fn scaled(&self, color: RGB8) -> u32 {
+        color.r as u32 * self.red as u32
+            + color.g as u32 * self.green as u32
+            + color.b as u32 * self.blue as u32
+            + 255 * self.idle as u32
+    }
+}
+
+/// Current per power feed of a frame.
+///
+/// Feed `i` powers the LEDs up to `ends[i]`, starting after the LEDs of
+/// the previous feed. LEDs after the last end are counted on the last
+/// feed. Feed the colors of a frame in order with [LoadMeter::update]
+/// and read the result with [LoadMeter::finish].
+#[derive(Debug, Clone)]
+pub struct LoadMeter<const FEEDS: usize> {
+    model: PowerModel,
+    ends: [usize; FEEDS],
+    scaled: [u32; FEEDS],
+    index: usize,
+    feed: usize,
+}
+
+impl<const FEEDS: usize> LoadMeter<FEEDS> {
+    /// Meter for feeds ending at `ends`, in ascending order.
+    pub const fn new(model: PowerModel, ends: [usize; FEEDS]) -> Self {
+        Self {
+            model,
+            ends,
+            scaled: [0; FEEDS],
+            index: 0,
+            feed: 0,
+        }
+    }
+
+    /// Add the next LED of the frame.
+    pub fn update(&mut self, color: RGB8) {
+        while self.feed + 1 < FEEDS && self.index >= self.ends[self.feed] {
+            self.feed += 1;
+        }
+        if let Some(scaled) = self.scaled.get_mut(self.feed) {
+            *scaled += self.model.scaled(color);
+        }
+        self.index += 1;
+    }
+
+    /// Current of each feed in mA.
+    pub fn finish(&self) -> [u32; FEEDS] {
+        self.scaled.map(|scaled| scaled / 255)
+    }
+}
@@ -0,0 +1,96 @@
+//! Interrupt executors splitting LED work by priority.
+//!
+//! In a BLE application the radio must win, but a frame whose end
+//! is handled late holds the PWM and delays the next one. Encoding
+//! (effects, palettes, the driver's gamma and current limit) can run on
+//! a low priority [PriorityExecutor] that the BLE host preempts, while
+//! the task owning the driver, which only starts frames and wakes on
+//! the PWM interrupt at their end, runs on a higher one. The two sides
+//! exchange frames over a [FrameChannel](crate::runner::FrameChannel)
+//! or a [Mailbox](crate::mailbox::Mailbox), which are safe to use
+//! across priorities.
+//!
+//! [priority_executor!](crate::priority_executor) declares an executor
+//! and the handler of the software interrupt it runs on:
+//!
+//! ```ignore
+//! priority_executor!(ENCODE, EGU2_SWI2);
+//! priority_executor!(TRANSMIT, EGU1_SWI1);
+//! static FRAMES: FrameChannel<NUM_LEDS, 2> = FrameChannel::new();
+//!
+//! // The MPSL reserves priorities 0, 1 and 4
+//! let transmit = TRANSMIT.start(interrupt::EGU1_SWI1, Priority::P5);
+//! let encode = ENCODE.start(interrupt::EGU2_SWI2, Priority::P7);
+//! transmit.must_spawn(ws2812_task(FRAMES.runner(ws)));
+//! encode.must_spawn(effect_task(FRAMES.handle()));
+//! ```
+
+use embassy_executor::{InterruptExecutor, SendSpawner};
+use embassy_nrf::interrupt::{Interrupt, InterruptExt, Priority};
+
+/// Executor running its tasks from a software interrupt.
+pub struct PriorityExecutor {
+    executor: InterruptExecutor,
+}
+
+impl Default for PriorityExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriorityExecutor {
+    /// Create an executor, declare it with
+    /// [priority_executor!](crate::priority_executor).
+    pub const fn new() -> Self {
+        Self {
+            executor: InterruptExecutor::new(),
+        }
+    }
+
+    /// Run the executor on `irq` at `priority` and get its spawner.
+    ///
+    /// `irq` must be the interrupt given to
+    /// [priority_executor!](crate::priority_executor) and otherwise
+    /// unused, for example a software interrupt the SoftDevice
+    /// Controller doesn't take.
+    ///
+    /// # Panics
+    ///
+    /// If the executor was already started.
+    pub fn start(&'static self, irq: Interrupt, priority: Priority) -> SendSpawner {
+        irq.set_priority(priority);
+        self.executor.start(irq)
+    }
+
+    /// Run the tasks that are ready, called by the interrupt handler.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from the interrupt the executor was started on.
+    #[doc(hidden)]
+    pub unsafe fn on_interrupt(&'static self) {
+        unsafe { self.executor.on_interrupt() }
+    }
+}
+
+/// Declare a static [PriorityExecutor] and the handler of
+/// the interrupt it runs on.
+///
+/// ```ignore
+/// priority_executor!(LED_EXECUTOR, EGU1_SWI1);
+/// let spawner = LED_EXECUTOR.start(interrupt::EGU1_SWI1, Priority::P6);
+/// ```
+#[macro_export]
+macro_rules! priority_executor {
+    ($name:ident, $irq:ident) => {
+        static $name: $crate::priority::PriorityExecutor =
+            $crate::priority::PriorityExecutor::new();
+
+        #[allow(non_snake_case)]
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn $irq() {
+            unsafe { $name.on_interrupt() }
+        }
+    };
+}
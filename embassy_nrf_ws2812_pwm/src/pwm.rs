@@ -0,0 +1,501 @@
+//! Sequence PWM the drivers send their samples with.
+//!
+//! On most chips this is the `pwm` module of embassy-nrf. embassy-nrf
+//! 0.8 has no PWM driver for the nRF54L series, so there the module
+//! drives PWM20 to PWM22 through their registers with the same types,
+//! covering what the drivers use. The nRF54L PWM reads its sequences
+//! through separate EasyDMA registers counted in bytes, starts them
+//! with the DMA tasks and holds the idle level of each channel in
+//! `IDLEOUT` rather than in the GPIO output.
+
+#[cfg(not(feature = "_nrf54l"))]
+pub use embassy_nrf::pwm::{
+    Config, CounterMode, Error, Instance, PWM_CLK_HZ, Prescaler, Sequence, SequenceConfig,
+    SequenceLoad, SequenceMode, SequencePwm, Sequencer, SingleSequenceMode, SingleSequencer,
+    StartSequence,
+};
+#[cfg(feature = "_nrf54l")]
+pub use nrf54l::*;
+
+#[cfg(feature = "_nrf54l")]
+mod nrf54l {
+    use crate::dma::in_dma_ram;
+    use core::sync::atomic::{Ordering, compiler_fence};
+    use embassy_nrf::gpio::{Level, Output, OutputDrive, Pin};
+    use embassy_nrf::pac::pwm::vals;
+    use embassy_nrf::{Peri, PeripheralType, interrupt, pac};
+
+    /// Clock of the PWM with the `Div1` prescaler, the 16 MHz
+    /// peripheral clock of the PERI power domain.
+    pub const PWM_CLK_HZ: u32 = 16_000_000;
+
+    /// Longest sequence in samples, `MAXCNT` counts bytes.
+    const MAX_SEQUENCE_LEN: usize = u16::MAX as usize / 2;
+
+    /// PWM error.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[non_exhaustive]
+    pub enum Error {
+        /// Sequences hold at most 32767 samples.
+        SequenceTooLong,
+        /// Sequences play at least once.
+        SequenceTimesAtLeastOne,
+        /// EasyDMA only reads data RAM.
+        BufferNotInRAM,
+    }
+
+    mod sealed {
+        use embassy_nrf::pac;
+
+        pub trait Sealed {
+            fn regs() -> pac::pwm::Pwm;
+        }
+    }
+
+    /// PWM peripheral instance.
+    pub trait Instance: sealed::Sealed + PeripheralType + 'static {
+        /// Interrupt of the instance.
+        type Interrupt: interrupt::typelevel::Interrupt;
+    }
+
+    /// PWM peripherals, embassy-nrf 0.8 doesn't hand them out
+    /// with the other peripherals of the nRF54L series.
+    pub mod peripherals {
+        use embassy_nrf::{Peri, PeripheralType};
+
+        macro_rules! peripheral {
+            ($type:ident) => {
+                #[doc = concat!(stringify!($type), " peripheral")]
+                #[derive(Debug, Clone, Copy)]
+                pub struct $type {
+                    _private: (),
+                }
+
+                impl $type {
+                    /// Take the peripheral.
+                    ///
+                    /// # Safety
+                    ///
+                    /// Only one instance may be in use at a time.
+                    pub unsafe fn steal() -> Peri<'static, Self> {
+                        // SAFETY: the caller owns the peripheral
+                        unsafe { Peri::new_unchecked(Self { _private: () }) }
+                    }
+                }
+
+                impl PeripheralType for $type {}
+            };
+        }
+
+        peripheral!(PWM20);
+        peripheral!(PWM21);
+        peripheral!(PWM22);
+    }
+
+    macro_rules! impl_pwm {
+        ($type:ident) => {
+            impl sealed::Sealed for peripherals::$type {
+                fn regs() -> pac::pwm::Pwm {
+                    pac::$type
+                }
+            }
+            impl Instance for peripherals::$type {
+                type Interrupt = interrupt::typelevel::$type;
+            }
+        };
+    }
+
+    impl_pwm!(PWM20);
+    impl_pwm!(PWM21);
+    impl_pwm!(PWM22);
+
+    /// PWM playing sequences of samples from RAM on up to four channels.
+    ///
+    /// PWM20 to PWM22 sit in the PERI power domain and can only drive
+    /// pins of that domain, see the pin assignments of the datasheet.
+    pub struct SequencePwm<'d> {
+        r: pac::pwm::Pwm,
+        outputs: [Option<Output<'d>>; 4],
+    }
+
+    impl<'d> SequencePwm<'d> {
+        /// Create a 1-channel PWM.
+        pub fn new_1ch<T: Instance>(
+            pwm: Peri<'d, T>,
+            ch0: Peri<'d, impl Pin>,
+            config: Config,
+        ) -> Result<Self, Error> {
+            let ch0 = Self::channel(ch0, config.ch0_drive);
+            Ok(Self::new_inner(pwm, [Some(ch0), None, None, None], &config))
+        }
+
+        /// Create a 2-channel PWM.
+        pub fn new_2ch<T: Instance>(
+            pwm: Peri<'d, T>,
+            ch0: Peri<'d, impl Pin>,
+            ch1: Peri<'d, impl Pin>,
+            config: Config,
+        ) -> Result<Self, Error> {
+            let ch0 = Self::channel(ch0, config.ch0_drive);
+            let ch1 = Self::channel(ch1, config.ch1_drive);
+            Ok(Self::new_inner(
+                pwm,
+                [Some(ch0), Some(ch1), None, None],
+                &config,
+            ))
+        }
+
+        /// Create a 4-channel PWM.
+        pub fn new_4ch<T: Instance>(
+            pwm: Peri<'d, T>,
+            ch0: Peri<'d, impl Pin>,
+            ch1: Peri<'d, impl Pin>,
+            ch2: Peri<'d, impl Pin>,
+            ch3: Peri<'d, impl Pin>,
+            config: Config,
+        ) -> Result<Self, Error> {
+            let channels = [
+                Some(Self::channel(ch0, config.ch0_drive)),
+                Some(Self::channel(ch1, config.ch1_drive)),
+                Some(Self::channel(ch2, config.ch2_drive)),
+                Some(Self::channel(ch3, config.ch3_drive)),
+            ];
+            Ok(Self::new_inner(pwm, channels, &config))
+        }
+
+        /// Low output and the `PSEL` value of a channel pin.
+        fn channel(
+            pin: Peri<'d, impl Pin>,
+            drive: OutputDrive,
+        ) -> (Output<'d>, pac::shared::regs::Psel) {
+            let psel = pin.psel_bits();
+            (Output::new(pin, Level::Low, drive), psel)
+        }
+
+        fn new_inner<T: Instance>(
+            _pwm: Peri<'d, T>,
+            channels: [Option<(Output<'d>, pac::shared::regs::Psel)>; 4],
+            config: &Config,
+        ) -> Self {
+            let r = T::regs();
+            let mut outputs = [None, None, None, None];
+            for (n, (channel, output)) in channels.into_iter().zip(&mut outputs).enumerate() {
+                if let Some((pin, psel)) = channel {
+                    r.psel().out(n).write_value(psel);
+                    *output = Some(pin);
+                }
+            }
+
+            r.intenclr().write(|w| w.0 = 0xFFFF_FFFF);
+            r.shorts().write(|_| ());
+            r.events_stopped().write_value(0);
+            r.events_loopsdone().write_value(0);
+            r.events_seqend(0).write_value(0);
+            r.events_seqend(1).write_value(0);
+
+            r.decoder().write(|w| {
+                w.set_load(vals::Load::from_bits(config.sequence_load as u8));
+                w.set_mode(vals::Mode::REFRESH_COUNT);
+            });
+            r.mode().write(|w| match config.counter_mode {
+                CounterMode::UpAndDown => w.set_updown(vals::Updown::UP_AND_DOWN),
+                CounterMode::Up => w.set_updown(vals::Updown::UP),
+            });
+            r.prescaler()
+                .write(|w| w.set_prescaler(vals::Prescaler::from_bits(config.prescaler as u8)));
+            r.countertop().write(|w| w.set_countertop(config.max_duty));
+
+            Self { r, outputs }
+        }
+
+        /// Hold each channel at the level of its GPIO output while
+        /// no sequence plays, like the PWM of the other chips.
+        fn set_idle_out(&self) {
+            let high = self
+                .outputs
+                .each_ref()
+                .map(|output| output.as_ref().is_some_and(|output| output.is_set_high()));
+            self.r.idleout().write(|w| {
+                w.set_val_0(high[0]);
+                w.set_val_1(high[1]);
+                w.set_val_2(high[2]);
+                w.set_val_3(high[3]);
+            });
+        }
+    }
+
+    impl Drop for SequencePwm<'_> {
+        fn drop(&mut self) {
+            self.r.enable().write(|w| w.set_enable(false));
+            for (n, output) in self.outputs.iter().enumerate() {
+                if output.is_some() {
+                    self.r
+                        .psel()
+                        .out(n)
+                        .write_value(pac::shared::regs::Psel(u32::MAX));
+                }
+            }
+        }
+    }
+
+    /// Configuration of the PWM.
+    #[non_exhaustive]
+    pub struct Config {
+        /// Up or up and down counter.
+        pub counter_mode: CounterMode,
+        /// Top value of the counter.
+        pub max_duty: u16,
+        /// Divider of [PWM_CLK_HZ].
+        pub prescaler: Prescaler,
+        /// How samples are spread across the channels.
+        pub sequence_load: SequenceLoad,
+        /// Drive strength of the channel 0 pin.
+        pub ch0_drive: OutputDrive,
+        /// Drive strength of the channel 1 pin.
+        pub ch1_drive: OutputDrive,
+        /// Drive strength of the channel 2 pin.
+        pub ch2_drive: OutputDrive,
+        /// Drive strength of the channel 3 pin.
+        pub ch3_drive: OutputDrive,
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Self {
+                counter_mode: CounterMode::Up,
+                max_duty: 1000,
+                prescaler: Prescaler::Div16,
+                sequence_load: SequenceLoad::Common,
+                ch0_drive: OutputDrive::Standard,
+                ch1_drive: OutputDrive::Standard,
+                ch2_drive: OutputDrive::Standard,
+                ch3_drive: OutputDrive::Standard,
+            }
+        }
+    }
+
+    /// Configuration of a sequence.
+    #[non_exhaustive]
+    #[derive(Clone, Default)]
+    pub struct SequenceConfig {
+        /// Extra PWM periods each sample is held for.
+        pub refresh: u32,
+        /// PWM periods after the sequence before the next one starts.
+        pub end_delay: u32,
+    }
+
+    /// Samples of a sequence and its configuration.
+    #[non_exhaustive]
+    pub struct Sequence<'s> {
+        /// Samples, at most 32767.
+        pub words: &'s [u16],
+        /// Configuration of the sequence.
+        pub config: SequenceConfig,
+    }
+
+    impl<'s> Sequence<'s> {
+        /// Create a sequence.
+        pub fn new(words: &'s [u16], config: SequenceConfig) -> Self {
+            Self { words, config }
+        }
+    }
+
+    /// A single sequence played a number of times.
+    pub struct SingleSequencer<'d, 's> {
+        sequencer: Sequencer<'d, 's>,
+    }
+
+    impl<'d, 's> SingleSequencer<'d, 's> {
+        /// Create a sequencer for one sequence.
+        pub fn new(pwm: &'s mut SequencePwm<'d>, words: &'s [u16], config: SequenceConfig) -> Self {
+            Self {
+                sequencer: Sequencer::new(pwm, Sequence::new(words, config), None),
+            }
+        }
+
+        /// Start or restart playback.
+        pub fn start(&self, times: SingleSequenceMode) -> Result<(), Error> {
+            let (start_seq, times) = match times {
+                SingleSequenceMode::Times(1) => (StartSequence::One, SequenceMode::Loop(1)),
+                SingleSequenceMode::Times(n) if n & 1 == 1 => {
+                    (StartSequence::One, SequenceMode::Loop(n / 2 + 1))
+                }
+                SingleSequenceMode::Times(n) => (StartSequence::Zero, SequenceMode::Loop(n / 2)),
+                SingleSequenceMode::Infinite => (StartSequence::Zero, SequenceMode::Infinite),
+            };
+            self.sequencer.start(start_seq, times)
+        }
+
+        /// Stop playback and disable the PWM.
+        pub fn stop(&self) {
+            self.sequencer.stop();
+        }
+    }
+
+    /// Two sequences played one after the other, stopped when dropped.
+    pub struct Sequencer<'d, 's> {
+        pwm: &'s mut SequencePwm<'d>,
+        sequence0: Sequence<'s>,
+        sequence1: Option<Sequence<'s>>,
+    }
+
+    impl<'d, 's> Sequencer<'d, 's> {
+        /// Create a sequencer, without `sequence1` the first
+        /// sequence is played twice per loop.
+        pub fn new(
+            pwm: &'s mut SequencePwm<'d>,
+            sequence0: Sequence<'s>,
+            sequence1: Option<Sequence<'s>>,
+        ) -> Self {
+            Self {
+                pwm,
+                sequence0,
+                sequence1,
+            }
+        }
+
+        /// Start or restart playback, `times` counts loops of both sequences.
+        pub fn start(&self, start_seq: StartSequence, times: SequenceMode) -> Result<(), Error> {
+            let sequences = [
+                &self.sequence0,
+                self.sequence1.as_ref().unwrap_or(&self.sequence0),
+            ];
+            for sequence in sequences {
+                if !in_dma_ram(sequence.words) {
+                    return Err(Error::BufferNotInRAM);
+                }
+                if sequence.words.len() > MAX_SEQUENCE_LEN {
+                    return Err(Error::SequenceTooLong);
+                }
+            }
+            if let SequenceMode::Loop(0) = times {
+                return Err(Error::SequenceTimesAtLeastOne);
+            }
+
+            self.stop();
+
+            let r = self.pwm.r;
+            for (n, sequence) in sequences.into_iter().enumerate() {
+                r.seq(n).refresh().write(|w| w.0 = sequence.config.refresh);
+                r.seq(n)
+                    .enddelay()
+                    .write(|w| w.0 = sequence.config.end_delay);
+                r.dma()
+                    .seq(n)
+                    .ptr()
+                    .write_value(sequence.words.as_ptr() as u32);
+                r.dma()
+                    .seq(n)
+                    .maxcnt()
+                    .write(|w| w.set_maxcnt(size_of_val(sequence.words) as u16));
+            }
+            self.pwm.set_idle_out();
+            r.enable().write(|w| w.set_enable(true));
+
+            compiler_fence(Ordering::SeqCst);
+
+            match times {
+                SequenceMode::Loop(n) => {
+                    r.loop_().write(|w| w.set_cnt(vals::LoopCnt::from_bits(n)));
+                }
+                // Play both once, then LOOPSDONE starts sequence 0 again
+                SequenceMode::Infinite => {
+                    r.loop_().write(|w| w.set_cnt(vals::LoopCnt::from_bits(1)));
+                    r.shorts().write(|w| w.set_loopsdone_dma_seq0_start(true));
+                }
+            }
+            let start = match start_seq {
+                StartSequence::Zero => 0,
+                StartSequence::One => 1,
+            };
+            r.tasks_dma().seq(start).start().write_value(1);
+            Ok(())
+        }
+
+        /// Stop playback and disable the PWM.
+        pub fn stop(&self) {
+            let r = self.pwm.r;
+            r.shorts().write(|_| ());
+            compiler_fence(Ordering::SeqCst);
+            r.tasks_stop().write_value(1);
+            r.enable().write(|w| w.set_enable(false));
+        }
+    }
+
+    impl Drop for Sequencer<'_, '_> {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    /// How many times to play a single sequence.
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum SingleSequenceMode {
+        /// Play the sequence n times.
+        Times(u16),
+        /// Repeat until stopped.
+        Infinite,
+    }
+
+    /// Sequence a loop starts with.
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum StartSequence {
+        /// Sequence 0.
+        Zero,
+        /// Sequence 1.
+        One,
+    }
+
+    /// How many loops of both sequences to play.
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum SequenceMode {
+        /// Play n loops.
+        Loop(u16),
+        /// Repeat until stopped.
+        Infinite,
+    }
+
+    /// Divider of [PWM_CLK_HZ].
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum Prescaler {
+        /// Divide by 1.
+        Div1,
+        /// Divide by 2.
+        Div2,
+        /// Divide by 4.
+        Div4,
+        /// Divide by 8.
+        Div8,
+        /// Divide by 16.
+        Div16,
+        /// Divide by 32.
+        Div32,
+        /// Divide by 64.
+        Div64,
+        /// Divide by 128.
+        Div128,
+    }
+
+    /// How samples are spread across the channels.
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum SequenceLoad {
+        /// Every sample goes to all channels.
+        Common,
+        /// One sample for channels 0 and 1, then one for 2 and 3.
+        Grouped,
+        /// One sample per channel.
+        Individual,
+        /// Three channels, the fourth sample is the counter top.
+        Waveform,
+    }
+
+    /// Counting direction of the PWM counter.
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub enum CounterMode {
+        /// Up counter, edge aligned duty cycles.
+        Up,
+        /// Up and down counter, center aligned duty cycles.
+        UpAndDown,
+    }
+}
@@ -0,0 +1,184 @@
+//! Four strips on one PWM instance.
+//!
+//! [Ws2812Quad] drives a strip from each of the four channels of a PWM
+//! instance. The sequence is loaded in individual mode, so every PWM
+//! period takes one sample per channel and the four strips are sent in
+//! parallel. Waveform mode would use channel 3 for the period and leave
+//! only three outputs. The strips share the timing, color order and
+//! polarity of a [Ws2812Builder], and a write to one strip sends the
+//! other strips their current frames again.
+
+use crate::builder::Ws2812Builder;
+use crate::dma;
+use crate::error::Error;
+use crate::gamma::gamma_table;
+use crate::instance::Events;
+#[cfg(feature = "async")]
+use crate::play;
+use crate::play_blocking;
+use crate::pwm::{Config, SequenceLoad, SequencePwm};
+use crate::{Encoder, Framing, IdlePin, Instance, InterruptHandler};
+use crate::{MAX_SAMPLES, buffer_len};
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::AnyPin;
+use embassy_nrf::interrupt::typelevel::Binding;
+use smart_leds::RGB8;
+
+/// Number of strips of a [Ws2812Quad].
+pub const STRIPS: usize = 4;
+
+/// Sample buffer of a [Ws2812Quad] driving strips of `LEDS` LEDs, the
+/// samples of the four channels for each of the 24 periods of an LED.
+///
+/// It takes as much RAM as the buffers of four single drivers.
+pub type QuadSamples<const LEDS: usize> = [[[u16; STRIPS]; 24]; LEDS];
+
+/// Four WS2812 strips of up to `LEDS` LEDs on the channels of one PWM instance.
+pub struct Ws2812Quad<'d, const LEDS: usize> {
+    pwm: SequencePwm<'d>,
+    events: Events,
+    buf: &'d mut QuadSamples<LEDS>,
+    framing: Framing,
+    encoder: Encoder,
+}
+
+impl<'d, const LEDS: usize> Ws2812Quad<'d, LEDS> {
+    /// Create a driver for strips on `pins` using the default [Timing](crate::timing::Timing) (WS2812B).
+    ///
+    /// Pins of different ports are passed as [AnyPin], for
+    /// example `[p.P0_13.into(), p.P0_14.into(), ...]`.
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pins: [Peri<'d, AnyPin>; STRIPS],
+        buf: &'d mut QuadSamples<LEDS>,
+    ) -> Result<Self, Error> {
+        Self::with_config(pwm, irq, pins, buf, Ws2812Builder::new())
+    }
+
+    /// Create a driver configured by `builder`.
+    ///
+    /// The tail, overflow, shadow and buffering options of the builder
+    /// don't apply, each write sends a whole frame to every strip.
+    pub fn with_config<T: Instance>(
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pins: [Peri<'d, AnyPin>; STRIPS],
+        buf: &'d mut QuadSamples<LEDS>,
+        builder: Ws2812Builder,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(
+                buffer_len(LEDS) * STRIPS <= MAX_SAMPLES,
+                "buffer longer than a PWM sequence"
+            )
+        };
+
+        let timing = builder.timing;
+        if !timing.is_valid_with(builder.prescaler) {
+            return Err(Error::InvalidConfig);
+        }
+
+        dma::check(buf.as_flattened().as_flattened())?;
+
+        let mut config = Config::default();
+        config.sequence_load = SequenceLoad::Individual;
+        config.prescaler = builder.prescaler;
+        config.max_duty = timing.period_ticks(builder.prescaler);
+        config.ch0_drive = builder.drive;
+        config.ch1_drive = builder.drive;
+        config.ch2_drive = builder.drive;
+        config.ch3_drive = builder.drive;
+
+        let idle = pins.each_ref().map(|pin| IdlePin::of(&**pin));
+        let [pin0, pin1, pin2, pin3] = pins;
+        let pwm = SequencePwm::new_4ch(pwm, pin0, pin1, pin2, pin3, config)?;
+        for pin in idle {
+            pin.set(builder.idle());
+        }
+        let mut encoder = Encoder::new(
+            &timing,
+            builder.prescaler,
+            builder.color_order,
+            builder.inverted,
+        );
+        encoder.gamma = (builder.gamma != 1.0).then(|| gamma_table(builder.gamma));
+        encoder.brightness = builder.brightness;
+        encoder.correction = builder.correction;
+
+        let framing = Framing::new(
+            &timing,
+            builder.prescaler,
+            builder.refresh,
+            &encoder,
+            STRIPS,
+        );
+        let mut quad = Self {
+            pwm,
+            events: Events::new::<T>(),
+            buf,
+            framing,
+            encoder,
+        };
+        for strip in 0..STRIPS {
+            quad.set(strip, core::iter::empty::<RGB8>());
+        }
+        Ok(quad)
+    }
+
+    /// Encode a frame for a strip without sending it, LEDs
+    /// past the end of the colors are turned off.
+    ///
+    /// # Panics
+    ///
+    /// If `strip` is not below [STRIPS].
+    pub fn set<T, I>(&mut self, strip: usize, iterator: T)
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        assert!(strip < STRIPS, "strip out of range");
+        let mut colors = iterator.into_iter().map(Into::into);
+        let mut samples = [0; 24];
+        for periods in self.buf.iter_mut() {
+            let color = colors.next().unwrap_or_default();
+            self.encoder.encode_pixel(&mut samples, color);
+            for (period, sample) in periods.iter_mut().zip(samples) {
+                period[strip] = sample;
+            }
+        }
+    }
+
+    /// Encode a frame for a strip and send the frames of all strips.
+    ///
+    /// # Panics
+    ///
+    /// If `strip` is not below [STRIPS].
+    #[cfg(feature = "async")]
+    pub async fn write<T, I>(&mut self, strip: usize, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        self.set(strip, iterator);
+        self.flush().await
+    }
+
+    /// Send the frames of all strips.
+    #[cfg(feature = "async")]
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let words = self.buf.as_flattened().as_flattened();
+        Ok(play(&mut self.pwm, &self.events, words, &self.framing).await?)
+    }
+
+    /// Send the frames of all strips, blocking until they have been sent.
+    pub fn flush_blocking(&mut self) -> Result<(), Error> {
+        let words = self.buf.as_flattened().as_flattened();
+        Ok(play_blocking(
+            &mut self.pwm,
+            &self.events,
+            words,
+            &self.framing,
+        )?)
+    }
+}
@@ -0,0 +1,220 @@
+//! Bounded frame queue for realtime streaming.
+//!
+//! Bursty senders push frames into a small ring of slots. When the ring
+//! is full the oldest frame is dropped so the displayed frame is never
+//! more than `SLOTS` frames behind, and statistics record how often that
+//! happened and how deep the queue got.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::waitqueue::AtomicWaker;
+use smart_leds::RGB8;
+
+/// Queue statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueStats {
+    /// Frames pushed into the queue.
+    pub received: u32,
+    /// Frames dropped because the queue was full.
+    pub dropped: u32,
+    /// Frames currently queued.
+    pub depth: usize,
+    /// Largest number of frames queued at once.
+    pub max_depth: usize,
+}
+
+struct State<const LEDS: usize, const SLOTS: usize> {
+    frames: [[RGB8; LEDS]; SLOTS],
+    head: usize,
+    stats: QueueStats,
+}
+
+/// Ring of `SLOTS` frames of `LEDS` colors with drop oldest semantics.
+///
+/// Frames are copied in and out inside a critical section
+/// so keep frames and the number of slots small.
+pub struct FrameQueue<const LEDS: usize, const SLOTS: usize> {
+    state: Mutex<CriticalSectionRawMutex, RefCell<State<LEDS, SLOTS>>>,
+    waker: AtomicWaker,
+}
+
+impl<const LEDS: usize, const SLOTS: usize> Default for FrameQueue<LEDS, SLOTS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LEDS: usize, const SLOTS: usize> FrameQueue<LEDS, SLOTS> {
+    /// Create an empty queue.
+    pub const fn new() -> Self {
+        const { assert!(SLOTS > 0, "queue needs at least one slot") };
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                frames: [[RGB8::new(0, 0, 0); LEDS]; SLOTS],
+                head: 0,
+                stats: QueueStats {
+                    received: 0,
+                    dropped: 0,
+                    depth: 0,
+                    max_depth: 0,
+                },
+            })),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Push a frame, dropping the oldest queued frame if the queue is full.
+    ///
+    /// Colors beyond `LEDS` are ignored and missing colors are black.
+    /// Returns `true` when a frame was dropped.
+    pub fn push(&self, frame: &[RGB8]) -> bool {
+        let dropped = self.state.lock(|state| {
+            let state = &mut *state.borrow_mut();
+            let dropped = state.stats.depth == SLOTS;
+            if dropped {
+                state.head = (state.head + 1) % SLOTS;
+                state.stats.depth -= 1;
+                state.stats.dropped = state.stats.dropped.wrapping_add(1);
+            }
+
+            let tail = (state.head + state.stats.depth) % SLOTS;
+            let slot = &mut state.frames[tail];
+            let len = frame.len().min(LEDS);
+            slot[..len].copy_from_slice(&frame[..len]);
+            slot[len..].fill(RGB8::default());
+
+            state.stats.depth += 1;
+            state.stats.max_depth = state.stats.max_depth.max(state.stats.depth);
+            state.stats.received = state.stats.received.wrapping_add(1);
+            dropped
+        });
+        self.waker.wake();
+        dropped
+    }
+
+    /// Copy the oldest queued frame into `out`, returns `false` if empty.
+    pub fn try_pop(&self, out: &mut [RGB8; LEDS]) -> bool {
+        self.state.lock(|state| {
+            let state = &mut *state.borrow_mut();
+            if state.stats.depth == 0 {
+                return false;
+            }
+            *out = state.frames[state.head];
+            state.head = (state.head + 1) % SLOTS;
+            state.stats.depth -= 1;
+            true
+        })
+    }
+
+    /// Wait for a frame and copy it into `out`.
+    pub async fn pop(&self, out: &mut [RGB8; LEDS]) {
+        poll_fn(|cx| {
+            self.waker.register(cx.waker());
+            if self.try_pop(out) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Current statistics.
+    pub fn stats(&self) -> QueueStats {
+        self.state.lock(|state| state.borrow().stats)
+    }
+
+    /// Reset the counters keeping the current depth.
+    pub fn reset_stats(&self) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            let depth = state.stats.depth;
+            state.stats = QueueStats {
+                depth,
+                max_depth: depth,
+                ..QueueStats::default()
+            };
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // Links the std critical section implementation
+    use critical_section as _;
+
+    fn frame(value: u8) -> [RGB8; 2] {
+        [RGB8::new(value, 0, 0); 2]
+    }
+
+    #[test]
+    fn pops_in_order() {
+        let queue = FrameQueue::<2, 3>::new();
+        let mut out = [RGB8::default(); 2];
+        assert!(!queue.try_pop(&mut out));
+
+        assert!(!queue.push(&frame(1)));
+        assert!(!queue.push(&frame(2)));
+        assert!(queue.try_pop(&mut out));
+        assert_eq!(out, frame(1));
+        assert!(queue.try_pop(&mut out));
+        assert_eq!(out, frame(2));
+        assert!(!queue.try_pop(&mut out));
+    }
+
+    #[test]
+    fn pads_and_truncates_frames() {
+        let queue = FrameQueue::<2, 2>::new();
+        let mut out = [RGB8::default(); 2];
+        queue.push(&[RGB8::new(1, 2, 3)]);
+        queue.push(&[RGB8::new(4, 5, 6); 3]);
+        queue.try_pop(&mut out);
+        assert_eq!(out, [RGB8::new(1, 2, 3), RGB8::default()]);
+        queue.try_pop(&mut out);
+        assert_eq!(out, [RGB8::new(4, 5, 6); 2]);
+    }
+
+    #[test]
+    fn drops_oldest_when_full() {
+        let queue = FrameQueue::<2, 2>::new();
+        let mut out = [RGB8::default(); 2];
+        queue.push(&frame(1));
+        queue.push(&frame(2));
+        assert!(queue.push(&frame(3)));
+        assert_eq!(
+            queue.stats(),
+            QueueStats {
+                received: 3,
+                dropped: 1,
+                depth: 2,
+                max_depth: 2,
+            }
+        );
+
+        queue.try_pop(&mut out);
+        assert_eq!(out, frame(2));
+        queue.try_pop(&mut out);
+        assert_eq!(out, frame(3));
+    }
+
+    #[test]
+    fn reset_keeps_depth() {
+        let queue = FrameQueue::<2, 4>::new();
+        let mut out = [RGB8::default(); 2];
+        (0..3).for_each(|value| _ = queue.push(&frame(value)));
+        queue.try_pop(&mut out);
+        queue.reset_stats();
+        assert_eq!(
+            queue.stats(),
+            QueueStats {
+                depth: 2,
+                max_depth: 2,
+                ..QueueStats::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Record animations to flash and play them back.
+//!
+//! A recording is a small header followed by frames of RGB bytes:
+//!
+//! | Offset | Size | Field                       |
+//! |--------|------|-----------------------------|
+//! | 0      | 4    | Magic `WSRC`                |
+//! | 4      | 2    | Number of LEDs (LE)         |
+//! | 6      | 2    | Frame duration in ms (LE)   |
+//! | 8      | 4    | Number of frames (LE)       |
+//! | 12     | ...  | Frames, 3 bytes per LED     |
+//!
+//! Host tools can generate the same format. Internal flash and QSPI
+//! flash in XIP mode are memory mapped so a [Recording] can be played
+//! directly from a slice of flash, or from `include_bytes!`.
+
+use embassy_time::Duration;
+use embedded_storage::nor_flash::NorFlash;
+use smart_leds::RGB8;
+
+/// Magic bytes at the start of a recording.
+pub const MAGIC: [u8; 4] = *b"WSRC";
+
+/// Size of the recording header.
+pub const HEADER_LEN: usize = 12;
+
+/// Error reading a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecordingError {
+    /// Missing or wrong magic bytes.
+    Magic,
+    /// Data is shorter than the header says.
+    Length,
+}
+
+/// Header of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingHeader {
+    /// Number of LEDs in each frame.
+    pub leds: usize,
+    /// Time each frame is shown for.
+    pub frame_duration: Duration,
+    /// Number of frames.
+    pub frames: usize,
+}
+
+impl RecordingHeader {
+    /// Parse a recording header.
+    pub fn parse(header: &[u8; HEADER_LEN]) -> Result<Self, RecordingError> {
+        if header[..4] != MAGIC {
+            return Err(RecordingError::Magic);
+        }
+        let frame_ms = u16::from_le_bytes([header[6], header[7]]);
+        Ok(Self {
+            leds: u16::from_le_bytes([header[4], header[5]]) as usize,
+            frame_duration: Duration::from_millis(frame_ms as u64),
+            frames: u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize,
+        })
+    }
+
+    /// Size of a frame in bytes.
+    pub fn frame_len(&self) -> usize {
+        self.leds * 3
+    }
+}
+
+/// Recording played from a byte slice.
+#[derive(Debug, Clone, Copy)]
+pub struct Recording<'a> {
+    leds: usize,
+    frame_duration: Duration,
+    frames: &'a [u8],
+}
+
+impl<'a> Recording<'a> {
+    /// Parse a recording.
+    pub fn parse(data: &'a [u8]) -> Result<Self, RecordingError> {
+        let (header, data) = data
+            .split_first_chunk::<HEADER_LEN>()
+            .ok_or(RecordingError::Magic)?;
+        let header = RecordingHeader::parse(header)?;
+        let frames = header
+            .frame_len()
+            .checked_mul(header.frames)
+            .and_then(|len| data.get(..len))
+            .ok_or(RecordingError::Length)?;
+        Ok(Self {
+            leds: header.leds,
+            frame_duration: header.frame_duration,
+            frames,
+        })
+    }
+
+    /// Number of LEDs in each frame.
+    pub fn leds(&self) -> usize {
+        self.leds
+    }
+
+    /// Time each frame is shown for.
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    /// Number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len().checked_div(self.leds * 3).unwrap_or(0)
+    }
+
+    /// Whether the recording has no frames.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Colors of a frame.
+    pub fn frame(&self, index: usize) -> Option<impl Iterator<Item = RGB8> + 'a> {
+        let len = self.leds * 3;
+        let bytes = self.frames.get(index * len..(index + 1) * len)?;
+        Some(
+            bytes
+                .chunks_exact(3)
+                .map(|rgb| RGB8::new(rgb[0], rgb[1], rgb[2])),
+        )
+    }
+}
+
+/// Writes a recording to flash.
+///
+/// The region is erased when the recorder is created and the header
+/// is written last by [Recorder::finish] so an interrupted recording
+/// doesn't parse.
+pub struct Recorder<'f, F: NorFlash> {
+    flash: &'f mut F,
+    offset: u32,
+    capacity: u32,
+    leds: u16,
+    frame_ms: u16,
+    frames: u32,
+    written: u32,
+    pending: [u8; 4],
+    pending_len: usize,
+}
+
+impl<'f, F: NorFlash> Recorder<'f, F> {
+    /// Erase `capacity` bytes at `offset` and start a recording.
+    ///
+    /// `offset` and `capacity` must be aligned to the erase size of the flash.
+    pub fn new(
+        flash: &'f mut F,
+        offset: u32,
+        capacity: u32,
+        leds: u16,
+        frame_duration: Duration,
+    ) -> Result<Self, F::Error> {
+        const { assert!(F::WRITE_SIZE <= 4 && 4 % F::WRITE_SIZE == 0) };
+        flash.erase(offset, offset + capacity)?;
+        Ok(Self {
+            flash,
+            offset,
+            capacity,
+            leds,
+            frame_ms: frame_duration.as_millis().min(u16::MAX as u64) as u16,
+            frames: 0,
+            written: HEADER_LEN as u32,
+            pending: [0; 4],
+            pending_len: 0,
+        })
+    }
+
+    /// Append a frame, colors beyond the number of LEDs are ignored and
+    /// missing colors are black.
+    ///
+    /// Returns `Ok(false)` without writing when the region is full.
+    pub fn push(&mut self, frame: impl IntoIterator<Item = RGB8>) -> Result<bool, F::Error> {
+        let frame_len = self.leds as u32 * 3;
+        if self.written + self.pending_len as u32 + frame_len > self.capacity {
+            return Ok(false);
+        }
+        let mut colors = frame.into_iter();
+        for _ in 0..self.leds {
+            let color = colors.next().unwrap_or_default();
+            for byte in [color.r, color.g, color.b] {
+                self.pending[self.pending_len] = byte;
+                self.pending_len += 1;
+                if self.pending_len == self.pending.len() {
+                    self.flash
+                        .write(self.offset + self.written, &self.pending)?;
+                    self.written += self.pending.len() as u32;
+                    self.pending_len = 0;
+                }
+            }
+        }
+        self.frames += 1;
+        Ok(true)
+    }
+
+    /// Write any remaining bytes and the header, returns the number of frames.
+    pub fn finish(mut self) -> Result<u32, F::Error> {
+        if self.pending_len > 0 {
+            // Erased flash reads as 0xff so pad with it
+            self.pending[self.pending_len..].fill(0xff);
+            self.flash
+                .write(self.offset + self.written, &self.pending)?;
+        }
+
+        let mut header = [0; HEADER_LEN];
+        header[..4].copy_from_slice(&MAGIC);
+        header[4..6].copy_from_slice(&self.leds.to_le_bytes());
+        header[6..8].copy_from_slice(&self.frame_ms.to_le_bytes());
+        header[8..12].copy_from_slice(&self.frames.to_le_bytes());
+        self.flash.write(self.offset, &header)?;
+        Ok(self.frames)
+    }
+}
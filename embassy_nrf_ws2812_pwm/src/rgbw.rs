@@ -0,0 +1,108 @@
+//! SK6812 RGBW strips.
+//!
+//! Each LED takes 32 bits, the red, green and blue channels in the
+//! color order of the builder followed by the white channel. Strips
+//! mixing RGB and RGBW chips are driven through a
+//! [ChipMap](crate::chip_map::ChipMap) instead.
+
+use crate::builder::Ws2812Builder;
+use crate::error::Error;
+use crate::timing::Timing;
+use crate::{Buffer, Instance, InterruptHandler, MAX_SAMPLES, Ws2812};
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, RGBW, SmartLedsWrite};
+
+/// Sample buffer of a [Ws2812Rgbw] driving `LEDS` LEDs, 32 samples per LED.
+pub type RgbwSamples<const LEDS: usize> = [[u16; 32]; LEDS];
+
+/// RGBW driver for a strip of `LEDS` LEDs.
+pub struct Ws2812Rgbw<'d, const LEDS: usize> {
+    ws: Ws2812<'d, LEDS>,
+}
+
+impl<'d, const LEDS: usize> Ws2812Rgbw<'d, LEDS> {
+    /// Create a driver using the SK6812 timing.
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut RgbwSamples<LEDS>,
+    ) -> Result<Self, Error> {
+        let builder = Ws2812Builder::new().timing(Timing::SK6812);
+        Self::with_config(pwm, irq, pin, buf, builder)
+    }
+
+    /// Create a driver configured by `builder`.
+    ///
+    /// The shadow, current limit and buffering options
+    /// of the builder don't apply.
+    pub fn with_config<T: Instance>(
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut RgbwSamples<LEDS>,
+        builder: Ws2812Builder,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(
+                LEDS * 32 <= MAX_SAMPLES,
+                "buffer longer than a PWM sequence"
+            )
+        };
+        let buf = Buffer::Slice(buf.as_flattened_mut());
+        Ok(Self {
+            ws: Ws2812::with_config(pwm, pin, None, buf, None, builder)?,
+        })
+    }
+
+    /// Scale every color while encoding, applied from the next write.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.ws.set_brightness(brightness);
+    }
+
+    /// Encode a frame, LEDs past the end of the colors are turned off.
+    fn encode(&mut self, mut colors: impl Iterator<Item = RGBW<u8>>) {
+        let encoder = &self.ws.encoder;
+        for chunk in self.ws.buf.chunks_exact_mut(32) {
+            let color = colors.next().unwrap_or_default();
+            let (rgb, white) = chunk.split_at_mut(24);
+            encoder.encode_pixel(rgb, RGB8::new(color.r, color.g, color.b));
+            encoder.encode_byte(white, color.a.0);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Ws2812Rgbw<'_, LEDS> {
+    type Error = Error;
+    type Color = RGBW<u8>;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight().await;
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame().await?)
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Ws2812Rgbw<'_, LEDS> {
+    type Error = Error;
+    type Color = RGBW<u8>;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight_blocking();
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame_blocking()?)
+    }
+}
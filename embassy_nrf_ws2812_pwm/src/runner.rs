@@ -0,0 +1,156 @@
+//! Background writer owning the driver.
+//!
+//! A [Ws2812Runner] owns the [Ws2812] and writes the [Frame]s it
+//! receives over a [FrameChannel], while any task can push frames
+//! through a cheap copyable [Ws2812Handle]. Generic tasks aren't
+//! supported by embassy so the runner is run from a small task for
+//! the concrete sizes:
+//!
+//! ```ignore
+//! static FRAMES: FrameChannel<8, 2> = FrameChannel::new();
+//!
+//! #[embassy_executor::task]
+//! async fn ws2812_task(mut runner: Ws2812Runner<'static, 'static, 8, 2>) {
+//!     loop {
+//!         let error = runner.run().await;
+//!         defmt::warn!("LED write failed: {}", defmt::Display2Format(&error));
+//!     }
+//! }
+//!
+//! spawner.must_spawn(ws2812_task(FRAMES.runner(ws)));
+//! FRAMES.handle().send(Frame::Fill(colors::RED)).await;
+//! ```
+
+use crate::Ws2812;
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, Receiver, Sender, TrySendError};
+use smart_leds::{RGB8, SmartLedsWriteAsync};
+
+/// Message for a [Ws2812Runner].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame<const LEDS: usize> {
+    /// Write these colors.
+    Pixels([RGB8; LEDS]),
+    /// Set every LED to a color.
+    Fill(RGB8),
+    /// Change the brightness and resend the last frame.
+    Brightness(u8),
+    /// Change the color order and resend the last frame.
+    ColorOrder(ColorOrder),
+}
+
+/// Channel of up to `DEPTH` frames of `LEDS` colors.
+pub struct FrameChannel<const LEDS: usize, const DEPTH: usize> {
+    channel: Channel<CriticalSectionRawMutex, Frame<LEDS>, DEPTH>,
+}
+
+impl<const LEDS: usize, const DEPTH: usize> Default for FrameChannel<LEDS, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LEDS: usize, const DEPTH: usize> FrameChannel<LEDS, DEPTH> {
+    /// Create an empty channel.
+    pub const fn new() -> Self {
+        Self {
+            channel: Channel::new(),
+        }
+    }
+
+    /// Runner writing the frames of this channel to `ws`.
+    pub fn runner<'d>(&self, ws: Ws2812<'d, LEDS>) -> Ws2812Runner<'d, '_, LEDS, DEPTH> {
+        Ws2812Runner {
+            ws,
+            receiver: self.channel.receiver(),
+            last: Frame::Fill(RGB8::default()),
+        }
+    }
+
+    /// Handle to push frames into this channel.
+    pub fn handle(&self) -> Ws2812Handle<'_, LEDS, DEPTH> {
+        Ws2812Handle {
+            sender: self.channel.sender(),
+        }
+    }
+}
+
+/// Owns the driver and writes the frames received over a [FrameChannel].
+pub struct Ws2812Runner<'d, 'c, const LEDS: usize, const DEPTH: usize> {
+    ws: Ws2812<'d, LEDS>,
+    receiver: Receiver<'c, CriticalSectionRawMutex, Frame<LEDS>, DEPTH>,
+    last: Frame<LEDS>,
+}
+
+impl<'d, const LEDS: usize, const DEPTH: usize> Ws2812Runner<'d, '_, LEDS, DEPTH> {
+    /// Write frames as they arrive until a write fails.
+    ///
+    /// The runner can be run again after handling the error.
+    pub async fn run(&mut self) -> Error {
+        loop {
+            if let Err(e) = self.write_next().await {
+                return e;
+            }
+        }
+    }
+
+    /// Wait for the next frame and write it.
+    ///
+    /// Lets the caller act on every write, [Ws2812Runner::run]
+    /// only returns on errors.
+    pub async fn write_next(&mut self) -> Result<(), Error> {
+        match self.receiver.receive().await {
+            frame @ (Frame::Pixels(_) | Frame::Fill(_)) => self.last = frame,
+            Frame::Brightness(brightness) => self.ws.set_brightness(brightness),
+            Frame::ColorOrder(color_order) => self.ws.set_color_order(color_order),
+        }
+        match self.last {
+            Frame::Pixels(colors) => self.ws.write(colors).await,
+            Frame::Fill(color) => self.ws.fill(color).await,
+            Frame::Brightness(_) | Frame::ColorOrder(_) => Ok(()),
+        }
+    }
+
+    /// Colors of the last frame, before the brightness of the driver.
+    pub fn colors(&self) -> impl Iterator<Item = RGB8> + '_ {
+        let (pixels, fill) = match &self.last {
+            Frame::Pixels(colors) => (&colors[..], None),
+            Frame::Fill(color) => (&[][..], Some(*color)),
+            Frame::Brightness(_) | Frame::ColorOrder(_) => (&[][..], None),
+        };
+        let fill = core::iter::repeat_n(fill, LEDS).flatten();
+        pixels.iter().copied().chain(fill)
+    }
+
+    /// The driver, for example to change its settings.
+    pub fn ws(&mut self) -> &mut Ws2812<'d, LEDS> {
+        &mut self.ws
+    }
+
+    /// Release the driver.
+    pub fn release(self) -> Ws2812<'d, LEDS> {
+        self.ws
+    }
+}
+
+/// Cloneable handle pushing frames to a [Ws2812Runner].
+#[derive(Clone, Copy)]
+pub struct Ws2812Handle<'c, const LEDS: usize, const DEPTH: usize> {
+    sender: Sender<'c, CriticalSectionRawMutex, Frame<LEDS>, DEPTH>,
+}
+
+impl<const LEDS: usize, const DEPTH: usize> Ws2812Handle<'_, LEDS, DEPTH> {
+    /// Push a frame, waiting while the channel is full.
+    pub async fn send(&self, frame: Frame<LEDS>) {
+        self.sender.send(frame).await
+    }
+
+    /// Push a frame unless the channel is full.
+    pub fn try_send(&self, frame: Frame<LEDS>) -> Result<(), Frame<LEDS>> {
+        self.sender
+            .try_send(frame)
+            .map_err(|TrySendError::Full(frame)| frame)
+    }
+}
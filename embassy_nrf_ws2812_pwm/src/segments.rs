@@ -0,0 +1,266 @@
+//! Logical segments of one strip.
+//!
+//! [Segments] splits a strip into zones which are written, filled and
+//! dimmed on their own, like the segments of WLED. The colors of every
+//! segment are kept in a frame owned by [Segments] and composited when
+//! the frame is sent, so changing one zone doesn't need the colors of
+//! the others.
+//!
+//! Segments are addressed in logical order. A [reversed](Segment::reversed)
+//! segment starts at its last LED, for strips installed backwards, and a
+//! [mirrored](Segment::mirrored) one shows its colors on both halves from
+//! the ends towards the middle, for symmetric installations. The
+//! mapping is applied when the frame is sent.
+//!
+//! ```ignore
+//! let mut segments = Segments::new(ws, [Segment::new(0, 30), Segment::new(30, 30)]);
+//! segments.fill(0, colors::RED);
+//! segments.set(1, rainbow.iter().copied());
+//! segments.set_brightness(1, 64);
+//! segments.flush().await?;
+//! ```
+
+use crate::Ws2812;
+use crate::correction::Correction;
+use crate::error::Error;
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// Range of LEDs forming one segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// First LED of the segment.
+    pub start: usize,
+    /// Number of LEDs.
+    pub len: usize,
+    /// Brightness of the segment, from `0` (off) to `255` (full).
+    pub brightness: u8,
+    /// Whether the first logical LED is the last LED of the segment.
+    pub reversed: bool,
+    /// Whether both halves of the segment show the same colors.
+    pub mirrored: bool,
+}
+
+impl Segment {
+    /// Segment of `len` LEDs from `start` at full brightness.
+    pub const fn new(start: usize, len: usize) -> Self {
+        Self {
+            start,
+            len,
+            brightness: u8::MAX,
+            reversed: false,
+            mirrored: false,
+        }
+    }
+
+    /// Run the segment from its last LED to its first.
+    pub const fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// Show the colors on both halves, from the ends towards the
+    /// middle, or from the middle towards the ends when also
+    /// [reversed](Segment::reversed). The segment then takes half as
+    /// many colors, rounded up.
+    pub const fn mirrored(mut self, mirrored: bool) -> Self {
+        self.mirrored = mirrored;
+        self
+    }
+
+    /// Number of colors of the segment.
+    pub const fn logical_len(&self) -> usize {
+        if self.mirrored {
+            self.len.div_ceil(2)
+        } else {
+            self.len
+        }
+    }
+
+    /// LED after the last one of the segment.
+    const fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    /// Logical index shown by the LED `offset` LEDs into the segment.
+    const fn logical(&self, offset: usize) -> usize {
+        let from_end = self.len - 1 - offset;
+        let pos = if self.mirrored && from_end < offset {
+            from_end
+        } else {
+            offset
+        };
+        if self.reversed {
+            self.logical_len() - 1 - pos
+        } else {
+            pos
+        }
+    }
+}
+
+/// Strip split into `SEGMENTS` segments.
+pub struct Segments<'d, const LEDS: usize, const SEGMENTS: usize> {
+    ws: Ws2812<'d, LEDS>,
+    segments: [Segment; SEGMENTS],
+    colors: [RGB8; LEDS],
+}
+
+impl<'d, const LEDS: usize, const SEGMENTS: usize> Segments<'d, LEDS, SEGMENTS> {
+    /// Split the strip driven by `ws` into `segments`, LEDs
+    /// outside every segment are turned off.
+    ///
+    /// # Panics
+    ///
+    /// If a segment runs past the end of the strip or two segments overlap.
+    pub fn new(ws: Ws2812<'d, LEDS>, segments: [Segment; SEGMENTS]) -> Self {
+        for (i, segment) in segments.iter().enumerate() {
+            assert!(segment.end() <= LEDS, "segment past the end of the strip");
+            let overlaps = segments[..i]
+                .iter()
+                .any(|other| segment.start < other.end() && other.start < segment.end());
+            assert!(!overlaps, "segments overlap");
+        }
+        Self {
+            ws,
+            segments,
+            colors: [RGB8::default(); LEDS],
+        }
+    }
+
+    /// Range of LEDs and brightness of a segment.
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is not below `SEGMENTS`.
+    pub fn segment(&self, segment: usize) -> &Segment {
+        &self.segments[segment]
+    }
+
+    /// Colors of a segment in logical order, changes are sent
+    /// by the next flush.
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is not below `SEGMENTS`.
+    pub fn pixels_mut(&mut self, segment: usize) -> &mut [RGB8] {
+        let segment = &self.segments[segment];
+        &mut self.colors[segment.start..segment.start + segment.logical_len()]
+    }
+
+    /// Set the colors of a segment, LEDs of the segment
+    /// past the end of the colors are turned off.
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is not below `SEGMENTS`.
+    pub fn set<T, I>(&mut self, segment: usize, iterator: T)
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        let mut colors = iterator.into_iter().map(Into::into);
+        for pixel in self.pixels_mut(segment) {
+            *pixel = colors.next().unwrap_or_default();
+        }
+    }
+
+    /// Set every LED of a segment to `color`.
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is not below `SEGMENTS`.
+    pub fn fill(&mut self, segment: usize, color: RGB8) {
+        self.pixels_mut(segment).fill(color);
+    }
+
+    /// Scale the colors of a segment when the frame is sent,
+    /// on top of the brightness of the driver.
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is not below `SEGMENTS`.
+    pub fn set_brightness(&mut self, segment: usize, brightness: u8) {
+        self.segments[segment].brightness = brightness;
+    }
+
+    /// Composite the segments and send the frame.
+    #[cfg(feature = "async")]
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let frame = composite(&self.segments, &self.colors);
+        SmartLedsWriteAsync::write(&mut self.ws, frame).await
+    }
+
+    /// Blocking version of [Segments::flush].
+    pub fn flush_blocking(&mut self) -> Result<(), Error> {
+        let frame = composite(&self.segments, &self.colors);
+        SmartLedsWrite::write(&mut self.ws, frame)
+    }
+
+    /// The driver, for example to change its settings.
+    pub fn ws(&mut self) -> &mut Ws2812<'d, LEDS> {
+        &mut self.ws
+    }
+
+    /// Release the driver.
+    pub fn release(self) -> Ws2812<'d, LEDS> {
+        self.ws
+    }
+}
+
+/// Frame of every LED of the strip, mapped from the logical colors
+/// and scaled by the brightness of its segment, off outside the segments.
+fn composite<'a>(segments: &'a [Segment], colors: &'a [RGB8]) -> impl Iterator<Item = RGB8> + 'a {
+    (0..colors.len()).map(|index| {
+        match segments
+            .iter()
+            .find(|segment| (segment.start..segment.end()).contains(&index))
+        {
+            Some(segment) => {
+                let color = colors[segment.start + segment.logical(index - segment.start)];
+                let brightness = segment.brightness;
+                Correction::new(brightness, brightness, brightness).apply(color)
+            }
+            None => RGB8::default(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logical(segment: Segment) -> [usize; 5] {
+        core::array::from_fn(|offset| segment.logical(offset))
+    }
+
+    #[test]
+    fn logical_runs_forward() {
+        assert_eq!(logical(Segment::new(3, 5)), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn logical_reversed() {
+        assert_eq!(logical(Segment::new(0, 5).reversed(true)), [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn logical_mirrored() {
+        let segment = Segment::new(0, 5).mirrored(true);
+        assert_eq!(segment.logical_len(), 3);
+        assert_eq!(logical(segment), [0, 1, 2, 1, 0]);
+
+        let even = Segment::new(0, 4).mirrored(true);
+        assert_eq!(even.logical_len(), 2);
+        assert_eq!(
+            [0, 1, 2, 3].map(|offset| even.logical(offset)),
+            [0, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn logical_mirrored_reversed() {
+        let segment = Segment::new(0, 5).mirrored(true).reversed(true);
+        assert_eq!(logical(segment), [2, 1, 0, 1, 2]);
+    }
+}
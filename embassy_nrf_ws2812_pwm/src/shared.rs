@@ -0,0 +1,61 @@
+//! Driver shared between tasks.
+//!
+//! [SharedWs2812] puts the driver behind an async mutex and implements
+//! [SmartLedsWriteAsync] for `&SharedWs2812`, so several tasks (for
+//! example a BLE task and a sensor task) can write to the strip through
+//! a shared reference without their own locking.
+
+use crate::Ws2812;
+use crate::error::Error;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use smart_leds::{RGB8, SmartLedsWriteAsync};
+
+/// [Ws2812] behind an async mutex.
+pub struct SharedWs2812<'d, M: RawMutex, const LEDS: usize> {
+    ws: Mutex<M, Ws2812<'d, LEDS>>,
+}
+
+impl<'d, M: RawMutex, const LEDS: usize> SharedWs2812<'d, M, LEDS> {
+    /// Share a driver.
+    pub fn new(ws: Ws2812<'d, LEDS>) -> Self {
+        Self { ws: Mutex::new(ws) }
+    }
+
+    /// Lock the driver, for example to write several frames
+    /// without another task writing in between.
+    pub async fn lock(&self) -> MutexGuard<'_, M, Ws2812<'d, LEDS>> {
+        self.ws.lock().await
+    }
+
+    /// Write a frame unless another task holds the driver, then
+    /// fail with [Error::Busy] instead of waiting for it.
+    ///
+    /// For tasks where a dropped frame is better than a late one.
+    pub async fn try_write<T, I>(&self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        let mut ws = self.ws.try_lock().map_err(|_| Error::Busy)?;
+        ws.write(iterator).await
+    }
+
+    /// Release the driver.
+    pub fn into_inner(self) -> Ws2812<'d, LEDS> {
+        self.ws.into_inner()
+    }
+}
+
+impl<M: RawMutex, const LEDS: usize> SmartLedsWriteAsync for &SharedWs2812<'_, M, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.lock().await.write(iterator).await
+    }
+}
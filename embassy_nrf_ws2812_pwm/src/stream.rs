@@ -0,0 +1,278 @@
+//! Strips of any length from a small fixed buffer.
+//!
+//! A sequence is limited to 32767 samples, about 1365 LEDs, and a
+//! [Ws2812] buffer takes 48 bytes per LED. [Ws2812Stream] instead
+//! chains two buffers of `LEDS` LEDs with the SEQ0/SEQ1 loop of the
+//! PWM. While EasyDMA sends one buffer the next LEDs are encoded into
+//! the other, so the RAM used is fixed however long the strip is.
+//!
+//! Each buffer has to be refilled before the other one finishes, which
+//! takes 1.25 µs per sample with the WS2812B timing. A chunk shorter
+//! than the worst case latency of the writing task glitches the strip,
+//! the blocking write or a high priority executor keeps it short.
+//!
+//! Frames are written with the smart-leds traits and the colors are
+//! only taken from the iterator as they are encoded, so a frame can be
+//! generated on the fly without ever being stored. Two chunks of 10
+//! LEDs (480 bytes) drive a strip of any length.
+//!
+//! ```ignore
+//! static BUFS: StaticCell<[Samples<10>; 2]> = StaticCell::new();
+//! let bufs = BUFS.init([[[0; 24]; 10]; 2]);
+//! let mut ws = Ws2812Builder::new().build_streaming(p.PWM0, Irqs, p.P0_13, bufs)?;
+//! ws.write((0..3000).map(|i| hsv2rgb(Hsv { hue: i as u8, sat: 255, val: 32 }))).await?;
+//! ```
+//!
+//! [Ws2812]: crate::Ws2812
+
+use crate::builder::Ws2812Builder;
+use crate::dma;
+use crate::error::Error;
+use crate::gamma::gamma_table;
+use crate::instance::{Event, Events};
+use crate::pwm::{
+    Config, Sequence, SequenceConfig, SequenceLoad, SequenceMode, SequencePwm, Sequencer,
+    StartSequence,
+};
+use crate::timing::POLARITY;
+use crate::{Encoder, IdlePin, Instance, InterruptHandler, MAX_SAMPLES, Samples, buffer_len};
+use core::ptr;
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// WS2812 driver streaming frames through two buffers of `LEDS` LEDs.
+pub struct Ws2812Stream<'d, const LEDS: usize> {
+    pwm: SequencePwm<'d>,
+    events: Events,
+    bufs: &'d mut [Samples<LEDS>; 2],
+    /// Idle samples latching a frame, each held for `refresh + 1` periods.
+    reset: usize,
+    refresh: u32,
+    encoder: Encoder,
+}
+
+impl<'d, const LEDS: usize> Ws2812Stream<'d, LEDS> {
+    /// Create a driver using the default [Timing](crate::timing::Timing) (WS2812B).
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        bufs: &'d mut [Samples<LEDS>; 2],
+    ) -> Result<Self, Error> {
+        Self::with_config(pwm, pin, bufs, Ws2812Builder::new())
+    }
+
+    /// Create the driver once the interrupt binding was checked
+    /// by the public constructors.
+    pub(crate) fn with_config<T: Instance>(
+        pwm: Peri<'d, T>,
+        pin: Peri<'d, impl Pin>,
+        bufs: &'d mut [Samples<LEDS>; 2],
+        builder: Ws2812Builder,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(LEDS > 0, "chunk without LEDs");
+            assert!(
+                buffer_len(LEDS) <= MAX_SAMPLES,
+                "chunk longer than a sequence"
+            );
+        };
+
+        let timing = builder.timing;
+        if !timing.is_valid_with(builder.prescaler) {
+            return Err(Error::InvalidConfig);
+        }
+        dma::check(bufs.as_flattened().as_flattened())?;
+
+        let mut config = Config::default();
+        config.sequence_load = SequenceLoad::Common;
+        config.prescaler = builder.prescaler;
+        config.max_duty = timing.period_ticks(builder.prescaler);
+        config.ch0_drive = builder.drive;
+
+        let idle = IdlePin::of(&*pin);
+        let pwm = SequencePwm::new_1ch(pwm, pin, config)?;
+        idle.set(builder.idle());
+        let mut encoder = Encoder::new(
+            &timing,
+            builder.prescaler,
+            builder.color_order,
+            builder.inverted,
+        );
+        encoder.gamma = (builder.gamma != 1.0).then(|| gamma_table(builder.gamma));
+        encoder.brightness = builder.brightness;
+        encoder.correction = builder.correction;
+        Ok(Self {
+            pwm,
+            events: Events::new::<T>(),
+            bufs,
+            reset: timing
+                .reset_periods(builder.prescaler)
+                .div_ceil(builder.refresh + 1) as usize,
+            refresh: builder.refresh,
+            encoder,
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Ws2812Stream<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    /// Send a frame of any length, encoding it while it is sent.
+    ///
+    /// Dropping the future stops the sequence, the strip may show
+    /// a partial frame until the next write.
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let mut chunks = Chunks {
+            bufs: self.bufs.as_mut_ptr(),
+            encoder: &self.encoder,
+            colors: iterator.into_iter().map(Into::into).fuse(),
+            reset: self.reset,
+            last: None,
+        };
+        let events = self.events;
+        let seq = chunks.start(&mut self.pwm, &events, self.refresh)?;
+        let mut index = 0;
+        loop {
+            events.wait(Event::SeqEnd(index)).await;
+            if chunks.refill(&events, index) {
+                break;
+            }
+            index ^= 1;
+        }
+        drop(seq);
+        Ok(())
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Ws2812Stream<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    /// Send a frame of any length, blocking until it has been sent.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let mut chunks = Chunks {
+            bufs: self.bufs.as_mut_ptr(),
+            encoder: &self.encoder,
+            colors: iterator.into_iter().map(Into::into).fuse(),
+            reset: self.reset,
+            last: None,
+        };
+        let events = self.events;
+        let seq = chunks.start(&mut self.pwm, &events, self.refresh)?;
+        let mut index = 0;
+        loop {
+            events.wait_blocking(Event::SeqEnd(index));
+            if chunks.refill(&events, index) {
+                break;
+            }
+            index ^= 1;
+        }
+        drop(seq);
+        Ok(())
+    }
+}
+
+/// Encoding state of a streamed frame.
+struct Chunks<'e, const LEDS: usize, C> {
+    bufs: *mut Samples<LEDS>,
+    encoder: &'e Encoder,
+    colors: C,
+    /// Idle samples still needed to latch the frame.
+    reset: usize,
+    /// Buffer holding the end of the reset period once encoded.
+    last: Option<usize>,
+}
+
+impl<const LEDS: usize, C: Iterator<Item = RGB8>> Chunks<'_, LEDS, C> {
+    /// Samples of a buffer.
+    const CHUNK: usize = buffer_len(LEDS);
+
+    /// Fill both buffers and start looping through them.
+    fn start<'d, 's>(
+        &mut self,
+        pwm: &'s mut SequencePwm<'d>,
+        events: &Events,
+        refresh: u32,
+    ) -> Result<Sequencer<'d, 's>, Error> {
+        self.fill(0);
+        self.fill(1);
+
+        events.clear(Event::SeqEnd(0));
+        events.clear(Event::SeqEnd(1));
+        let mut config = SequenceConfig::default();
+        config.refresh = refresh;
+        // SAFETY: the buffers are only written through `bufs` while
+        // EasyDMA sends the other one, the sequences are not read again
+        // after the sequencer has started.
+        let (seq0, seq1) = unsafe {
+            (
+                core::slice::from_raw_parts(self.bufs.cast::<u16>(), Self::CHUNK),
+                core::slice::from_raw_parts(self.bufs.add(1).cast::<u16>(), Self::CHUNK),
+            )
+        };
+        let seq = Sequencer::new(
+            pwm,
+            Sequence::new(seq0, config.clone()),
+            Some(Sequence::new(seq1, config)),
+        );
+        seq.start(StartSequence::Zero, SequenceMode::Infinite)?;
+        Ok(seq)
+    }
+
+    /// Refill buffer `index` after its SEQEND event, returns `true`
+    /// once the whole frame including the reset is sent.
+    fn refill(&mut self, events: &Events, index: usize) -> bool {
+        events.clear(Event::SeqEnd(index));
+        if self.last == Some(index) {
+            return true;
+        }
+        self.fill(index);
+        false
+    }
+
+    /// Encode the next LEDs into buffer `index`, padding with idle samples.
+    fn fill(&mut self, index: usize) {
+        // SAFETY: in bounds of the two buffers borrowed for the stream
+        let buf = unsafe { self.bufs.add(index).cast::<u16>() };
+        let idle = self.encoder.zero & POLARITY;
+        let mut samples = [0; 24];
+        let mut pos = 0;
+        while pos < Self::CHUNK {
+            let Some(color) = self.colors.next() else {
+                break;
+            };
+            self.encoder.encode_pixel(&mut samples, color);
+            for sample in samples {
+                // SAFETY: `pos` is below the chunk length and EasyDMA is not
+                // reading this buffer
+                unsafe { ptr::write_volatile(buf.add(pos), sample) };
+                pos += 1;
+            }
+        }
+        if pos < Self::CHUNK {
+            for i in pos..Self::CHUNK {
+                // SAFETY: as above
+                unsafe { ptr::write_volatile(buf.add(i), idle) };
+            }
+            self.reset = self.reset.saturating_sub(Self::CHUNK - pos);
+            if self.reset == 0 && self.last.is_none() {
+                self.last = Some(index);
+            }
+        }
+    }
+}
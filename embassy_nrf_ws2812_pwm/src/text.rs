@@ -0,0 +1,247 @@
+//! Scrolling text on a matrix.
+//!
+//! [Marquee] draws ASCII text in a 5 × 7 font onto a [Matrix] and
+//! scrolls it from right to left, one column every interval of an
+//! embassy [Ticker]. Once the text has left the display it enters
+//! again from the right.
+//!
+//! ```ignore
+//! let mut matrix = Matrix::<32, 8>::new(Layout::SERPENTINE);
+//! let mut marquee = Marquee::new("Hello, world!", colors::RED).interval(Duration::from_millis(40));
+//! let error = marquee.run(&mut matrix, &mut ws).await;
+//! ```
+
+use crate::matrix::{Mapping, Matrix};
+use embassy_time::{Duration, Ticker};
+use smart_leds::{RGB8, SmartLedsWriteAsync};
+
+/// Columns of a glyph.
+pub const GLYPH_WIDTH: usize = 5;
+/// Rows of a glyph.
+pub const GLYPH_HEIGHT: usize = 7;
+/// Columns taken by each character, the glyph and a blank column.
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+
+/// Glyphs of the printable ASCII characters from `' '` to `'~'`, one
+/// byte per column from left to right with the top row in bit 0.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // '#'
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '''
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // ')'
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // '*'
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // '0'
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // '@'
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // 'A'
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // 'D'
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // 'G'
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // 'H'
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // 'J'
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // 'M'
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // 'N'
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // 'O'
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // 'Q'
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // 'T'
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // 'U'
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // 'V'
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\'
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // 'f'
+    [0x08, 0x54, 0x54, 0x54, 0x3c], // 'g'
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // 'j'
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // 'l'
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x14, 0x7c], // 'q'
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // 't'
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // 'u'
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // 'v'
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // 'y'
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08], // '~'
+];
+
+/// Glyph of `c`, characters outside printable ASCII show as `'?'`.
+pub const fn glyph(c: char) -> [u8; GLYPH_WIDTH] {
+    let c = if c >= ' ' && c <= '~' { c } else { '?' };
+    FONT[c as usize - ' ' as usize]
+}
+
+/// Text scrolling across a matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marquee<'t> {
+    text: &'t str,
+    color: RGB8,
+    background: RGB8,
+    row: Option<usize>,
+    interval: Duration,
+    position: usize,
+}
+
+impl<'t> Marquee<'t> {
+    /// `text` in `color` on black, centered vertically and
+    /// scrolling one column every 50 ms.
+    pub const fn new(text: &'t str, color: RGB8) -> Self {
+        Self {
+            text,
+            color,
+            background: RGB8 { r: 0, g: 0, b: 0 },
+            row: None,
+            interval: Duration::from_millis(50),
+            position: 0,
+        }
+    }
+
+    /// Color of the LEDs around the glyphs.
+    pub const fn background(mut self, background: RGB8) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Draw the top of the glyphs at `row` instead of centering them.
+    pub const fn row(mut self, row: usize) -> Self {
+        self.row = Some(row);
+        self
+    }
+
+    /// Time between scrolling one column, shorter is faster.
+    pub const fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Replace the text and start scrolling it from the right edge.
+    pub fn set_text(&mut self, text: &'t str) {
+        self.text = text;
+        self.position = 0;
+    }
+
+    /// Change the color of the glyphs, applied from the next render.
+    pub fn set_color(&mut self, color: RGB8) {
+        self.color = color;
+    }
+
+    /// Columns taken by the text.
+    pub fn width(&self) -> usize {
+        self.text.chars().count() * ADVANCE
+    }
+
+    /// Draw the text at its current position, covering the whole matrix.
+    pub fn render<const W: usize, const H: usize, M: Mapping>(&self, matrix: &mut Matrix<W, H, M>) {
+        matrix.fill(self.background);
+        let top = self.row.unwrap_or(H.saturating_sub(GLYPH_HEIGHT) / 2);
+        // The text starts just past the right edge at position 0
+        let start = W as isize - self.position as isize;
+        for (i, c) in self.text.chars().enumerate() {
+            let left = start + (i * ADVANCE) as isize;
+            if left >= W as isize {
+                break;
+            }
+            for (column, bits) in glyph(c).into_iter().enumerate() {
+                let x = left + column as isize;
+                if x < 0 {
+                    continue;
+                }
+                for y in 0..GLYPH_HEIGHT {
+                    if bits & (1 << y) != 0 {
+                        matrix.set(x as usize, top + y, self.color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scroll one column to the left on a matrix `width` columns wide,
+    /// returns `true` when the text has left the display and starts over.
+    pub fn step(&mut self, width: usize) -> bool {
+        self.position += 1;
+        let wrapped = self.position >= width + self.width();
+        if wrapped {
+            self.position = 0;
+        }
+        wrapped
+    }
+
+    /// Render, write and scroll the text every interval until a write fails.
+    pub async fn run<const W: usize, const H: usize, M, D>(
+        &mut self,
+        matrix: &mut Matrix<W, H, M>,
+        ws: &mut D,
+    ) -> D::Error
+    where
+        M: Mapping,
+        D: SmartLedsWriteAsync<Color = RGB8>,
+    {
+        let mut ticker = Ticker::every(self.interval);
+        loop {
+            self.render(matrix);
+            if let Err(e) = ws.write(matrix.iter()).await {
+                return e;
+            }
+            self.step(W);
+            ticker.next().await;
+        }
+    }
+}
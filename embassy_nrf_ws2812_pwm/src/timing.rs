@@ -0,0 +1,249 @@
+//! Bit timings for WS2812 compatible LED chips.
+
+use crate::pwm::{PWM_CLK_HZ, Prescaler};
+#[cfg(test)]
+use core::ops::RangeInclusive;
+
+/// PWM clock frequency using the `Div1` prescaler.
+///
+/// The PWM runs from the 16 MHz peripheral clock on nRF52, the nRF5340
+/// application core and nRF91 alike, whatever the CPU clock. On the
+/// nRF54L series PWM20 to PWM22 are clocked by PCLK16M of the PERI
+/// power domain, see [PWM_CLK_HZ](crate::pwm::PWM_CLK_HZ) of the chip.
+pub(crate) const PWM_CLOCK: u64 = PWM_CLK_HZ as u64;
+
+/// Polarity bit so each PWM period starts with the output high.
+pub(crate) const POLARITY: u16 = 0x8000;
+
+/// Bit timings of an LED chip.
+///
+/// Durations are rounded to the nearest PWM tick, 62.5ns with the
+/// `Div1` prescaler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timing {
+    /// High time of a zero bit in nanoseconds.
+    pub t0h: u32,
+    /// High time of a one bit in nanoseconds.
+    pub t1h: u32,
+    /// Total time of a bit in nanoseconds.
+    pub period: u32,
+    /// Time to hold the line low to latch a frame in microseconds.
+    pub reset: u32,
+}
+
+impl Timing {
+    /// WS2812.
+    pub const WS2812: Timing = Timing::new(350, 700, 1250, 50);
+
+    /// WS2812B.
+    pub const WS2812B: Timing = Timing::new(400, 800, 1250, 300);
+
+    /// WS2811 in slow (400kHz) mode.
+    pub const WS2811: Timing = Timing::new(500, 1200, 2500, 50);
+
+    /// WS2813.
+    pub const WS2813: Timing = Timing::new(300, 750, 1250, 300);
+
+    /// WS2815.
+    pub const WS2815: Timing = Timing::new(300, 750, 1250, 300);
+
+    /// SK6812.
+    pub const SK6812: Timing = Timing::new(300, 600, 1250, 80);
+
+    /// TM1814.
+    pub const TM1814: Timing = Timing::new(360, 720, 1250, 200);
+
+    /// Create custom timings.
+    pub const fn new(t0h: u32, t1h: u32, period: u32, reset: u32) -> Self {
+        Self {
+            t0h,
+            t1h,
+            period,
+            reset,
+        }
+    }
+
+    /// Whether the timings can be generated by the PWM.
+    pub const fn is_valid(&self) -> bool {
+        self.is_valid_with(Prescaler::Div1)
+    }
+
+    /// Whether the timings can be generated by the PWM clocked
+    /// through `prescaler`.
+    ///
+    /// Only checks that zero and one bits stay distinct, with coarse
+    /// ticks the rounded high times may fall outside the tolerances
+    /// of the chip.
+    pub const fn is_valid_with(&self, prescaler: Prescaler) -> bool {
+        let period = ticks(self.period, prescaler);
+        ticks(self.t0h, prescaler) > 0
+            && ticks(self.t0h, prescaler) < ticks(self.t1h, prescaler)
+            && ticks(self.t1h, prescaler) < period
+            && period < POLARITY as u32
+    }
+
+    /// PWM ticks for a single bit.
+    pub(crate) const fn period_ticks(&self, prescaler: Prescaler) -> u16 {
+        ticks(self.period, prescaler) as u16
+    }
+
+    /// PWM ticks for the high time of a zero bit.
+    pub(crate) const fn zero_ticks(&self, prescaler: Prescaler) -> u16 {
+        ticks(self.t0h, prescaler) as u16
+    }
+
+    /// PWM ticks for the high time of a one bit.
+    pub(crate) const fn one_ticks(&self, prescaler: Prescaler) -> u16 {
+        ticks(self.t1h, prescaler) as u16
+    }
+
+    /// Number of PWM periods covering the reset time, counted in
+    /// whole ticks so a period rounded down doesn't shorten it.
+    pub(crate) const fn reset_periods(&self, prescaler: Prescaler) -> u32 {
+        let clock = PWM_CLOCK >> divider_shift(prescaler);
+        let reset_ticks = (self.reset as u64 * clock).div_ceil(1_000_000);
+        reset_ticks.div_ceil(self.period_ticks(prescaler) as u64) as u32
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::WS2812B
+    }
+}
+
+/// Range of each high and low time of a bit a chip accepts,
+/// in nanoseconds.
+#[cfg(test)]
+pub(crate) struct Bounds {
+    pub(crate) t0h: RangeInclusive<u32>,
+    pub(crate) t0l: RangeInclusive<u32>,
+    pub(crate) t1h: RangeInclusive<u32>,
+    pub(crate) t1l: RangeInclusive<u32>,
+}
+
+#[cfg(test)]
+impl Bounds {
+    /// WS2812B datasheet, ±150 ns around each nominal time.
+    pub(crate) const WS2812B: Bounds = Bounds {
+        t0h: 250..=550,
+        t0l: 700..=1000,
+        t1h: 650..=950,
+        t1l: 300..=600,
+    };
+
+    /// Whether a zero and a one with these high and low times are accepted.
+    pub(crate) fn accepts(&self, zero: (u32, u32), one: (u32, u32)) -> bool {
+        self.t0h.contains(&zero.0)
+            && self.t0l.contains(&zero.1)
+            && self.t1h.contains(&one.0)
+            && self.t1l.contains(&one.1)
+    }
+}
+
+/// Convert nanoseconds to the nearest number of PWM ticks.
+const fn ticks(nanos: u32, prescaler: Prescaler) -> u32 {
+    let clock = PWM_CLOCK >> divider_shift(prescaler);
+    ((nanos as u64 * clock + 500_000_000) / 1_000_000_000) as u32
+}
+
+/// Power of two dividing the PWM clock.
+const fn divider_shift(prescaler: Prescaler) -> u32 {
+    match prescaler {
+        Prescaler::Div1 => 0,
+        Prescaler::Div2 => 1,
+        Prescaler::Div4 => 2,
+        Prescaler::Div8 => 3,
+        Prescaler::Div16 => 4,
+        Prescaler::Div32 => 5,
+        Prescaler::Div64 => 6,
+        Prescaler::Div128 => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMINGS: [Timing; 7] = [
+        Timing::WS2812,
+        Timing::WS2812B,
+        Timing::WS2811,
+        Timing::WS2813,
+        Timing::WS2815,
+        Timing::SK6812,
+        Timing::TM1814,
+    ];
+
+    const PRESCALERS: [Prescaler; 8] = [
+        Prescaler::Div1,
+        Prescaler::Div2,
+        Prescaler::Div4,
+        Prescaler::Div8,
+        Prescaler::Div16,
+        Prescaler::Div32,
+        Prescaler::Div64,
+        Prescaler::Div128,
+    ];
+
+    /// Ticks of the reset sent after a frame.
+    fn reset_ticks(timing: &Timing, prescaler: Prescaler) -> u64 {
+        timing.reset_periods(prescaler) as u64 * timing.period_ticks(prescaler) as u64
+    }
+
+    #[test]
+    fn reset_latches_every_timing() {
+        for timing in TIMINGS {
+            for prescaler in PRESCALERS.into_iter().filter(|&p| timing.is_valid_with(p)) {
+                let clock = PWM_CLOCK >> divider_shift(prescaler);
+                // The line stays idle at least the reset time
+                assert!(reset_ticks(&timing, prescaler) * 1_000_000 >= timing.reset as u64 * clock);
+                // and by less than a period more
+                let period = timing.period_ticks(prescaler) as u64;
+                assert!(
+                    (reset_ticks(&timing, prescaler) - period) * 1_000_000
+                        < timing.reset as u64 * clock
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reset_counts_rounded_period() {
+        // 1210 ns rounds down to 19 ticks, 1187.5 ns
+        let timing = Timing::new(400, 800, 1210, 300);
+        assert_eq!(timing.period_ticks(Prescaler::Div1), 19);
+        assert_eq!(timing.reset_periods(Prescaler::Div1), 253);
+        assert!(reset_ticks(&timing, Prescaler::Div1) >= 300 * 16);
+    }
+
+    #[test]
+    fn ws2812b_ticks() {
+        let timing = Timing::WS2812B;
+        assert_eq!(timing.period_ticks(Prescaler::Div1), 20);
+        assert_eq!(timing.zero_ticks(Prescaler::Div1), 6);
+        assert_eq!(timing.one_ticks(Prescaler::Div1), 13);
+        assert_eq!(timing.period_ticks(Prescaler::Div2), 10);
+        assert_eq!(timing.reset_periods(Prescaler::Div1), 240);
+    }
+
+    #[test]
+    fn ws2812b_within_bounds() {
+        let timing = Timing::WS2812B;
+        let nanos = |ticks: u16| (ticks as u64 * 1_000_000_000 / PWM_CLOCK) as u32;
+        let period = timing.period_ticks(Prescaler::Div1);
+        let zero = timing.zero_ticks(Prescaler::Div1);
+        let one = timing.one_ticks(Prescaler::Div1);
+        assert!(Bounds::WS2812B.accepts(
+            (nanos(zero), nanos(period - zero)),
+            (nanos(one), nanos(period - one)),
+        ));
+    }
+
+    #[test]
+    fn coarse_prescaler_is_invalid() {
+        assert!(Timing::WS2812B.is_valid_with(Prescaler::Div2));
+        assert!(!Timing::WS2812B.is_valid_with(Prescaler::Div16));
+    }
+}
@@ -0,0 +1,257 @@
+//! TM1814 RGBW strips.
+//!
+//! The TM1814 sets the drive current of its channels from a preamble
+//! sent before the pixel data of every frame, the 4 current settings
+//! for white, red, green and blue followed by their complements which
+//! the chip checks. The data line is inverted, idling high, and each
+//! LED takes white then the red, green and blue channels.
+
+use crate::builder::Ws2812Builder;
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use crate::timing::Timing;
+use crate::white::WhiteLed;
+use crate::{Buffer, Instance, InterruptHandler, MAX_SAMPLES, Ws2812};
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, RGBW, SmartLedsWrite};
+
+/// PWM samples of the current preamble.
+const PREAMBLE: usize = 64;
+
+/// Sample buffer of a [Tm1814] driving `LEDS` LEDs, the
+/// preamble then 32 samples per LED.
+#[repr(C)]
+pub struct Tm1814Samples<const LEDS: usize> {
+    preamble: [u16; PREAMBLE],
+    leds: [[u16; 32]; LEDS],
+}
+
+impl<const LEDS: usize> Tm1814Samples<LEDS> {
+    /// Number of samples of the buffer.
+    const LEN: usize = PREAMBLE + LEDS * 32;
+
+    /// Zeroed buffer.
+    pub const fn new() -> Self {
+        Self {
+            preamble: [0; PREAMBLE],
+            leds: [[0; 32]; LEDS],
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u16] {
+        // SAFETY: `repr(C)` arrays of u16 are laid out without padding
+        unsafe { core::slice::from_raw_parts_mut((self as *mut Self).cast(), Self::LEN) }
+    }
+}
+
+impl<const LEDS: usize> Default for Tm1814Samples<LEDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drive current of each channel, from `0` (6.5 mA)
+/// to `63` (38 mA) in steps of 0.5 mA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Current {
+    pub white: u8,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Current {
+    /// Highest setting.
+    pub const MAX: u8 = 63;
+
+    /// Every channel at the same setting, clamped to [Current::MAX].
+    pub const fn uniform(setting: u8) -> Self {
+        let setting = if setting > Self::MAX {
+            Self::MAX
+        } else {
+            setting
+        };
+        Self {
+            white: setting,
+            red: setting,
+            green: setting,
+            blue: setting,
+        }
+    }
+
+    /// Setting closest to `milliamps` without going over, 6.5 mA
+    /// and below give the lowest setting.
+    pub const fn setting_for_ma(milliamps: f32) -> u8 {
+        let steps = (milliamps - 6.5) * 2.0;
+        if steps <= 0.0 {
+            0
+        } else if steps >= Self::MAX as f32 {
+            Self::MAX
+        } else {
+            steps as u8
+        }
+    }
+
+    /// Preamble bytes sent before the pixel data.
+    fn preamble(&self) -> [u8; 8] {
+        let c = [self.white, self.red, self.green, self.blue].map(|c| c.min(Self::MAX));
+        [c[0], c[1], c[2], c[3], !c[0], !c[1], !c[2], !c[3]]
+    }
+}
+
+impl Default for Current {
+    /// Lowest current on every channel.
+    fn default() -> Self {
+        Self::uniform(0)
+    }
+}
+
+/// TM1814 driver for a strip of `LEDS` LEDs.
+pub struct Tm1814<'d, const LEDS: usize> {
+    ws: Ws2812<'d, LEDS>,
+    current: Current,
+    white: WhiteLed,
+}
+
+impl<'d, const LEDS: usize> Tm1814<'d, LEDS> {
+    /// Create a driver setting the channel currents to `current`.
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Tm1814Samples<LEDS>,
+        current: Current,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(
+                Tm1814Samples::<LEDS>::LEN <= MAX_SAMPLES,
+                "buffer longer than a PWM sequence"
+            )
+        };
+        let builder = Ws2812Builder::new()
+            .timing(Timing::TM1814)
+            .color_order(ColorOrder::Rgb)
+            .inverted(true);
+        let buf = Buffer::Slice(buf.as_mut_slice());
+        let ws = Ws2812::with_config(pwm, pin, None, buf, None, builder)?;
+        Ok(Self {
+            ws,
+            current,
+            white: WhiteLed::default(),
+        })
+    }
+
+    /// Channel currents sent with every frame.
+    pub fn current(&self) -> Current {
+        self.current
+    }
+
+    /// Change the channel currents, applied from the next write.
+    pub fn set_current(&mut self, current: Current) {
+        self.current = current;
+    }
+
+    /// Scale every color while encoding, applied from the next write.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.ws.set_brightness(brightness);
+    }
+
+    /// White die of the LEDs used by [Tm1814::set_white].
+    pub fn white_led(&self) -> WhiteLed {
+        self.white
+    }
+
+    /// Set the white die of the LEDs, [WhiteLed::NEUTRAL] by default.
+    pub fn set_white_led(&mut self, white: WhiteLed) {
+        self.white = white;
+    }
+
+    /// Light every LED white at `cct` kelvin, see [WhiteLed::mix].
+    #[cfg(feature = "async")]
+    pub async fn set_white(&mut self, cct: u32, brightness: u8) -> Result<(), Error> {
+        let color = self.white.mix(cct, brightness);
+        SmartLedsWriteAsync::write(self, core::iter::repeat(color)).await
+    }
+
+    /// Blocking version of [Tm1814::set_white].
+    pub fn set_white_blocking(&mut self, cct: u32, brightness: u8) -> Result<(), Error> {
+        let color = self.white.mix(cct, brightness);
+        SmartLedsWrite::write(self, core::iter::repeat(color))
+    }
+
+    /// Encode the preamble and a frame, LEDs past the end of the
+    /// colors are turned off.
+    fn encode(&mut self, mut colors: impl Iterator<Item = RGBW<u8>>) {
+        let encoder = &self.ws.encoder;
+        let buf = &mut *self.ws.buf;
+        let (preamble, pixels) = buf.split_at_mut(PREAMBLE);
+        for (samples, byte) in preamble.chunks_exact_mut(8).zip(self.current.preamble()) {
+            encoder.encode_raw(samples, byte);
+        }
+        for chunk in pixels.chunks_exact_mut(32) {
+            let color = colors.next().unwrap_or_default();
+            let (white, rgb) = chunk.split_at_mut(8);
+            encoder.encode_byte(white, color.a.0);
+            encoder.encode_pixel(rgb, RGB8::new(color.r, color.g, color.b));
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Tm1814<'_, LEDS> {
+    type Error = Error;
+    type Color = RGBW<u8>;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight().await;
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame().await?)
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Tm1814<'_, LEDS> {
+    type Error = Error;
+    type Color = RGBW<u8>;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight_blocking();
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame_blocking()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_hold_preamble_then_leds() {
+        let mut buf = Tm1814Samples::<10>::new();
+        buf.leds[0][0] = 1;
+        let samples = buf.as_mut_slice();
+        assert_eq!(samples.len(), PREAMBLE + 10 * 32);
+        assert_eq!(samples[PREAMBLE], 1);
+    }
+
+    #[test]
+    fn preamble_ends_with_complements() {
+        let current = Current::uniform(70);
+        assert_eq!(current.white, Current::MAX);
+        assert_eq!(current.preamble()[4], !Current::MAX);
+        assert_eq!(Current::setting_for_ma(20.0), 27);
+        assert_eq!(Current::setting_for_ma(5.0), 0);
+    }
+}
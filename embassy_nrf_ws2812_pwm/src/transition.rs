@@ -0,0 +1,181 @@
+//! Crossfades between effects.
+//!
+//! Switching effects directly snaps from one frame to a completely
+//! different one. A [Crossfade] is an [Effect] blending the outgoing
+//! effect into the incoming one over a duration, shaped by an
+//! [Easing] curve, and then shows the incoming effect alone. [Still]
+//! turns a fixed frame into an effect to fade from or to.
+//!
+//! ```ignore
+//! let elapsed = runner.elapsed();
+//! let fade = Crossfade::<_, _, NUM_LEDS>::new(old, new, Duration::from_millis(500))
+//!     .resume(elapsed)
+//!     .easing(Easing::EaseInOut);
+//! ```
+
+use crate::effects::{Effect, blend};
+use embassy_time::Duration;
+use smart_leds::RGB8;
+
+/// Shape of a transition over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starts slowly and speeds up.
+    EaseIn,
+    /// Starts quickly and slows down.
+    EaseOut,
+    /// Starts and ends slowly.
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased amount for `progress`, both from `0` (start) to `255` (end).
+    pub const fn apply(self, progress: u8) -> u8 {
+        let p = progress as u32;
+        let eased = match self {
+            Easing::Linear => p,
+            Easing::EaseIn => p * p / 255,
+            Easing::EaseOut => 255 - (255 - p) * (255 - p) / 255,
+            // Smoothstep, 3p² - 2p³ scaled to 0..=255
+            Easing::EaseInOut => p * p * (3 * 255 - 2 * p) / (255 * 255),
+        };
+        eased as u8
+    }
+}
+
+/// Fixed frame shown as an effect, LEDs past `LEN` are turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Still<const LEN: usize> {
+    colors: [RGB8; LEN],
+}
+
+impl<const LEN: usize> Still<LEN> {
+    /// Effect showing `colors`.
+    pub const fn new(colors: [RGB8; LEN]) -> Self {
+        Self { colors }
+    }
+}
+
+impl<const LEN: usize> Effect for Still<LEN> {
+    fn frame(&mut self, _t: Duration, out: &mut [RGB8]) {
+        for (i, color) in out.iter_mut().enumerate() {
+            *color = self.colors.get(i).copied().unwrap_or_default();
+        }
+    }
+}
+
+/// Blends from the effect `A` to the effect `B`, rendering at
+/// most `LEN` LEDs of `B` while both are shown.
+///
+/// The incoming effect starts with the crossfade, the outgoing one
+/// continues from where it was when given [Crossfade::resume].
+pub struct Crossfade<A, B, const LEN: usize> {
+    from: A,
+    to: B,
+    duration: Duration,
+    easing: Easing,
+    elapsed: Duration,
+    incoming: [RGB8; LEN],
+}
+
+impl<A: Effect, B: Effect, const LEN: usize> Crossfade<A, B, LEN> {
+    /// Fade from `from` to `to` over `duration`.
+    pub const fn new(from: A, to: B, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing: Easing::EaseInOut,
+            elapsed: Duration::from_ticks(0),
+            incoming: [RGB8 { r: 0, g: 0, b: 0 }; LEN],
+        }
+    }
+
+    /// Shape of the fade, [Easing::EaseInOut] by default.
+    pub const fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Continue the outgoing effect from `elapsed`, the time
+    /// it had been running for, instead of restarting it.
+    pub const fn resume(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Whether the fade is over at time `t` and only the
+    /// incoming effect is shown.
+    pub fn is_done(&self, t: Duration) -> bool {
+        t >= self.duration
+    }
+
+    /// The incoming effect, to show it on its own once the fade is done.
+    pub fn into_incoming(self) -> B {
+        self.to
+    }
+}
+
+impl<A: Effect, B: Effect, const LEN: usize> Effect for Crossfade<A, B, LEN> {
+    fn frame(&mut self, t: Duration, out: &mut [RGB8]) {
+        if self.is_done(t) {
+            self.to.frame(t, out);
+            return;
+        }
+        let progress = (t.as_ticks() * 255 / self.duration.as_ticks().max(1)) as u8;
+        let amount = self.easing.apply(progress);
+        self.from.frame(self.elapsed + t, out);
+        let len = out.len().min(LEN);
+        self.to.frame(t, &mut self.incoming[..len]);
+        for (i, color) in out.iter_mut().enumerate() {
+            let incoming = self.incoming[..len].get(i).copied().unwrap_or_default();
+            *color = blend(*color, incoming, amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    #[test]
+    fn easing_keeps_ends() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0), 0);
+            assert_eq!(easing.apply(255), 255);
+        }
+    }
+
+    #[test]
+    fn easing_is_monotonic() {
+        for easing in EASINGS {
+            for p in 0..255 {
+                assert!(easing.apply(p) <= easing.apply(p + 1));
+            }
+        }
+    }
+
+    #[test]
+    fn easing_curves() {
+        for p in 1..255 {
+            assert_eq!(Easing::Linear.apply(p), p);
+            assert!(Easing::EaseIn.apply(p) < p);
+            assert!(Easing::EaseOut.apply(p) > p);
+        }
+        assert_eq!(Easing::EaseIn.apply(128), 64);
+        assert_eq!(Easing::EaseInOut.apply(128), 128);
+        assert!(Easing::EaseInOut.apply(64) < 64);
+        assert!(Easing::EaseInOut.apply(192) > 192);
+    }
+}
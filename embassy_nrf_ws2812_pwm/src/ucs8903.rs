@@ -0,0 +1,114 @@
+//! UCS8903 LEDs with 16 bits per channel.
+//!
+//! Each LED takes 48 bits, the red, green and blue channels as 16 bit
+//! values, most significant bit first. The extra resolution keeps
+//! dark fades smooth in architectural lighting where 8 bit steps show.
+//! Colors are [RGB16] values and are sent as given, without the
+//! brightness scaling and gamma correction of the 8 bit path.
+
+use crate::builder::Ws2812Builder;
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use crate::{Buffer, Instance, InterruptHandler, MAX_SAMPLES, Ws2812};
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+pub use smart_leds::RGB16;
+use smart_leds::SmartLedsWrite;
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+
+/// Sample buffer of a [Ucs8903] driving `LEDS` LEDs, 48 samples per LED.
+pub type Ucs8903Samples<const LEDS: usize> = [[u16; 48]; LEDS];
+
+/// UCS8903 driver for a strip of `LEDS` LEDs.
+pub struct Ucs8903<'d, const LEDS: usize> {
+    ws: Ws2812<'d, LEDS>,
+}
+
+impl<'d, const LEDS: usize> Ucs8903<'d, LEDS> {
+    /// Create a driver using the default timing and RGB order.
+    pub fn new<T: Instance>(
+        pwm: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Ucs8903Samples<LEDS>,
+    ) -> Result<Self, Error> {
+        Self::with_config(
+            pwm,
+            irq,
+            pin,
+            buf,
+            Ws2812Builder::new().color_order(ColorOrder::Rgb),
+        )
+    }
+
+    /// Create a driver configured by `builder`.
+    ///
+    /// The brightness, gamma, shadow and buffering options
+    /// of the builder don't apply.
+    pub fn with_config<T: Instance>(
+        pwm: Peri<'d, T>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        pin: Peri<'d, impl Pin>,
+        buf: &'d mut Ucs8903Samples<LEDS>,
+        builder: Ws2812Builder,
+    ) -> Result<Self, Error> {
+        const {
+            assert!(
+                LEDS * 48 <= MAX_SAMPLES,
+                "buffer longer than a PWM sequence"
+            )
+        };
+        let buf = Buffer::Slice(buf.as_flattened_mut());
+        Ok(Self {
+            ws: Ws2812::with_config(pwm, pin, None, buf, None, builder)?,
+        })
+    }
+
+    /// Encode a frame, LEDs past the end of the colors are turned off.
+    fn encode(&mut self, mut colors: impl Iterator<Item = RGB16>) {
+        let encoder = &self.ws.encoder;
+        for chunk in self.ws.buf.chunks_exact_mut(48) {
+            let color = colors.next().unwrap_or_default();
+            let channels = encoder.color_order.channels(color);
+            for (value, samples) in channels.into_iter().zip(chunk.chunks_exact_mut(16)) {
+                let [high, low] = value.to_be_bytes();
+                let (first, second) = samples.split_at_mut(8);
+                encoder.encode_raw(first, high);
+                encoder.encode_raw(second, low);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Ucs8903<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB16;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight().await;
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame().await?)
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Ucs8903<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB16;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.ws.wait_in_flight_blocking();
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.ws.transmit_frame_blocking()?)
+    }
+}
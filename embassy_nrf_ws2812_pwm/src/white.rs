@@ -0,0 +1,78 @@
+//! Tunable white on RGBW strips.
+//!
+//! The white die of an RGBW LED has a fixed color temperature, warm,
+//! neutral or cool depending on the part. [WhiteLed::mix] drives the
+//! white channel as far as it matches the requested temperature and
+//! adds the difference with the red, green and blue dies, so at the
+//! native temperature only the white channel is lit.
+//!
+//! ```ignore
+//! let mut strip = Tm1814::new(p.PWM0, Irqs, p.P0_13, buf, Current::uniform(20))?;
+//! strip.set_white_led(WhiteLed::NEUTRAL);
+//! strip.set_white(2700, 128).await?;
+//! ```
+//!
+//! WS2805 RGBCW chips with separate warm and cold white channels
+//! aren't supported yet.
+
+use smart_leds::{RGBW, White};
+
+use crate::correction::Correction;
+use crate::scale;
+
+/// White channel of an RGBW LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WhiteLed {
+    /// Color temperature of the white die in kelvin.
+    pub kelvin: u32,
+}
+
+impl Default for WhiteLed {
+    fn default() -> Self {
+        Self::NEUTRAL
+    }
+}
+
+impl WhiteLed {
+    /// Warm white die, about 3000 K.
+    pub const WARM: Self = Self::new(3000);
+    /// Neutral white die, about 4500 K.
+    pub const NEUTRAL: Self = Self::new(4500);
+    /// Cool white die, about 6500 K.
+    pub const COOL: Self = Self::new(6500);
+
+    /// White die with a color temperature of `kelvin`.
+    pub const fn new(kelvin: u32) -> Self {
+        Self { kelvin }
+    }
+
+    /// Color showing white at `cct` kelvin, scaled by `brightness`.
+    ///
+    /// Uses the black body curve of [Correction::from_kelvin] for both
+    /// the target and the die, so the tint is approximate.
+    pub fn mix(&self, cct: u32, brightness: u8) -> RGBW<u8> {
+        let target = Correction::from_kelvin(cct);
+        let native = Correction::from_kelvin(self.kelvin);
+        let target = [target.red, target.green, target.blue];
+        let native = [native.red, native.green, native.blue];
+        // Largest white level not exceeding the target on any channel
+        let white = target
+            .iter()
+            .zip(native)
+            .filter(|&(_, n)| n > 0)
+            .map(|(&t, n)| (t as u16 * 255 / n as u16).min(255) as u8)
+            .min()
+            .unwrap_or(255);
+        let [r, g, b] = core::array::from_fn(|i| {
+            let tint = target[i].saturating_sub(scale(native[i], white));
+            scale(tint, brightness)
+        });
+        RGBW {
+            r,
+            g,
+            b,
+            a: White(scale(white, brightness)),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! WS2812 driver using the I2S peripheral.
+//!
+//! Frees the PWM instances for motors or buzzers. The I2S master clock
+//! runs at 32 MHz / 10 with a ratio of 32, so SDOUT shifts 3.2 Mbit/s
+//! and every WS2812 bit is sent as 4 I2S bits, `1000` for a zero and
+//! `1110` for a one. That gives 312 ns and 937 ns high times in a
+//! 1.25 µs bit, within the WS2812B tolerances. The timing is fixed by
+//! the clock so [Timing](crate::timing::Timing) doesn't apply.
+//!
+//! Frames of any length are streamed through two buffers of 16 LEDs,
+//! a color byte takes 2 of their 16 bit samples. The MCK, SCK and LRCK
+//! outputs can't be disconnected, pass spare pins for them.
+//!
+//! ```ignore
+//! bind_interrupts!(struct Irqs {
+//!     I2S => i2s::InterruptHandler<peripherals::I2S>;
+//! });
+//!
+//! let mut ws = Ws2812::new(p.I2S, Irqs, p.P0_13, p.P0_28, p.P0_29, p.P0_30);
+//! ws.write(colors.iter().copied()).await?;
+//! ```
+
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use crate::scale;
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::i2s::{
+    self, Align, Channels, Config, I2S, InterruptHandler, MasterClock, MckFreq, MultiBuffering,
+    OutputStream, Ratio, SampleWidth,
+};
+use embassy_nrf::interrupt::typelevel::Binding;
+use smart_leds::{RGB8, SmartLedsWriteAsync};
+
+/// Samples of low output latching a frame, 300 µs at 3.2 Mbit/s.
+const RESET_SAMPLES: usize = 60;
+
+/// Samples of each buffer, 16 LEDs.
+const BUFFER_SAMPLES: usize = 16 * 6;
+
+/// I2S bits of the 4 WS2812 bits of every nibble, most significant first.
+const NIBBLES: [u16; 16] = {
+    let mut table = [0; 16];
+    let mut nibble = 0;
+    while nibble < 16 {
+        let mut bit = 0;
+        while bit < 4 {
+            let pattern = if nibble & (0x8 >> bit) != 0 {
+                0b1110
+            } else {
+                0b1000
+            };
+            table[nibble] |= pattern << (12 - 4 * bit);
+            bit += 1;
+        }
+        nibble += 1;
+    }
+    table
+};
+
+/// WS2812 driver on the I2S peripheral, streaming frames of any length.
+pub struct Ws2812<'d> {
+    stream: OutputStream<'d, i16, 2, BUFFER_SAMPLES>,
+    color_order: ColorOrder,
+    brightness: u8,
+    /// Whether a write was dropped with the stream running.
+    running: bool,
+}
+
+impl<'d> Ws2812<'d> {
+    /// Create a driver sending on `sdout`.
+    pub fn new<T: i2s::Instance>(
+        i2s: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        sdout: Peri<'d, impl Pin>,
+        mck: Peri<'d, impl Pin>,
+        sck: Peri<'d, impl Pin>,
+        lrck: Peri<'d, impl Pin>,
+    ) -> Self {
+        let mut config = Config::default();
+        config.sample_width = SampleWidth::_16bit;
+        config.align = Align::Left;
+        config.channels = Channels::Stereo;
+        let clock = MasterClock::new(MckFreq::_32MDiv10, Ratio::_32x);
+        let stream = I2S::new_master(i2s, irq, mck, sck, lrck, clock, config)
+            .output(sdout, MultiBuffering::new());
+        Self {
+            stream,
+            color_order: ColorOrder::default(),
+            brightness: u8::MAX,
+            running: false,
+        }
+    }
+
+    /// Change the order of the color channels, applied from the next write.
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.color_order = color_order;
+    }
+
+    /// Scale every color while encoding, applied from the next write.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Fill the next buffer, padding with low samples.
+    /// Returns the number of padding samples.
+    fn fill(&mut self, samples: &mut impl Iterator<Item = i16>) -> usize {
+        let buf = self.stream.buffer();
+        let mut len = 0;
+        for (slot, sample) in buf.iter_mut().zip(samples) {
+            *slot = sample;
+            len += 1;
+        }
+        buf[len..].fill(0);
+        BUFFER_SAMPLES - len
+    }
+}
+
+impl SmartLedsWriteAsync for Ws2812<'_> {
+    type Error = Error;
+    type Color = RGB8;
+
+    /// Send a frame, encoding it while it is sent.
+    ///
+    /// Dropping the future leaves the stream repeating its buffers
+    /// until the next write stops it, the strip may show a partial
+    /// frame until then.
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        if self.running {
+            self.stream.stop().await;
+        }
+        let (order, brightness) = (self.color_order, self.brightness);
+        let mut samples = iterator
+            .into_iter()
+            .flat_map(|color| order.bytes(color.into()))
+            .flat_map(|byte| {
+                let byte = scale(byte, brightness);
+                [NIBBLES[byte as usize >> 4], NIBBLES[byte as usize & 0xf]]
+            })
+            .map(|sample| sample as i16);
+
+        let mut reset = RESET_SAMPLES.saturating_sub(self.fill(&mut samples));
+        self.running = true;
+        self.stream.start().await?;
+        while reset > 0 {
+            reset = reset.saturating_sub(self.fill(&mut samples));
+            self.stream.send().await?;
+        }
+        // A send returns once the buffer before it has started, two more
+        // buffers of low samples make sure the last one has finished
+        for _ in 0..2 {
+            self.fill(&mut core::iter::empty());
+            self.stream.send().await?;
+        }
+        self.stream.stop().await;
+        self.running = false;
+        Ok(())
+    }
+}
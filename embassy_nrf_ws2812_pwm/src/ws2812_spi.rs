@@ -0,0 +1,223 @@
+//! WS2812 driver using a SPIM peripheral.
+//!
+//! For boards where every PWM instance is taken. MOSI shifts at 8 MHz
+//! and every WS2812 bit is sent as 10 SPI bits, `1110000000` for a zero
+//! and `1111110000` for a one. That gives 375 ns and 750 ns high times
+//! in a 1.25 µs bit, within the WS2812B tolerances for both the high
+//! and the low times. The timing is fixed by the clock so
+//! [Timing](crate::timing::Timing) doesn't apply.
+//!
+//! The [Buffer] holds 30 bytes per LED followed by the low bytes
+//! latching the frame. No SCK pin is used.
+//!
+//! ```ignore
+//! bind_interrupts!(struct Irqs {
+//!     SPI2 => spim::InterruptHandler<peripherals::SPI2>;
+//! });
+//!
+//! static BUF: StaticCell<ws2812_spi::Buffer<60>> = StaticCell::new();
+//! let buf = BUF.init(ws2812_spi::Buffer::new());
+//! let mut ws = ws2812_spi::Ws2812::new(p.SPI2, Irqs, p.P0_13, buf);
+//! ws.write(colors.iter().copied()).await?;
+//! ```
+
+use crate::color_order::ColorOrder;
+use crate::error::Error;
+use crate::scale;
+use embassy_nrf::Peri;
+use embassy_nrf::gpio::Pin;
+use embassy_nrf::interrupt::typelevel::Binding;
+use embassy_nrf::spim::{self, Config, Frequency, InterruptHandler, Spim};
+#[cfg(feature = "async")]
+use smart_leds::SmartLedsWriteAsync;
+use smart_leds::{RGB8, SmartLedsWrite};
+
+/// Low bytes latching a frame, 300 µs at 8 MHz.
+const RESET_BYTES: usize = 300;
+
+/// SPI bits of a zero and a one.
+const ZERO: u64 = 0b11100_00000;
+const ONE: u64 = 0b11111_10000;
+
+/// SPI bytes of the 4 WS2812 bits of every nibble, most significant first.
+const NIBBLES: [[u8; 5]; 16] = {
+    let mut table = [[0; 5]; 16];
+    let mut nibble = 0;
+    while nibble < 16 {
+        let mut bits = 0;
+        let mut bit = 0;
+        while bit < 4 {
+            let pattern = if nibble & (0x8 >> bit) != 0 {
+                ONE
+            } else {
+                ZERO
+            };
+            bits = bits << 10 | pattern;
+            bit += 1;
+        }
+        let bytes = bits.to_be_bytes();
+        table[nibble] = [bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]];
+        nibble += 1;
+    }
+    table
+};
+
+/// SPI bytes of a [Ws2812] driving `LEDS` LEDs.
+#[repr(C)]
+pub struct Buffer<const LEDS: usize> {
+    leds: [[u8; 30]; LEDS],
+    reset: [u8; RESET_BYTES],
+}
+
+impl<const LEDS: usize> Buffer<LEDS> {
+    /// Buffer holding the line low.
+    pub const fn new() -> Self {
+        Self {
+            leds: [[0; 30]; LEDS],
+            reset: [0; RESET_BYTES],
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `repr(C)` arrays of bytes are laid out without padding
+        unsafe { core::slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+impl<const LEDS: usize> Default for Buffer<LEDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// WS2812 driver on a SPIM peripheral for a strip of `LEDS` LEDs.
+pub struct Ws2812<'d, const LEDS: usize> {
+    spim: Spim<'d>,
+    buf: &'d mut Buffer<LEDS>,
+    color_order: ColorOrder,
+    brightness: u8,
+}
+
+impl<'d, const LEDS: usize> Ws2812<'d, LEDS> {
+    /// Create a driver sending on `mosi`.
+    pub fn new<T: spim::Instance>(
+        spim: Peri<'d, T>,
+        irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        mosi: Peri<'d, impl Pin>,
+        buf: &'d mut Buffer<LEDS>,
+    ) -> Self {
+        let mut config = Config::default();
+        config.frequency = Frequency::M8;
+        config.orc = 0;
+        buf.reset.fill(0);
+        Self {
+            spim: Spim::new_txonly_nosck(spim, irq, mosi, config),
+            buf,
+            color_order: ColorOrder::default(),
+            brightness: u8::MAX,
+        }
+    }
+
+    /// Change the order of the color channels, applied from the next write.
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.color_order = color_order;
+    }
+
+    /// Scale every color while encoding, applied from the next write.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Encode a frame, LEDs past the end of the colors are turned off.
+    fn encode(&mut self, mut colors: impl Iterator<Item = RGB8>) {
+        for chunk in self.buf.leds.iter_mut() {
+            let color = colors.next().unwrap_or_default();
+            let bytes = self.color_order.bytes(color);
+            encode_led(chunk, bytes.map(|byte| scale(byte, self.brightness)));
+        }
+    }
+}
+
+/// Encode the color bytes of one LED, in the order they are sent.
+fn encode_led(chunk: &mut [u8; 30], bytes: [u8; 3]) {
+    let nibbles = bytes.into_iter().flat_map(|byte| [byte >> 4, byte & 0xf]);
+    for (nibble, out) in nibbles.zip(chunk.chunks_exact_mut(5)) {
+        out.copy_from_slice(&NIBBLES[nibble as usize]);
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const LEDS: usize> SmartLedsWriteAsync for Ws2812<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.spim.write(self.buf.as_bytes()).await?)
+    }
+}
+
+impl<const LEDS: usize> SmartLedsWrite for Ws2812<'_, LEDS> {
+    type Error = Error;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.encode(iterator.into_iter().map(Into::into));
+        Ok(self.spim.blocking_write(self.buf.as_bytes())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::timing::Bounds;
+
+    /// Nanoseconds of one SPI bit at 8 MHz.
+    const BIT_NANOS: u32 = 125;
+
+    #[test]
+    fn buffer_holds_leds_then_reset() {
+        let mut buf = Buffer::<60>::new();
+        buf.leds[59] = [0xee; 30];
+        let bytes = buf.as_bytes();
+        assert_eq!(bytes.len(), 60 * 30 + RESET_BYTES);
+        assert_eq!(bytes[59 * 30..60 * 30], [0xee; 30]);
+        assert!(bytes[60 * 30..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn bits_within_ws2812b_bounds() {
+        // 0b01 in every channel, a zero followed by a one
+        let mut chunk = [0; 30];
+        encode_led(&mut chunk, [0x55; 3]);
+        let bits = chunk
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0));
+
+        // High and low time of every WS2812 bit from the SPI bits
+        let mut times = Vec::new();
+        let (mut high, mut low) = (0, 0);
+        for bit in bits {
+            if bit && low > 0 {
+                times.push((high * BIT_NANOS, low * BIT_NANOS));
+                (high, low) = (0, 0);
+            }
+            if bit { high += 1 } else { low += 1 }
+        }
+        times.push((high * BIT_NANOS, low * BIT_NANOS));
+
+        assert_eq!(times.len(), 24);
+        for pair in times.chunks_exact(2) {
+            assert!(Bounds::WS2812B.accepts(pair[0], pair[1]));
+        }
+    }
+}
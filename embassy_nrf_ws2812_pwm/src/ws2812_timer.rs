@@ -0,0 +1,335 @@
+//! WS2812 driver generating the waveform with TIMER, PPI and GPIOTE.
+//!
+//! For when the SoftDevice controller and the application already use
+//! the PWM, I2S and SPIM instances. A 16 MHz TIMER restarts every bit
+//! period, its compare events are routed through PPI to a GPIOTE
+//! channel that raises the pin at the start of a bit and lowers it at
+//! the end of the high time. The edges are timed by the hardware, the
+//! CPU only loads the high time of a bit into the compare register once
+//! the period of the previous one has wrapped. Loading it any earlier
+//! would let the compare fire a second time within the previous bit.
+//!
+//! That leaves the high time of a zero, about 400 ns, to load each bit,
+//! so the write blocks and must not be interrupted: wrap it in a
+//! critical section or run it from the highest priority. A late update
+//! stretches a bit and corrupts the rest of the frame, which suits a few
+//! status LEDs better than long strips.
+//!
+//! embassy-nrf doesn't expose the state of the compare events, so the
+//! driver polls them through the registers of the TIMER [Instance].
+
+use crate::color_order::ColorOrder;
+use crate::timing::Timing;
+use core::convert::Infallible;
+use embassy_nrf::gpio::{Level, Output, OutputDrive, Pin};
+use embassy_nrf::gpiote::{self, OutputChannel, OutputChannelPolarity};
+use embassy_nrf::ppi::{AnyConfigurableChannel, Ppi};
+use embassy_nrf::pwm::Prescaler;
+use embassy_nrf::timer::{self, Cc, Frequency, Timer};
+use embassy_nrf::{Peri, pac, peripherals};
+use smart_leds::{RGB8, SmartLedsWrite};
+
+mod sealed {
+    use embassy_nrf::pac;
+
+    pub trait Sealed {
+        fn regs() -> pac::timer::Timer;
+    }
+}
+
+/// TIMER instance the driver can poll the compare events of.
+pub trait Instance: timer::Instance + sealed::Sealed {}
+
+macro_rules! impl_instance {
+    ($type:ident) => {
+        impl sealed::Sealed for peripherals::$type {
+            fn regs() -> pac::timer::Timer {
+                pac::$type
+            }
+        }
+        impl Instance for peripherals::$type {}
+    };
+}
+
+impl_instance!(TIMER0);
+impl_instance!(TIMER1);
+impl_instance!(TIMER2);
+#[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+impl_instance!(TIMER3);
+#[cfg(any(feature = "nrf52832", feature = "nrf52833", feature = "nrf52840"))]
+impl_instance!(TIMER4);
+
+/// WS2812 driver on a TIMER, two PPI channels and a GPIOTE channel.
+pub struct Ws2812<'d> {
+    timer: Timer<'d>,
+    regs: pac::timer::Timer,
+    /// Ends the high time of a bit.
+    high: Cc<'d>,
+    out: OutputChannel<'d>,
+    /// Raises the pin at the start of each bit.
+    rise: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    _fall: Ppi<'d, AnyConfigurableChannel, 1, 1>,
+    timing: Timing,
+    color_order: ColorOrder,
+}
+
+impl<'d> Ws2812<'d> {
+    /// Create a driver using the default [Timing] (WS2812B).
+    pub fn new<T: Instance>(
+        timer: Peri<'d, T>,
+        channel: Peri<'d, impl gpiote::Channel>,
+        ppi: [Peri<'d, AnyConfigurableChannel>; 2],
+        pin: Peri<'d, impl Pin>,
+    ) -> Self {
+        Self::new_with_timing(timer, channel, ppi, pin, Timing::default())
+    }
+
+    /// Create a driver with custom timings.
+    pub fn new_with_timing<T: Instance>(
+        timer: Peri<'d, T>,
+        channel: Peri<'d, impl gpiote::Channel>,
+        ppi: [Peri<'d, AnyConfigurableChannel>; 2],
+        pin: Peri<'d, impl Pin>,
+        timing: Timing,
+    ) -> Self {
+        let timer = Timer::new(timer);
+        timer.set_frequency(Frequency::F16MHz);
+        let high = timer.cc(0);
+        let period = timer.cc(1);
+        period.write(timing.period_ticks(Prescaler::Div1) as u32);
+        period.short_compare_clear();
+
+        let output = Output::new(pin, Level::Low, OutputDrive::Standard);
+        let out = OutputChannel::new(channel, output, OutputChannelPolarity::Toggle);
+        let [rise, fall] = ppi;
+        let rise = Ppi::new_one_to_one(rise, period.event_compare(), out.task_set());
+        let mut fall = Ppi::new_one_to_one(fall, high.event_compare(), out.task_clr());
+        fall.enable();
+        Self {
+            timer,
+            regs: T::regs(),
+            high,
+            out,
+            rise,
+            _fall: fall,
+            timing,
+            color_order: ColorOrder::default(),
+        }
+    }
+
+    /// Change the order of the color channels, applied from the next write.
+    pub fn set_color_order(&mut self, color_order: ColorOrder) {
+        self.color_order = color_order;
+    }
+
+    /// Wait for the compare event of CC register `n` and clear it.
+    fn wait_compare(&self, n: usize) {
+        let event = self.regs.events_compare(n);
+        while event.read() == 0 {}
+        event.write_value(0);
+    }
+
+    /// Timer ticks of the high time of a bit.
+    fn high_ticks(&self, bit: bool) -> u32 {
+        if bit {
+            self.timing.one_ticks(Prescaler::Div1) as u32
+        } else {
+            self.timing.zero_ticks(Prescaler::Div1) as u32
+        }
+    }
+}
+
+/// Steps of a write on the compare registers, kept apart from the
+/// registers so the order of the loads can be checked on the host.
+trait Compare {
+    /// Load the high time of the first bit and start the timer.
+    fn start(&mut self, high: u32);
+    /// Wait for the period compare, the start of the next bit.
+    fn wait_period(&mut self);
+    /// Load the high time of the current bit into CC0.
+    fn load_high(&mut self, high: u32);
+    /// Stop raising the pin at the start of the next periods.
+    fn stop_rise(&mut self);
+    /// Stop the timer.
+    fn stop(&mut self);
+}
+
+impl Compare for Ws2812<'_> {
+    fn start(&mut self, high: u32) {
+        self.timer.stop();
+        self.timer.clear();
+        self.high.clear_events();
+        self.timer.cc(1).clear_events();
+        self.high.write(high);
+        self.rise.enable();
+        self.out.set();
+        self.timer.start();
+    }
+
+    fn wait_period(&mut self) {
+        self.wait_compare(1);
+    }
+
+    fn load_high(&mut self, high: u32) {
+        self.high.write(high);
+    }
+
+    fn stop_rise(&mut self) {
+        self.rise.disable();
+    }
+
+    fn stop(&mut self) {
+        self.timer.stop();
+    }
+}
+
+/// Send the high times of a frame, one per bit, then hold the reset.
+fn send(compare: &mut impl Compare, mut highs: impl Iterator<Item = u32>, reset_periods: u32) {
+    let Some(first) = highs.next() else {
+        return;
+    };
+    compare.start(first);
+    for high in highs {
+        // CC0 of the previous bit has fired, but a higher value loaded
+        // before the wrap would fire again within the same period
+        compare.wait_period();
+        compare.load_high(high);
+    }
+
+    // Keep the pin low from the end of the last bit on and hold it
+    // for the reset
+    compare.stop_rise();
+    compare.wait_period();
+    for _ in 0..reset_periods {
+        compare.wait_period();
+    }
+    compare.stop();
+}
+
+impl SmartLedsWrite for Ws2812<'_> {
+    type Error = Infallible;
+    type Color = RGB8;
+
+    /// Send a frame, see the [module docs](self) for the timing caveats.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let order = self.color_order;
+        let (zero, one) = (self.high_ticks(false), self.high_ticks(true));
+        let highs = iterator
+            .into_iter()
+            .flat_map(|color| order.bytes(color.into()))
+            .flat_map(|byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+            .map(|bit| if bit { one } else { zero });
+        send(self, highs, self.timing.reset_periods(Prescaler::Div1));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks the CPU takes to notice the wrap and load CC0.
+    const LOAD_TICKS: u32 = 2;
+
+    /// GPIOTE task triggered through PPI, with the tick it happened at.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Task {
+        Set(u32),
+        Clr(u32),
+    }
+
+    /// TIMER with CC0 ending the high time and CC1 clearing the
+    /// counter at the end of the period, routed to GPIOTE like the driver.
+    struct Sim {
+        period: u32,
+        cc0: u32,
+        counter: u32,
+        now: u32,
+        rise: bool,
+        tasks: Vec<Task>,
+    }
+
+    impl Sim {
+        fn new(timing: Timing) -> Self {
+            Self {
+                period: timing.period_ticks(Prescaler::Div1) as u32,
+                cc0: 0,
+                counter: 0,
+                now: 0,
+                rise: false,
+                tasks: Vec::new(),
+            }
+        }
+
+        fn tick(&mut self) {
+            self.counter += 1;
+            self.now += 1;
+            if self.counter == self.cc0 {
+                self.tasks.push(Task::Clr(self.now));
+            }
+        }
+    }
+
+    impl Compare for Sim {
+        fn start(&mut self, high: u32) {
+            self.cc0 = high;
+            self.rise = true;
+            self.tasks.push(Task::Set(self.now));
+        }
+
+        fn wait_period(&mut self) {
+            while self.counter < self.period {
+                self.tick();
+            }
+            self.counter = 0;
+            if self.rise {
+                self.tasks.push(Task::Set(self.now));
+            }
+        }
+
+        fn load_high(&mut self, high: u32) {
+            for _ in 0..LOAD_TICKS {
+                self.tick();
+            }
+            self.cc0 = high;
+        }
+
+        fn stop_rise(&mut self) {
+            self.rise = false;
+        }
+
+        fn stop(&mut self) {}
+    }
+
+    #[test]
+    fn zero_then_one_sets_and_clears_once_per_bit() {
+        let timing = Timing::WS2812B;
+        let zero = timing.zero_ticks(Prescaler::Div1) as u32;
+        let one = timing.one_ticks(Prescaler::Div1) as u32;
+        let period = timing.period_ticks(Prescaler::Div1) as u32;
+        let mut sim = Sim::new(timing);
+        send(&mut sim, [zero, one].into_iter(), 1);
+
+        // One set and one clear per bit, CC0 doesn't fire again within a bit
+        let frame = sim.tasks.iter().take_while(|task| match task {
+            Task::Set(tick) | Task::Clr(tick) => *tick < 2 * period,
+        });
+        assert!(frame.copied().eq([
+            Task::Set(0),
+            Task::Clr(zero),
+            Task::Set(period),
+            Task::Clr(period + one),
+        ]));
+        // Only clears follow, the pin stays low through the reset
+        assert!(
+            sim.tasks[4..]
+                .iter()
+                .all(|task| matches!(task, Task::Clr(_)))
+        );
+        assert_eq!(sim.now, 3 * period);
+    }
+}
@@ -2,42 +2,30 @@
 #![no_main]
 
 use embassy_executor::Spawner;
-use embassy_nrf_ws2812_pwm::Ws2812;
-use embassy_time::{Duration, Timer};
-use smart_leds::{
-    RGB8, SmartLedsWriteAsync as _, brightness,
-    hsv::{Hsv, hsv2rgb},
-};
-use static_cell::StaticCell;
+use embassy_nrf::{bind_interrupts, peripherals};
+use embassy_nrf_ws2812_pwm::clock::SystemClock;
+use embassy_nrf_ws2812_pwm::effects::{EffectRunner, Rainbow};
+use embassy_nrf_ws2812_pwm::{InterruptHandler, ws2812};
+use embassy_time::Duration;
 use {defmt_rtt as _, panic_probe as _};
 
 const NUM_LEDS: usize = 8;
-const BUFFER_SIZE: usize = NUM_LEDS * 24;
-static LED_BUFFER: StaticCell<[u16; BUFFER_SIZE]> = StaticCell::new();
+
+bind_interrupts!(struct Irqs {
+    PWM0 => InterruptHandler<peripherals::PWM0>;
+});
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_nrf::init(Default::default());
 
-    let buf = LED_BUFFER.init([0u16; BUFFER_SIZE]);
-    let mut ws: Ws2812<_> = Ws2812::new(p.PWM0, p.P0_14, buf);
-
-    let mut hue_offset = 0u8;
-    loop {
-        let mut colors = [RGB8::default(); 8];
+    let mut ws = ws2812!(p.PWM0, Irqs, p.P0_14, NUM_LEDS).expect("to create WS2812 driver");
 
-        for (i, color) in colors.iter_mut().enumerate() {
-            let hue = hue_offset.wrapping_add((i as u8) * 32);
-            let hsv = Hsv {
-                hue,
-                sat: 255,
-                val: 50, // Keep brightness reasonable
-            };
-            *color = hsv2rgb(hsv);
-        }
+    ws.set_brightness(64);
 
-        ws.write(brightness(colors.into_iter(), 64)).await.unwrap();
-        hue_offset = hue_offset.wrapping_add(4);
-        Timer::after(Duration::from_millis(25)).await;
-    }
+    // Keep brightness reasonable
+    let rainbow = Rainbow::new(32, Duration::from_millis(1600)).value(50);
+    let mut runner = EffectRunner::<_, NUM_LEDS>::new(SystemClock, rainbow, 40);
+    let error = runner.run(&mut ws).await;
+    panic!("failed to write the LEDs: {:?}", error);
 }
@@ -0,0 +1,7 @@
+MEMORY
+{
+  /* NOTE 1 K = 1 KiBi = 1024 bytes */
+  /* These values correspond to the NRF54L15 */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 1524K
+  RAM : ORIGIN = 0x20000000, LENGTH = 256K
+}
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+use embassy_executor::Spawner;
+use embassy_nrf::bind_interrupts;
+use embassy_nrf_ws2812_pwm::clock::SystemClock;
+use embassy_nrf_ws2812_pwm::effects::{EffectRunner, Rainbow};
+use embassy_nrf_ws2812_pwm::pwm::peripherals::PWM20;
+use embassy_nrf_ws2812_pwm::{InterruptHandler, ws2812};
+use embassy_time::Duration;
+use {defmt_rtt as _, panic_probe as _};
+
+const NUM_LEDS: usize = 8;
+
+bind_interrupts!(struct Irqs {
+    PWM20 => InterruptHandler<PWM20>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    // embassy-nrf doesn't hand out the PWM instances of the nRF54L.
+    // SAFETY: nothing else uses PWM20.
+    let pwm = unsafe { PWM20::steal() };
+    // PWM20 is in the PERI power domain, so is port 1.
+    let mut ws = ws2812!(pwm, Irqs, p.P1_04, NUM_LEDS).expect("to create WS2812 driver");
+
+    ws.set_brightness(64);
+
+    // Keep brightness reasonable
+    let rainbow = Rainbow::new(32, Duration::from_millis(1600)).value(50);
+    let mut runner = EffectRunner::<_, NUM_LEDS>::new(SystemClock, rainbow, 40);
+    let error = runner.run(&mut ws).await;
+    panic!("failed to write the LEDs: {:?}", error);
+}